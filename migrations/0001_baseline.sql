@@ -0,0 +1,27 @@
+-- Tables that existed before migrations were introduced. Databases created before then are
+-- brought up to this layout by Database::adopt_legacy_schema before migrations run.
+CREATE TABLE IF NOT EXISTS project_version (
+    path varchar(300),
+    name varchar(150),
+    created_at timestamp,
+    accessed_at timestamp,
+    modified_at timestamp,
+    description varchar(300),
+    backup_of varchar(150),
+    backed_up_at timestamp,
+    primary key (name, created_at)
+);
+
+CREATE TABLE IF NOT EXISTS version_tag (
+    version_path varchar(300),
+    tag varchar(100),
+    primary key (version_path, tag)
+);
+
+CREATE TABLE IF NOT EXISTS version_lineage (
+    project_path varchar(300),
+    version_path varchar(300),
+    parent_path varchar(300),
+    similarity real,
+    primary key (version_path)
+);
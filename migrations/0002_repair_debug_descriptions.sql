@@ -0,0 +1,6 @@
+-- Descriptions used to be written with Rust's Debug formatting, as `None` or `Some("...")`.
+UPDATE project_version SET description = NULL WHERE description = 'None';
+
+UPDATE project_version
+SET description = substr(description, 7, length(description) - 8)
+WHERE description LIKE 'Some("%")';
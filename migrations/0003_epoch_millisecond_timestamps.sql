@@ -0,0 +1,43 @@
+-- Timestamps were stored as the Debug text of a Duration since the epoch, e.g. '1686000000.123s'.
+-- Store them as integer milliseconds since the epoch, and record the project each version is in.
+-- At millisecond precision copies of a project collide on (name, created_at), so versions are
+-- keyed on their path instead.
+CREATE TABLE project_version_new (
+    path varchar(300) primary key,
+    name varchar(150),
+    project_path varchar(300),
+    created_at integer,
+    accessed_at integer,
+    modified_at integer,
+    description varchar(300),
+    backup_of varchar(150),
+    backed_up_at integer
+);
+
+INSERT OR REPLACE INTO project_version_new (path, name, project_path, created_at, accessed_at, modified_at, description, backup_of, backed_up_at)
+SELECT
+    path,
+    name,
+    CASE
+        WHEN backup_of IS NULL THEN rtrim(rtrim(path, replace(path, '/', '')), '/')
+        WHEN path LIKE '%/Backup/%' THEN rtrim(rtrim(
+            rtrim(rtrim(path, replace(path, '/', '')), '/'),
+            replace(rtrim(rtrim(path, replace(path, '/', '')), '/'), '/', '')
+        ), '/')
+        ELSE NULL
+    END,
+    CAST(round(CAST(rtrim(created_at, 's') AS REAL) * 1000) AS INTEGER),
+    CAST(round(CAST(rtrim(accessed_at, 's') AS REAL) * 1000) AS INTEGER),
+    CAST(round(CAST(rtrim(modified_at, 's') AS REAL) * 1000) AS INTEGER),
+    description,
+    backup_of,
+    CAST(round(CAST(rtrim(backed_up_at, 's') AS REAL) * 1000) AS INTEGER)
+FROM project_version;
+
+DROP TABLE project_version;
+
+ALTER TABLE project_version_new RENAME TO project_version;
+
+CREATE INDEX project_version_project_path ON project_version (project_path);
+
+CREATE INDEX project_version_modified_at ON project_version (modified_at);
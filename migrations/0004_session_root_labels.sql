@@ -0,0 +1,5 @@
+-- The label of the session root a version was scanned from, so projects on a drive that isn't
+-- mounted can be kept in the index and listings can be filtered by root.
+ALTER TABLE project_version ADD COLUMN root varchar(100);
+
+CREATE INDEX project_version_root ON project_version (root);
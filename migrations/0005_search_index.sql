@@ -0,0 +1,77 @@
+-- Text that `search` looks through: project, version and track names, descriptions, tags, clip
+-- and locator names and device and plugin names. Version names, descriptions and tags are kept in
+-- sync by triggers; the rest is written when a set is scanned.
+CREATE TABLE search_entry (
+    id integer PRIMARY KEY,
+    kind varchar(20) NOT NULL,
+    text text NOT NULL,
+    project_path varchar(250),
+    version_path varchar(250),
+    track varchar(150)
+);
+
+CREATE INDEX search_entry_project_path ON search_entry (project_path);
+
+CREATE INDEX search_entry_version_path ON search_entry (version_path);
+
+-- Trigrams match any part of a word, so `bass` finds `weird bass` and `Bassline`, and misspelt
+-- queries still share most of their trigrams with what they were meant to find.
+CREATE VIRTUAL TABLE search_entry_text USING fts5 (
+    text,
+    content = 'search_entry',
+    content_rowid = 'id',
+    tokenize = 'trigram'
+);
+
+CREATE TRIGGER search_entry_insert AFTER INSERT ON search_entry BEGIN
+    INSERT INTO search_entry_text (rowid, text) VALUES (new.id, new.text);
+END;
+
+CREATE TRIGGER search_entry_delete AFTER DELETE ON search_entry BEGIN
+    INSERT INTO search_entry_text (search_entry_text, rowid, text) VALUES ('delete', old.id, old.text);
+END;
+
+-- Backups are copies of a version, so only the versions themselves are searchable.
+CREATE TRIGGER project_version_search_insert AFTER INSERT ON project_version
+WHEN new.backup_of IS NULL BEGIN
+    INSERT INTO search_entry (kind, text, project_path, version_path)
+    VALUES ('version', new.name, new.project_path, new.path);
+    INSERT INTO search_entry (kind, text, project_path, version_path)
+    SELECT 'description', new.description, new.project_path, new.path
+    WHERE new.description IS NOT NULL;
+END;
+
+CREATE TRIGGER project_version_search_update AFTER UPDATE OF name, description, project_path ON project_version
+WHEN new.backup_of IS NULL BEGIN
+    DELETE FROM search_entry WHERE version_path = old.path AND kind IN ('version', 'description');
+    INSERT INTO search_entry (kind, text, project_path, version_path)
+    VALUES ('version', new.name, new.project_path, new.path);
+    INSERT INTO search_entry (kind, text, project_path, version_path)
+    SELECT 'description', new.description, new.project_path, new.path
+    WHERE new.description IS NOT NULL;
+END;
+
+CREATE TRIGGER project_version_search_delete AFTER DELETE ON project_version BEGIN
+    DELETE FROM search_entry WHERE version_path = old.path;
+END;
+
+CREATE TRIGGER version_tag_search_insert AFTER INSERT ON version_tag BEGIN
+    INSERT INTO search_entry (kind, text, project_path, version_path)
+    SELECT 'tag', new.tag, (SELECT project_path FROM project_version WHERE path = new.version_path),
+        new.version_path;
+END;
+
+CREATE TRIGGER version_tag_search_delete AFTER DELETE ON version_tag BEGIN
+    DELETE FROM search_entry WHERE kind = 'tag' AND version_path = old.version_path AND text = old.tag;
+END;
+
+INSERT INTO search_entry (kind, text, project_path, version_path)
+SELECT 'version', name, project_path, path FROM project_version WHERE backup_of IS NULL;
+
+INSERT INTO search_entry (kind, text, project_path, version_path)
+SELECT 'description', description, project_path, path FROM project_version
+WHERE backup_of IS NULL AND description IS NOT NULL;
+
+INSERT INTO search_entry (kind, text, project_path, version_path)
+SELECT 'tag', tag, (SELECT project_path FROM project_version WHERE path = version_path), version_path
+FROM version_tag;
@@ -0,0 +1,11 @@
+-- What a file looked like when it was last scanned, so rescans can skip files whose size and
+-- modified time haven't changed and recognise a moved or renamed set by its contents.
+ALTER TABLE project_version ADD COLUMN size integer;
+
+ALTER TABLE project_version ADD COLUMN content_hash varchar(64);
+
+-- Set when a scan no longer finds the file. Removed versions keep their description, tags and
+-- lineage in case the file comes back.
+ALTER TABLE project_version ADD COLUMN removed_at integer;
+
+CREATE INDEX project_version_content_hash ON project_version (content_hash);
@@ -0,0 +1,54 @@
+-- Projects as records of their own, so a project keeps its id, notes and versions when its folder
+-- is renamed or moved. `modified_at` and `version_count` summarise the project's versions that
+-- haven't been removed and are refreshed by every scan.
+CREATE TABLE project (
+    id integer PRIMARY KEY,
+    name varchar(150) NOT NULL,
+    path varchar(300) NOT NULL UNIQUE,
+    root varchar(100),
+    created_at integer,
+    modified_at integer,
+    version_count integer NOT NULL DEFAULT 0,
+    notes text,
+    removed_at integer
+);
+
+CREATE INDEX project_root ON project (root);
+
+ALTER TABLE project_version ADD COLUMN project_id integer REFERENCES project (id);
+
+CREATE INDEX project_version_project_id ON project_version (project_id);
+
+-- A project is named after its folder without Live's ` Project` suffix.
+WITH project_directory AS (
+    SELECT
+        project_path AS path,
+        replace(project_path, rtrim(project_path, replace(project_path, '/', '')), '') AS directory,
+        max(root) AS root,
+        min(created_at) AS created_at
+    FROM project_version
+    WHERE project_path IS NOT NULL
+    GROUP BY project_path
+)
+INSERT INTO project (name, path, root, created_at)
+SELECT
+    CASE WHEN directory LIKE '% Project' THEN substr(directory, 1, length(directory) - 8) ELSE directory END,
+    path,
+    root,
+    created_at
+FROM project_directory;
+
+UPDATE project_version SET project_id = (SELECT id FROM project WHERE project.path = project_version.project_path);
+
+UPDATE project SET
+    version_count = (
+        SELECT count(*) FROM project_version
+        WHERE project_id = project.id AND backup_of IS NULL AND removed_at IS NULL
+    ),
+    modified_at = (
+        SELECT max(modified_at) FROM project_version
+        WHERE project_id = project.id AND removed_at IS NULL
+    );
+
+UPDATE project SET removed_at = (SELECT max(removed_at) FROM project_version WHERE project_id = project.id)
+WHERE NOT EXISTS (SELECT 1 FROM project_version WHERE project_id = project.id AND removed_at IS NULL);
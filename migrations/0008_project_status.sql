@@ -0,0 +1,14 @@
+-- Where each project is at, and a checklist of what's left to do on it.
+ALTER TABLE project ADD COLUMN status varchar(20);
+
+CREATE INDEX project_status ON project (status);
+
+CREATE TABLE project_todo (
+    id integer PRIMARY KEY,
+    project_id integer NOT NULL REFERENCES project (id),
+    text text NOT NULL,
+    done_at integer,
+    created_at integer NOT NULL
+);
+
+CREATE INDEX project_todo_project_id ON project_todo (project_id);
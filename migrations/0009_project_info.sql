@@ -0,0 +1,8 @@
+-- What a project's `Ableton Project Info` folder says about it. Folders of sets without one are
+-- loose sets rather than projects Live created. Existing projects are taken to have one until
+-- they're scanned again.
+ALTER TABLE project ADD COLUMN loose boolean NOT NULL DEFAULT 0;
+
+ALTER TABLE project ADD COLUMN project_format varchar(20);
+
+ALTER TABLE project ADD COLUMN preview_path varchar(300);
@@ -0,0 +1,14 @@
+-- Racks, presets, clips and grooves found while scanning, kept apart from the versions of sets.
+CREATE TABLE library_file (
+    path varchar(300) PRIMARY KEY,
+    kind varchar(20) NOT NULL,
+    name varchar(150) NOT NULL,
+    root varchar(100),
+    modified_at integer NOT NULL,
+    size integer,
+    content_hash varchar(64),
+    creator varchar(100),
+    removed_at integer
+);
+
+CREATE INDEX library_file_kind ON library_file (kind);
@@ -0,0 +1,13 @@
+-- The rack and preset files the devices in each indexed set were loaded from, to tell which
+-- library files are used where. Sets indexed before this have no record of their presets, so
+-- their hashes are cleared to have the next scan read them again.
+CREATE TABLE preset_use (
+    version_path varchar(300) NOT NULL,
+    preset_path varchar(300) NOT NULL,
+    device varchar(150) NOT NULL,
+    track varchar(150)
+);
+
+CREATE INDEX preset_use_version_path ON preset_use (version_path);
+
+UPDATE project_version SET content_hash = NULL WHERE backup_of IS NULL;
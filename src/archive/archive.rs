@@ -0,0 +1,791 @@
+use crate::cli::output::{progress_bar, status};
+use crate::config::config::SessionRoot;
+use crate::parser::structs::file_kind::FileKind;
+use crate::parser::structs::live_set::SampleReference;
+use crate::project::disk::project_files;
+use crate::project::project::{AbletonProjectDirectory, SAMPLES_DIRECTORY};
+use crate::project::samples::SampleReferences;
+use chrono::{DateTime, Datelike, Local, Timelike};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+use xml::reader::{EventReader, XmlEvent};
+use xml::EmitterConfig;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+pub const ARCHIVE_EXTENSION: &str = "zip";
+/// The manifest at the top of an archive, next to the project's folder.
+pub const ARCHIVE_MANIFEST: &str = "ableton-v.json";
+/// The checksums at the top of an archive, in the format `sha256sum -c` checks.
+pub const CHECKSUMS_FILE: &str = "SHA256SUMS";
+/// The version of the manifest's format.
+const ARCHIVE_FORMAT: u32 = 1;
+/// Where samples from outside a project are collected to, as Live's Collect All and Save does.
+const COLLECTED_DIRECTORY: &str = "Imported";
+/// Formats that are compressed already, which are stored as they are. Live's own files are gzipped.
+const COMPRESSED_EXTENSIONS: [&str; 4] = ["flac", "ogg", "mp3", "m4a"];
+/// Files this large are written with Zip64 fields. It's below the 4 GB limit, so a file that grows
+/// a little while it's archived still fits.
+const ZIP64_THRESHOLD: u64 = 0xF000_0000;
+
+/// An archive opened for reading.
+pub type ArchiveReader = ZipArchive<BufReader<File>>;
+
+/// What an archive holds: the project's versions, where the samples from outside it went, and a
+/// checksum of every file.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveManifest {
+    pub format: u32,
+    pub project: String,
+    /// The name of the project's folder, which the archive keeps its files in.
+    pub directory: String,
+    /// Where the project's folder was when it was archived.
+    #[serde(default)]
+    pub source: Option<PathBuf>,
+    pub archived_at: DateTime<Local>,
+    pub versions: Vec<ArchivedVersion>,
+    pub collected_samples: Vec<CollectedSample>,
+    /// Sample references that couldn't be found, as the sets store them.
+    pub missing_samples: Vec<String>,
+    pub files: Vec<FileChecksum>,
+}
+
+impl ArchiveManifest {
+    /// Where the file at `path` in the project's folder is in the archive.
+    pub fn entry_name(&self, path: &str) -> String {
+        format!("{}/{}", self.directory, path)
+    }
+}
+
+impl Display for ArchiveManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let backups = self
+            .versions
+            .iter()
+            .filter(|version| version.backup)
+            .count();
+        write!(
+            f,
+            "{} ({} versions, {} backups, {} files, {} samples collected",
+            self.project,
+            self.versions.len() - backups,
+            backups,
+            self.files.len(),
+            self.collected_samples.len()
+        )?;
+        if !self.missing_samples.is_empty() {
+            write!(f, ", {} missing", self.missing_samples.len())?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// A set or backup in an archived project.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchivedVersion {
+    /// The set's path in the project's folder, with forward slashes.
+    pub path: String,
+    pub name: String,
+    pub backup: bool,
+    pub modified_at: DateTime<Local>,
+}
+
+/// A sample from outside the project that was copied into it.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CollectedSample {
+    pub original: PathBuf,
+    /// The copy's path in the project's folder, with forward slashes.
+    pub path: String,
+}
+
+/// A file's size and SHA-256 when it was archived.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileChecksum {
+    /// The file's path in the project's folder, with forward slashes.
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+    pub modified_at: DateTime<Local>,
+}
+
+/// Packs the project's files, and the samples its sets and backups use from outside it, into a
+/// zip archive at `output`, with a manifest and checksums. The archive is written next to `output`
+/// and only moved there once it's complete.
+pub fn archive_project(
+    project: &AbletonProjectDirectory,
+    output: &Path,
+) -> anyhow::Result<ArchiveManifest> {
+    if output.exists() {
+        anyhow::bail!("{} already exists", output.display());
+    }
+    let path = fs::canonicalize(&project.path)?;
+    let directory = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow::anyhow!("{} has no usable folder name", path.display()))?
+        .to_string();
+
+    let mut sources: Vec<(PathBuf, String)> = project_files(project, &path)
+        .into_iter()
+        .filter_map(|file| {
+            let relative = archive_path(file.strip_prefix(&path).ok()?)?;
+            Some((file, relative))
+        })
+        .collect();
+    sources.sort_by(|a, b| a.1.cmp(&b.1));
+    let mut taken: HashSet<String> = sources
+        .iter()
+        .map(|(_, relative)| relative.to_lowercase())
+        .collect();
+
+    let references = SampleReferences::read(project);
+    for set in &references.unreadable {
+        status!(
+            "warning: couldn't read {}, so samples only it uses from outside the project aren't collected",
+            set.display()
+        );
+    }
+    let mut external: Vec<&PathBuf> = references.external(&path).collect();
+    external.sort();
+    let mut collected_samples = vec![];
+    for sample in external {
+        let relative = collected_path(sample, &taken);
+        taken.insert(relative.to_lowercase());
+        sources.push((sample.clone(), relative.clone()));
+        collected_samples.push(CollectedSample {
+            original: sample.clone(),
+            path: relative,
+        });
+    }
+    let mut missing_samples: Vec<String> = references.missing.into_iter().collect();
+    missing_samples.sort();
+    for sample in &missing_samples {
+        status!("warning: {} can't be found, so it isn't archived", sample);
+    }
+
+    let versions = project
+        .versions
+        .iter()
+        .chain(&project.backups)
+        .filter_map(|version| {
+            Some(ArchivedVersion {
+                path: archive_path(version.path.strip_prefix(&project.path).ok()?)?,
+                name: version.name.clone(),
+                backup: version.backup_of.is_some(),
+                modified_at: DateTime::from(version.modified_at),
+            })
+        })
+        .collect();
+    let mut manifest = ArchiveManifest {
+        format: ARCHIVE_FORMAT,
+        project: project.name.clone(),
+        directory,
+        source: Some(path.clone()),
+        archived_at: Local::now(),
+        versions,
+        collected_samples,
+        missing_samples,
+        files: vec![],
+    };
+
+    let mut partial = OsString::from(output.as_os_str());
+    partial.push(".partial");
+    let partial = PathBuf::from(partial);
+    let written = write_archive(&mut manifest, &sources, &partial);
+    if let Err(error) = written {
+        let _ = fs::remove_file(&partial);
+        return Err(error);
+    }
+    fs::rename(&partial, output)?;
+    Ok(manifest)
+}
+
+fn write_archive(
+    manifest: &mut ArchiveManifest,
+    sources: &[(PathBuf, String)],
+    path: &Path,
+) -> anyhow::Result<()> {
+    let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+    let mut zip = ZipWriter::new(BufWriter::new(file));
+    let progress = progress_bar(sources.len() as u64, "archiving files");
+    for (source, relative) in sources {
+        let metadata = fs::metadata(source)?;
+        let modified_at = metadata.modified()?;
+        let relinked = match FileKind::from_path(source) {
+            Some(FileKind::Set) if !manifest.collected_samples.is_empty() => {
+                relink_samples(source, manifest).unwrap_or_else(|error| {
+                    status!(
+                        "warning: couldn't point {} at the samples collected from outside the project: {}",
+                        source.display(),
+                        error
+                    );
+                    None
+                })
+            }
+            _ => None,
+        };
+        let (inner, size): (Box<dyn Read>, u64) = match relinked {
+            Some(contents) => {
+                let size = contents.len() as u64;
+                (Box::new(io::Cursor::new(contents)), size)
+            }
+            None => (Box::new(File::open(source)?), metadata.len()),
+        };
+        let mut reader = HashingReader {
+            inner,
+            hasher: Sha256::new(),
+        };
+        let options = file_options(modified_at, size, is_compressible(source));
+        let size = zip
+            .start_file(manifest.entry_name(relative), options)
+            .map_err(anyhow::Error::from)
+            .and_then(|()| Ok(io::copy(&mut reader, &mut zip)?))
+            .map_err(|error| error.context(format!("couldn't archive {}", source.display())))?;
+        manifest.files.push(FileChecksum {
+            path: relative.clone(),
+            size,
+            sha256: format!("{:x}", reader.hasher.finalize()),
+            modified_at: DateTime::from(modified_at),
+        });
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+
+    let now: SystemTime = manifest.archived_at.into();
+    let contents = serde_json::to_vec_pretty(manifest)?;
+    zip.start_file(
+        ARCHIVE_MANIFEST,
+        file_options(now, contents.len() as u64, true),
+    )?;
+    zip.write_all(&contents)?;
+    let checksums: String = manifest
+        .files
+        .iter()
+        .map(|file| format!("{}  {}\n", file.sha256, manifest.entry_name(&file.path)))
+        .collect();
+    zip.start_file(
+        CHECKSUMS_FILE,
+        file_options(now, checksums.len() as u64, true),
+    )?;
+    zip.write_all(checksums.as_bytes())?;
+    let file = zip
+        .finish()?
+        .into_inner()
+        .map_err(|error| error.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// The set at `source` with the references to samples that were collected from outside the
+/// project pointed at their copies in `Samples/Imported`, gzipped as Live saves it. Live stores
+/// relative paths from the project's folder, for backups too. None if it uses none of them.
+fn relink_samples(source: &Path, manifest: &ArchiveManifest) -> anyhow::Result<Option<Vec<u8>>> {
+    let reader = EventReader::new(BufReader::new(GzDecoder::new(File::open(source)?)));
+    let mut writer = EmitterConfig::new()
+        .perform_indent(false)
+        .create_writer(GzEncoder::new(vec![], Compression::default()));
+    let mut stack: Vec<String> = vec![];
+    // A sample's `FileRef` is held back until both of its paths have been read.
+    let mut file_ref: Option<Vec<XmlEvent>> = None;
+    let mut relinked = false;
+    for event in reader {
+        let event = event?;
+        let mut ended = false;
+        match &event {
+            XmlEvent::StartElement { name, .. } => {
+                if name.local_name == "FileRef"
+                    && stack.last().map(String::as_str) == Some("SampleRef")
+                {
+                    file_ref = Some(vec![]);
+                }
+                stack.push(name.local_name.clone());
+            }
+            XmlEvent::EndElement { name } => {
+                stack.pop();
+                ended = name.local_name == "FileRef"
+                    && stack.last().map(String::as_str) == Some("SampleRef");
+            }
+            _ => {}
+        }
+        match file_ref.as_mut() {
+            Some(events) => events.push(event),
+            None => {
+                if let Some(event) = event.as_writer_event() {
+                    writer.write(event)?;
+                }
+            }
+        }
+        if ended {
+            let mut events = file_ref.take().unwrap_or_default();
+            relinked |= relink_file_ref(&mut events, manifest);
+            for event in events.iter().filter_map(XmlEvent::as_writer_event) {
+                writer.write(event)?;
+            }
+        }
+    }
+    if !relinked {
+        return Ok(None);
+    }
+    Ok(Some(writer.into_inner().finish()?))
+}
+
+/// Points a sample's `FileRef` at the sample's copy, if it was collected. Returns whether it was.
+fn relink_file_ref(events: &mut [XmlEvent], manifest: &ArchiveManifest) -> bool {
+    let Some(project_path) = manifest.source.as_ref() else {
+        return false;
+    };
+    let value = |element: &str| {
+        events.iter().find_map(|event| match event {
+            XmlEvent::StartElement {
+                name, attributes, ..
+            } if name.local_name == element => attributes
+                .iter()
+                .find(|attribute| attribute.name.local_name == "Value")
+                .map(|attribute| attribute.value.clone())
+                .filter(|value| !value.is_empty()),
+            _ => None,
+        })
+    };
+    let sample = SampleReference {
+        path: value("Path"),
+        relative_path: value("RelativePath"),
+    };
+    let Some(original) = sample.resolve(project_path) else {
+        return false;
+    };
+    let Some(collected) = manifest
+        .collected_samples
+        .iter()
+        .find(|collected| collected.original == original)
+    else {
+        return false;
+    };
+    for event in events {
+        if let XmlEvent::StartElement {
+            name, attributes, ..
+        } = event
+        {
+            let value = match name.local_name.as_str() {
+                "RelativePath" => collected.path.clone(),
+                "Path" => project_path.join(&collected.path).display().to_string(),
+                _ => continue,
+            };
+            for attribute in attributes
+                .iter_mut()
+                .filter(|attribute| attribute.name.local_name == "Value")
+            {
+                attribute.value = value.clone();
+            }
+        }
+    }
+    true
+}
+
+/// How a file of `size` bytes is stored, deflated if `compress`. Zip keeps times as MS-DOS does,
+/// in local time from 1980, so earlier times are left out.
+fn file_options(modified_at: SystemTime, size: u64, compress: bool) -> FileOptions {
+    let options = FileOptions::default()
+        .compression_method(if compress {
+            CompressionMethod::Deflated
+        } else {
+            CompressionMethod::Stored
+        })
+        .unix_permissions(0o644)
+        .large_file(size >= ZIP64_THRESHOLD);
+    let time = DateTime::<Local>::from(modified_at).naive_local();
+    match zip::DateTime::from_date_and_time(
+        time.year().try_into().unwrap_or(0),
+        time.month() as u8,
+        time.day() as u8,
+        time.hour() as u8,
+        time.minute() as u8,
+        time.second() as u8,
+    ) {
+        Ok(time) => options.last_modified_time(time),
+        Err(()) => options,
+    }
+}
+
+/// Opens the archive at `path` for reading.
+pub fn open_archive(path: &Path) -> anyhow::Result<ArchiveReader> {
+    Ok(ZipArchive::new(BufReader::new(File::open(path)?))?)
+}
+
+/// Writes the contents of the archive's file `name` to `writer`, returning their size. The zip
+/// crate fails the read if they don't match the CRC-32 the archive stored for them.
+pub fn extract(zip: &mut ArchiveReader, name: &str, writer: &mut dyn Write) -> anyhow::Result<u64> {
+    let mut entry = match zip.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => anyhow::bail!("{} is missing from the archive", name),
+        Err(error) => return Err(error.into()),
+    };
+    Ok(io::copy(&mut entry, writer)?)
+}
+
+/// Reads an archive's manifest, checking that the paths in it stay inside the project's folder.
+pub fn read_manifest(zip: &mut ArchiveReader) -> anyhow::Result<ArchiveManifest> {
+    let mut contents = vec![];
+    extract(zip, ARCHIVE_MANIFEST, &mut contents)?;
+    let manifest: ArchiveManifest = serde_json::from_slice(&contents)?;
+    if manifest.format > ARCHIVE_FORMAT {
+        anyhow::bail!(
+            "the archive was made by a newer version of ableton-v (format {})",
+            manifest.format
+        );
+    }
+    if archive_path(Path::new(&manifest.directory)).is_none_or(|path| path.contains('/')) {
+        anyhow::bail!(
+            "the archive's folder {:?} isn't a folder name",
+            manifest.directory
+        );
+    }
+    if let Some(file) = manifest
+        .files
+        .iter()
+        .find(|file| archive_path(Path::new(&file.path)).as_ref() != Some(&file.path))
+    {
+        anyhow::bail!("the archive's file {:?} isn't a path inside it", file.path);
+    }
+    Ok(manifest)
+}
+
+/// Unpacks the project in the archive at `archive` into `root`, checking every file against its
+/// checksum and giving it back the time it was modified. The files are unpacked into a hidden
+/// folder that's only renamed to the project's once they've all been checked.
+pub fn unarchive_project(
+    archive: &Path,
+    root: &SessionRoot,
+) -> anyhow::Result<(PathBuf, ArchiveManifest)> {
+    let mut zip = open_archive(archive)?;
+    let manifest = read_manifest(&mut zip)?;
+    let target = root.path.join(&manifest.directory);
+    if target.exists() {
+        anyhow::bail!("{} already exists", target.display());
+    }
+    let staging = root
+        .path
+        .join(format!(".{}.unarchiving", manifest.directory));
+    if staging.exists() {
+        anyhow::bail!(
+            "{} is left from an earlier unarchive; remove it first",
+            staging.display()
+        );
+    }
+    let extracted = extract_files(&mut zip, &manifest, &staging);
+    if let Err(error) = extracted {
+        let _ = fs::remove_dir_all(&staging);
+        return Err(error);
+    }
+    fs::rename(&staging, &target)?;
+    Ok((target, manifest))
+}
+
+fn extract_files(
+    zip: &mut ArchiveReader,
+    manifest: &ArchiveManifest,
+    directory: &Path,
+) -> anyhow::Result<()> {
+    let progress = progress_bar(manifest.files.len() as u64, "unarchiving files");
+    for file in &manifest.files {
+        let name = manifest.entry_name(&file.path);
+        let path = directory.join(&file.path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut writer = HashingWriter::new(BufWriter::new(File::create(&path)?));
+        let size = extract(zip, &name, &mut writer)?;
+        let (unpacked, sha256) = writer.finish();
+        if size != file.size || sha256 != file.sha256 {
+            anyhow::bail!("{} doesn't match its checksum in the manifest", name);
+        }
+        let unpacked = unpacked.into_inner().map_err(|error| error.into_error())?;
+        unpacked.set_modified(file.modified_at.into())?;
+        unpacked.sync_all()?;
+        progress.inc(1);
+    }
+    progress.finish_and_clear();
+    Ok(())
+}
+
+/// A path with forward slashes, or None if it isn't a plain relative path.
+pub fn archive_path(path: &Path) -> Option<String> {
+    let parts: Option<Vec<&str>> = path
+        .components()
+        .map(|component| match component {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect();
+    Some(parts?.join("/")).filter(|path| !path.is_empty())
+}
+
+/// Where a sample from outside the project goes in `Samples/Imported`, numbered like Live numbers
+/// copies if another file already has its name.
+fn collected_path(sample: &Path, taken: &HashSet<String>) -> String {
+    let stem = sample
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = sample
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    (1..)
+        .map(|number| {
+            let name = match number {
+                1 => format!("{}{}", stem, extension),
+                number => format!("{} {}{}", stem, number, extension),
+            };
+            format!("{}/{}/{}", SAMPLES_DIRECTORY, COLLECTED_DIRECTORY, name)
+        })
+        .find(|path| !taken.contains(&path.to_lowercase()))
+        .expect("a free name")
+}
+
+fn is_compressible(path: &Path) -> bool {
+    let compressed = FileKind::from_path(path).is_some()
+        || path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                COMPRESSED_EXTENSIONS
+                    .iter()
+                    .any(|compressed| extension.eq_ignore_ascii_case(compressed))
+            });
+    !compressed
+}
+
+/// Hashes what's read through it.
+struct HashingReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Hashes what's written through it.
+pub struct HashingWriter<W: Write> {
+    pub inner: W,
+    pub hasher: Sha256,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W) -> HashingWriter<W> {
+        HashingWriter {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// The writer, and a hex SHA-256 of everything written through it.
+    pub fn finish(self) -> (W, String) {
+        (self.inner, format!("{:x}", self.hasher.finalize()))
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::verify::{verify_archive, verify_project, Problem};
+    use crate::parser::als::AbletonXmlParser;
+    use crate::project::samples::tests::write_set;
+
+    /// A project with one set using a recorded sample inside it.
+    fn project_in(dir: &Path) -> AbletonProjectDirectory {
+        let path = dir.join("Song Project");
+        let sample = path.join("Samples/Recorded/take.mp3");
+        fs::create_dir_all(sample.parent().unwrap()).unwrap();
+        fs::write(
+            &sample,
+            "a recorded take that is stored as it is".repeat(10),
+        )
+        .unwrap();
+        write_set(
+            &path.join("Song.als"),
+            &[(&sample, "Samples/Recorded/take.mp3")],
+        );
+        AbletonProjectDirectory::new(fs::canonicalize(&path).unwrap(), None)
+    }
+
+    #[test]
+    fn archived_projects_unarchive_intact() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = project_in(dir.path());
+        let archive = dir.path().join("Song Project.zip");
+        let manifest = archive_project(&project, &archive).unwrap();
+        assert_eq!(manifest.files.len(), 2);
+        assert!(!dir.path().join("Song Project.zip.partial").exists());
+
+        let report = verify_archive(&archive).unwrap();
+        assert_eq!(report.failures(), 0, "{:?}", report.problems);
+        assert_eq!((report.files, report.sets, report.samples), (2, 1, 1));
+
+        let root = SessionRoot::new(None, dir.path().join("restored"));
+        fs::create_dir_all(&root.path).unwrap();
+        let (unpacked, _) = unarchive_project(&archive, &root).unwrap();
+        assert_eq!(unpacked, root.path.join("Song Project"));
+        for file in ["Song.als", "Samples/Recorded/take.mp3"] {
+            let original = project.path.join(file);
+            let copy = unpacked.join(file);
+            assert_eq!(fs::read(&copy).unwrap(), fs::read(&original).unwrap());
+            assert_eq!(
+                fs::metadata(&copy).unwrap().modified().unwrap(),
+                fs::metadata(&original).unwrap().modified().unwrap()
+            );
+        }
+        let restored = AbletonProjectDirectory::new(unpacked, None);
+        let report = verify_project(&restored, false).unwrap();
+        assert_eq!(report.failures(), 0, "{:?}", report.problems);
+        // An existing folder is never overwritten.
+        assert!(unarchive_project(&archive, &root).is_err());
+    }
+
+    #[test]
+    fn archived_sets_use_the_samples_collected_into_them() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = project_in(dir.path());
+        let kick = dir.path().join("Elsewhere/kick.wav");
+        fs::create_dir_all(kick.parent().unwrap()).unwrap();
+        fs::write(&kick, "a kick from outside the project").unwrap();
+        let kick = fs::canonicalize(&kick).unwrap();
+        let take = project.path.join("Samples/Recorded/take.mp3");
+        write_set(
+            &project.path.join("Song.als"),
+            &[
+                (&take, "Samples/Recorded/take.mp3"),
+                (&kick, "../Elsewhere/kick.wav"),
+            ],
+        );
+        fs::create_dir_all(project.path.join("Backup")).unwrap();
+        write_set(
+            &project.path.join("Backup/Song [2023-06-01 153012].als"),
+            &[(&kick, "../Elsewhere/kick.wav")],
+        );
+        let project = AbletonProjectDirectory::new(project.path.clone(), None);
+        let archive = dir.path().join("Song Project.zip");
+        let manifest = archive_project(&project, &archive).unwrap();
+        assert_eq!(manifest.collected_samples.len(), 1);
+        assert_eq!(
+            manifest.collected_samples[0].path,
+            "Samples/Imported/kick.wav"
+        );
+
+        let report = verify_archive(&archive).unwrap();
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!(report.sets, 2);
+
+        let root = SessionRoot::new(None, dir.path().join("restored"));
+        fs::create_dir_all(&root.path).unwrap();
+        let (unpacked, _) = unarchive_project(&archive, &root).unwrap();
+        for set in ["Song.als", "Backup/Song [2023-06-01 153012].als"] {
+            let contents = AbletonXmlParser::new()
+                .read_contents(File::open(unpacked.join(set)).unwrap())
+                .unwrap();
+            let kick = contents.samples.last().unwrap();
+            assert_eq!(
+                kick.relative_path.as_deref(),
+                Some("Samples/Imported/kick.wav")
+            );
+            assert_eq!(
+                kick.path.as_deref().map(Path::new),
+                Some(project.path.join("Samples/Imported/kick.wav").as_path())
+            );
+        }
+        fs::remove_dir_all(dir.path().join("Elsewhere")).unwrap();
+        let report = verify_project(&AbletonProjectDirectory::new(unpacked, None), false).unwrap();
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+    }
+
+    #[test]
+    fn damaged_archives_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = project_in(dir.path());
+        let archive = dir.path().join("Song Project.zip");
+        archive_project(&project, &archive).unwrap();
+        let mut bytes = fs::read(&archive).unwrap();
+        let take = bytes
+            .windows(10)
+            .position(|window| window == b"a recorded")
+            .unwrap();
+        bytes[take] ^= 1;
+        fs::write(&archive, bytes).unwrap();
+
+        let report = verify_archive(&archive).unwrap();
+        assert!(matches!(
+            report.problems.as_slice(),
+            [Problem::Corrupt { path }] if path == "Samples/Recorded/take.mp3"
+        ));
+        let root = SessionRoot::new(None, dir.path().join("restored"));
+        fs::create_dir_all(&root.path).unwrap();
+        assert!(unarchive_project(&archive, &root).is_err());
+        assert!(fs::read_dir(&root.path).unwrap().next().is_none());
+    }
+
+    #[test]
+    fn manifests_with_paths_outside_the_archive_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        for (directory, path) in [("Song Project", "../../escaped"), ("..", "escaped")] {
+            let manifest = ArchiveManifest {
+                format: ARCHIVE_FORMAT,
+                project: String::from("Song"),
+                directory: String::from(directory),
+                source: None,
+                archived_at: Local::now(),
+                versions: vec![],
+                collected_samples: vec![],
+                missing_samples: vec![],
+                files: vec![FileChecksum {
+                    path: String::from(path),
+                    size: 0,
+                    sha256: format!("{:x}", Sha256::digest(b"")),
+                    modified_at: Local::now(),
+                }],
+            };
+            let archive = dir.path().join("evil.zip");
+            let mut zip = ZipWriter::new(File::create(&archive).unwrap());
+            zip.start_file(ARCHIVE_MANIFEST, FileOptions::default())
+                .unwrap();
+            zip.write_all(&serde_json::to_vec(&manifest).unwrap())
+                .unwrap();
+            zip.start_file(manifest.entry_name(path), FileOptions::default())
+                .unwrap();
+            zip.finish().unwrap();
+
+            let root = SessionRoot::new(None, dir.path().join("root"));
+            fs::create_dir_all(&root.path).unwrap();
+            assert!(unarchive_project(&archive, &root).is_err());
+            assert!(!dir.path().join("escaped").exists());
+            assert!(verify_archive(&archive).unwrap().failures() > 0);
+            fs::remove_file(&archive).unwrap();
+        }
+    }
+}
@@ -0,0 +1,557 @@
+use crate::archive::archive::{
+    archive_path, open_archive, read_manifest, ArchiveManifest, FileChecksum, HashingWriter,
+};
+use crate::cli::output::progress_bar;
+use crate::parser::als::AbletonXmlParser;
+use crate::parser::structs::file_kind::FileKind;
+use crate::parser::structs::live_set::{LiveSetContents, SampleReference};
+use crate::project::disk::project_files;
+use crate::project::project::AbletonProjectDirectory;
+use crate::version::version::{hash_file, to_epoch_millis};
+use anyhow::Context;
+use chrono::{DateTime, Local};
+use flate2::read::GzDecoder;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use zip::result::ZipError;
+
+/// The checksums `verify --record` writes into a project's folder, which copies of the folder take
+/// with them.
+pub const RECORDED_CHECKSUMS: &str = "ableton-v checksums.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedChecksums {
+    pub recorded_at: DateTime<Local>,
+    pub files: Vec<FileChecksum>,
+}
+
+/// Something wrong with a project or archive. Paths are relative to the project's folder.
+#[derive(Debug, Serialize)]
+#[serde(tag = "problem", rename_all = "snake_case")]
+pub enum Problem {
+    /// A file with a checksum that isn't there.
+    Missing { path: String },
+    /// A file that doesn't match its checksum though nothing changed it, as bit rot does.
+    Corrupt { path: String },
+    /// A file that was changed after its checksum was recorded.
+    Changed { path: String },
+    /// A file that can't be read, or a set that doesn't decompress or parse.
+    Unreadable { path: String, error: String },
+    /// A sample reference that doesn't resolve to a file.
+    MissingSample { set: String, sample: String },
+    /// A sample reference that resolves outside the project's folder.
+    ExternalSample { set: String, sample: String },
+    /// A file with no checksum to check it against.
+    Unrecorded { path: String },
+}
+
+impl Problem {
+    /// Whether the problem is worth knowing about, but doesn't mean anything is damaged.
+    pub fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            Problem::ExternalSample { .. } | Problem::Unrecorded { .. }
+        )
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            Problem::Missing { path } => write!(f, "missing: {}", path),
+            Problem::Corrupt { path } => {
+                write!(f, "corrupt: {} doesn't match its checksum", path)
+            }
+            Problem::Changed { path } => write!(
+                f,
+                "changed: {} was modified since its checksum was recorded",
+                path
+            ),
+            Problem::Unreadable { path, error } => write!(f, "unreadable: {}: {}", path, error),
+            Problem::MissingSample { set, sample } => {
+                write!(f, "missing sample: {} uses {}", set, sample)
+            }
+            Problem::ExternalSample { set, sample } => {
+                write!(f, "external sample: {} uses {}", set, sample)
+            }
+            Problem::Unrecorded { path } => write!(f, "unrecorded: {} has no checksum", path),
+        }
+    }
+}
+
+/// What checking a project's folder or an archive found.
+#[derive(Debug, Default, Serialize)]
+pub struct VerifyReport {
+    pub path: PathBuf,
+    /// When the checksums were recorded, or the archive made. None if the folder has none.
+    pub recorded_at: Option<DateTime<Local>>,
+    /// Files checked against their checksums.
+    pub files: usize,
+    /// Sets and backups decompressed and parsed.
+    pub sets: usize,
+    /// Sample references looked for.
+    pub samples: usize,
+    pub problems: Vec<Problem>,
+}
+
+impl VerifyReport {
+    fn new(path: PathBuf) -> VerifyReport {
+        VerifyReport {
+            path,
+            ..VerifyReport::default()
+        }
+    }
+
+    /// The problems that mean something is damaged or missing.
+    pub fn failures(&self) -> usize {
+        self.problems
+            .iter()
+            .filter(|problem| !problem.is_warning())
+            .count()
+    }
+}
+
+impl Display for VerifyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            f,
+            "{}: {} files checked, {} sets read, {} sample references looked for",
+            self.path.display(),
+            self.files,
+            self.sets,
+            self.samples
+        )?;
+        if let Some(recorded_at) = self.recorded_at {
+            writeln!(
+                f,
+                "    checksums from {}",
+                recorded_at.format("%Y-%m-%d %H:%M")
+            )?;
+        } else if self.path.is_dir() {
+            writeln!(
+                f,
+                "    no checksums recorded; `verify --record` records them"
+            )?;
+        }
+        for problem in &self.problems {
+            writeln!(f, "    {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// A project or archive that failed verification.
+#[derive(Debug)]
+pub struct Damaged {
+    pub path: PathBuf,
+    pub problems: usize,
+}
+
+impl Display for Damaged {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{} problems found in {}",
+            self.problems,
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for Damaged {}
+
+/// Checks a project's folder: that every set and backup decompresses and parses, that their
+/// samples can be found, and that every file matches the checksum recorded in the folder, if
+/// there is one. With `record`, the checksums of the files as they are now are recorded instead.
+pub fn verify_project(
+    project: &AbletonProjectDirectory,
+    record: bool,
+) -> anyhow::Result<VerifyReport> {
+    let path = fs::canonicalize(&project.path)?;
+    let mut report = VerifyReport::new(path.clone());
+    let mut files: Vec<(PathBuf, String)> = project_files(project, &path)
+        .into_iter()
+        .filter_map(|file| {
+            let relative = archive_path(file.strip_prefix(&path).ok()?)?;
+            Some((file, relative))
+        })
+        .filter(|(_, relative)| relative != RECORDED_CHECKSUMS)
+        .collect();
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+
+    verify_sets(project, &path, &mut report);
+
+    let checksums_path = path.join(RECORDED_CHECKSUMS);
+    if record {
+        let progress = progress_bar(files.len() as u64, "hashing files");
+        let hashed: Vec<Result<FileChecksum, Problem>> = files
+            .par_iter()
+            .map(|(file, relative)| {
+                let checksum = checksum(file, relative).map_err(|error| Problem::Unreadable {
+                    path: relative.clone(),
+                    error: format!("{:#}", error),
+                });
+                progress.inc(1);
+                checksum
+            })
+            .collect();
+        progress.finish_and_clear();
+        let mut recorded = RecordedChecksums {
+            recorded_at: Local::now(),
+            files: vec![],
+        };
+        for checksum in hashed {
+            match checksum {
+                Ok(checksum) => recorded.files.push(checksum),
+                Err(problem) => report.problems.push(problem),
+            }
+        }
+        fs::write(&checksums_path, serde_json::to_string_pretty(&recorded)?)?;
+        report.recorded_at = Some(recorded.recorded_at);
+        report.files = recorded.files.len();
+    } else if checksums_path.is_file() {
+        let recorded: RecordedChecksums =
+            serde_json::from_str(&fs::read_to_string(&checksums_path)?)
+                .with_context(|| format!("couldn't read {}", checksums_path.display()))?;
+        report.recorded_at = Some(recorded.recorded_at);
+        report.files = recorded.files.len();
+        let progress = progress_bar(recorded.files.len() as u64, "checking files");
+        let problems: Vec<Problem> = recorded
+            .files
+            .par_iter()
+            .filter_map(|file| {
+                let problem = check_file(&path, file);
+                progress.inc(1);
+                problem
+            })
+            .collect();
+        progress.finish_and_clear();
+        report.problems.extend(problems);
+        let recorded: HashSet<&str> = recorded
+            .files
+            .iter()
+            .map(|file| file.path.as_str())
+            .collect();
+        report.problems.extend(
+            files
+                .iter()
+                .filter(|(_, relative)| !recorded.contains(relative.as_str()))
+                .map(|(_, relative)| Problem::Unrecorded {
+                    path: relative.clone(),
+                }),
+        );
+    }
+    Ok(report)
+}
+
+/// Checks an archive: that every file in its manifest is in it and matches its checksum, that
+/// every set and backup decompresses and parses, and that their samples are in the archive.
+pub fn verify_archive(path: &Path) -> anyhow::Result<VerifyReport> {
+    let mut report = VerifyReport::new(path.to_path_buf());
+    // An archive that was cut short, e.g. by an incomplete copy, loses its directory at the end.
+    let opened = open_archive(path).and_then(|mut zip| Ok((read_manifest(&mut zip)?, zip)));
+    let (manifest, mut zip) = match opened {
+        Ok(opened) => opened,
+        Err(error) => {
+            report.problems.push(Problem::Unreadable {
+                path: path.display().to_string(),
+                error: format!("{:#}", error),
+            });
+            return Ok(report);
+        }
+    };
+    report.recorded_at = Some(manifest.archived_at);
+    report.files = manifest.files.len();
+
+    let mut sets = vec![];
+    let progress = progress_bar(manifest.files.len() as u64, "checking files");
+    for file in &manifest.files {
+        progress.inc(1);
+        let mut entry = match zip.by_name(&manifest.entry_name(&file.path)) {
+            Ok(entry) => entry,
+            Err(ZipError::FileNotFound) => {
+                report.problems.push(Problem::Missing {
+                    path: file.path.clone(),
+                });
+                continue;
+            }
+            Err(error) => {
+                report.problems.push(Problem::Unreadable {
+                    path: file.path.clone(),
+                    error: error.to_string(),
+                });
+                continue;
+            }
+        };
+        let is_set = FileKind::from_path(Path::new(&file.path)) == Some(FileKind::Set);
+        let mut contents = vec![];
+        let mut writer = HashingWriter::new(if is_set {
+            Box::new(&mut contents) as Box<dyn Write>
+        } else {
+            Box::new(io::sink())
+        });
+        // Contents that can't be read back, or don't match their CRC-32, are damaged.
+        let extracted = io::copy(&mut entry, &mut writer);
+        let (writer, sha256) = writer.finish();
+        drop(writer);
+        let Ok(size) = extracted else {
+            report.problems.push(Problem::Corrupt {
+                path: file.path.clone(),
+            });
+            continue;
+        };
+        if size != file.size || sha256 != file.sha256 {
+            report.problems.push(Problem::Corrupt {
+                path: file.path.clone(),
+            });
+        } else if is_set {
+            sets.push((file.path.clone(), contents));
+        }
+    }
+    progress.finish_and_clear();
+
+    let prefix = manifest.entry_name("");
+    let recorded: HashSet<&str> = manifest
+        .files
+        .iter()
+        .map(|file| file.path.as_str())
+        .collect();
+    report.problems.extend(
+        zip.file_names()
+            .filter_map(|name| name.strip_prefix(&prefix))
+            .filter(|relative| !relative.is_empty() && !relative.ends_with('/'))
+            .filter(|relative| !recorded.contains(relative))
+            .map(|relative| Problem::Unrecorded {
+                path: String::from(relative),
+            }),
+    );
+
+    let checked: Vec<(usize, Vec<Problem>)> = sets
+        .par_iter()
+        .map(|(set, contents)| match read_set(contents.as_slice()) {
+            Ok(live_set) => {
+                let problems = live_set
+                    .samples
+                    .iter()
+                    .filter(|sample| !is_in_archive(&manifest, &recorded, sample))
+                    .map(|sample| Problem::MissingSample {
+                        set: set.clone(),
+                        sample: stored_path(sample),
+                    })
+                    .collect();
+                (live_set.samples.len(), problems)
+            }
+            Err(error) => (
+                0,
+                vec![Problem::Unreadable {
+                    path: set.clone(),
+                    error: format!("{:#}", error),
+                }],
+            ),
+        })
+        .collect();
+    report.sets = sets.len();
+    for (samples, problems) in checked {
+        report.samples += samples;
+        report.problems.extend(problems);
+    }
+    Ok(report)
+}
+
+/// Reads every set and backup in the project in parallel, and looks for the samples they use.
+fn verify_sets(project: &AbletonProjectDirectory, path: &Path, report: &mut VerifyReport) {
+    let sets: Vec<&Path> = project
+        .versions
+        .iter()
+        .chain(&project.backups)
+        .map(|version| version.path.as_path())
+        .collect();
+    let checked: Vec<(usize, Vec<Problem>)> = sets
+        .par_iter()
+        .map(|set| {
+            let name = set
+                .strip_prefix(&project.path)
+                .ok()
+                .and_then(archive_path)
+                .unwrap_or_else(|| set.display().to_string());
+            let live_set = fs::File::open(set)
+                .map_err(anyhow::Error::from)
+                .and_then(read_set);
+            match live_set {
+                Ok(live_set) => {
+                    let problems = live_set
+                        .samples
+                        .iter()
+                        .filter_map(|sample| match sample.resolve(&project.path) {
+                            None => Some(Problem::MissingSample {
+                                set: name.clone(),
+                                sample: stored_path(sample),
+                            }),
+                            Some(found) if !found.starts_with(path) => {
+                                Some(Problem::ExternalSample {
+                                    set: name.clone(),
+                                    sample: found.display().to_string(),
+                                })
+                            }
+                            Some(_) => None,
+                        })
+                        .collect();
+                    (live_set.samples.len(), problems)
+                }
+                Err(error) => (
+                    0,
+                    vec![Problem::Unreadable {
+                        path: name,
+                        error: format!("{:#}", error),
+                    }],
+                ),
+            }
+        })
+        .collect();
+    report.sets += sets.len();
+    for (samples, problems) in checked {
+        report.samples += samples;
+        report.problems.extend(problems);
+    }
+}
+
+/// Decompresses a whole set, so a damaged end is caught too, and parses it.
+fn read_set(reader: impl Read) -> anyhow::Result<LiveSetContents> {
+    let mut xml = vec![];
+    GzDecoder::new(reader)
+        .read_to_end(&mut xml)
+        .context("it doesn't decompress")?;
+    AbletonXmlParser::new().read_xml_as(xml.as_slice(), FileKind::Set)
+}
+
+fn checksum(file: &Path, relative: &str) -> anyhow::Result<FileChecksum> {
+    let metadata = fs::metadata(file)?;
+    Ok(FileChecksum {
+        path: String::from(relative),
+        size: metadata.len(),
+        sha256: hash_file(file)?,
+        modified_at: DateTime::from(metadata.modified()?),
+    })
+}
+
+/// Compares a file in the project's folder at `path` with its recorded checksum.
+fn check_file(path: &Path, recorded: &FileChecksum) -> Option<Problem> {
+    let relative = || recorded.path.clone();
+    let Some(file) = archive_path(Path::new(&recorded.path))
+        .filter(|relative| *relative == recorded.path)
+        .map(|relative| path.join(relative))
+    else {
+        return Some(Problem::Unreadable {
+            path: relative(),
+            error: String::from("it isn't a path inside the project"),
+        });
+    };
+    let current = match checksum(&file, &recorded.path) {
+        Ok(current) => current,
+        Err(_) if !file.exists() => return Some(Problem::Missing { path: relative() }),
+        Err(error) => {
+            return Some(Problem::Unreadable {
+                path: relative(),
+                error: format!("{:#}", error),
+            })
+        }
+    };
+    if current.size == recorded.size && current.sha256 == recorded.sha256 {
+        None
+    } else if current.size == recorded.size
+        && to_epoch_millis(&current.modified_at.into())
+            == to_epoch_millis(&recorded.modified_at.into())
+    {
+        Some(Problem::Corrupt { path: relative() })
+    } else {
+        Some(Problem::Changed { path: relative() })
+    }
+}
+
+/// Whether a sample a set in an archive uses is in the archive: relative to the project's folder,
+/// as Live stores it, or at its absolute path inside the project's folder when it was archived.
+/// Archiving points references to samples collected from outside the project at their copies.
+fn is_in_archive(
+    manifest: &ArchiveManifest,
+    files: &HashSet<&str>,
+    sample: &SampleReference,
+) -> bool {
+    let relative = sample
+        .relative_path
+        .as_ref()
+        .and_then(|relative| normalise(Path::new(&relative.replace('\\', "/"))));
+    if relative.is_some_and(|relative| files.contains(relative.as_str())) {
+        return true;
+    }
+    let Some(absolute) = sample.path.as_deref().map(Path::new) else {
+        return false;
+    };
+    manifest
+        .source
+        .as_ref()
+        .and_then(|source| absolute.strip_prefix(source).ok())
+        .and_then(archive_path)
+        .is_some_and(|relative| files.contains(relative.as_str()))
+}
+
+/// A relative path with `.` and `..` resolved and forward slashes, or None if it leads outside.
+fn normalise(path: &Path) -> Option<String> {
+    let mut parts = vec![];
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_str()?),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// A sample reference as the set stores it.
+fn stored_path(sample: &SampleReference) -> String {
+    sample
+        .path
+        .as_ref()
+        .or(sample.relative_path.as_ref())
+        .cloned()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::archive::archive_project;
+    use crate::project::samples::tests::write_set;
+
+    #[test]
+    fn backups_find_samples_from_the_project_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Song Project");
+        fs::create_dir_all(path.join("Samples/Recorded")).unwrap();
+        fs::write(path.join("Samples/Recorded/take.wav"), "audio").unwrap();
+        fs::create_dir_all(path.join("Backup")).unwrap();
+        // The absolute paths are stale, as they are once a project has moved.
+        let take = [(Path::new("/nowhere/take.wav"), "Samples/Recorded/take.wav")];
+        write_set(&path.join("Song.als"), &take);
+        write_set(&path.join("Backup/Song [2023-06-01 153012].als"), &take);
+        let project = AbletonProjectDirectory::new(fs::canonicalize(&path).unwrap(), None);
+
+        let report = verify_project(&project, false).unwrap();
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!((report.sets, report.samples), (2, 2));
+
+        let archive = dir.path().join("Song Project.zip");
+        archive_project(&project, &archive).unwrap();
+        let report = verify_archive(&archive).unwrap();
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!((report.sets, report.samples), (2, 2));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::config::config::{Config, SessionRoot};
+use crate::state::database::Database;
+use crate::state::state::{update_index, ProgramState};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::Instant;
+
+const BENCH_ROOT_LABEL: &str = "bench";
+const PLUGIN_NAMES: [&str; 4] = ["Serum", "Diva", "Pro-Q 3", "Valhalla Room"];
+const LOCATOR_NAMES: [&str; 4] = ["Intro", "Verse", "Drop", "Outro"];
+
+/// The shape of a generated library of synthetic projects.
+#[derive(Debug, Clone, Serialize)]
+pub struct SyntheticLibrary {
+    pub projects: usize,
+    /// Sets per project.
+    pub versions: usize,
+    /// Backups Live would have kept of each set.
+    pub backups: usize,
+    /// Tracks per set, each with a clip and a device.
+    pub tracks: usize,
+}
+
+impl SyntheticLibrary {
+    /// Writes the library's projects under `root`, returning how many sets were written and their
+    /// total size in bytes.
+    pub fn generate(&self, root: &Path) -> anyhow::Result<(usize, u64)> {
+        // Libraries are usually grouped, e.g. `<root>/<group>/<name> Project`.
+        let group = root.join("Synthetic");
+        let written = (0..self.projects)
+            .into_par_iter()
+            .map(|project| self.generate_project(&group, project))
+            .collect::<anyhow::Result<Vec<(usize, u64)>>>()?;
+        Ok(written
+            .into_iter()
+            .fold((0, 0), |(sets, bytes), (project_sets, project_bytes)| {
+                (sets + project_sets, bytes + project_bytes)
+            }))
+    }
+
+    fn generate_project(&self, group: &Path, project: usize) -> anyhow::Result<(usize, u64)> {
+        let directory = group.join(format!("Song {} Project", project));
+        let backup_directory = directory.join("Backup");
+        fs::create_dir_all(&backup_directory)?;
+        let mut sets = 0;
+        let mut bytes = 0;
+        for version in 0..self.versions {
+            let name = format!("Song {} v{}", project, version);
+            bytes += write_set(
+                &directory.join(format!("{}.als", name)),
+                &self.synthetic_set(project, version),
+            )?;
+            sets += 1;
+            for backup in 0..self.backups {
+                let path = backup_directory.join(format!(
+                    "{} [2023-06-{:02} 1530{:02}].als",
+                    name,
+                    backup % 28 + 1,
+                    backup % 60
+                ));
+                bytes += write_set(&path, &self.synthetic_set(project, backup))?;
+                sets += 1;
+            }
+        }
+        Ok((sets, bytes))
+    }
+
+    /// The XML of a set with the parts scans read: tracks, clips, devices, locators and tempo.
+    fn synthetic_set(&self, project: usize, version: usize) -> String {
+        let mut tracks = String::new();
+        for track in 0..self.tracks {
+            let device = if track % 2 == 0 {
+                format!(
+                    "<PluginDevice Id=\"0\"><PluginDesc><Vst3PluginInfo><Name Value=\"{}\"/></Vst3PluginInfo></PluginDesc></PluginDevice>",
+                    PLUGIN_NAMES[track % PLUGIN_NAMES.len()]
+                )
+            } else {
+                String::from("<Compressor2 Id=\"0\"><UserName Value=\"\"/></Compressor2>")
+            };
+            tracks.push_str(&format!(
+                "<MidiTrack Id=\"{id}\"><Name><EffectiveName Value=\"Track {id}\"/></Name><DeviceChain><DeviceChain><Devices>{device}</Devices></DeviceChain><MainSequencer><ClipSlotList><MidiClip Id=\"0\" Time=\"{time}\"><Name Value=\"idea {project}.{version}.{id}\"/></MidiClip></ClipSlotList></MainSequencer></DeviceChain></MidiTrack>",
+                id = track,
+                device = device,
+                time = version * 4,
+                project = project,
+                version = version,
+            ));
+        }
+        let locators: String = LOCATOR_NAMES
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                format!(
+                    "<Locator Id=\"{}\"><Time Value=\"{}\"/><Name Value=\"{}\"/></Locator>",
+                    index,
+                    index * 16,
+                    name
+                )
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Ableton MajorVersion=\"5\" MinorVersion=\"11.0_433\" Creator=\"Ableton Live 11.3.4\"><LiveSet><Tracks>{}</Tracks><MasterTrack><DeviceChain><Mixer><Tempo><Manual Value=\"{}\"/></Tempo></Mixer></DeviceChain></MasterTrack><Locators><Locators>{}</Locators></Locators></LiveSet></Ableton>",
+            tracks,
+            100 + (project + version) % 60,
+            locators
+        )
+    }
+}
+
+/// Sets are gzipped XML, like the ones Live writes.
+fn write_set(path: &Path, xml: &str) -> anyhow::Result<u64> {
+    let mut encoder = GzEncoder::new(fs::File::create(path)?, Compression::default());
+    encoder.write_all(xml.as_bytes())?;
+    encoder.finish()?;
+    Ok(fs::metadata(path)?.len())
+}
+
+/// How long one scan of the synthetic library took.
+#[derive(Debug, Serialize)]
+pub struct ScanTiming {
+    pub seconds: f64,
+    pub sets_per_second: f64,
+    pub megabytes_per_second: f64,
+}
+
+impl ScanTiming {
+    fn new(started: Instant, sets: usize, bytes: u64) -> ScanTiming {
+        let seconds = started.elapsed().as_secs_f64();
+        ScanTiming {
+            seconds,
+            sets_per_second: sets as f64 / seconds,
+            megabytes_per_second: bytes as f64 / 1_000_000.0 / seconds,
+        }
+    }
+}
+
+impl Display for ScanTiming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{:.2}s ({:.0} sets/s, {:.1} MB/s)",
+            self.seconds, self.sets_per_second, self.megabytes_per_second
+        )
+    }
+}
+
+/// The throughput of scanning a synthetic library, first into an empty index and then again
+/// with nothing changed.
+#[derive(Debug, Serialize)]
+pub struct BenchReport {
+    pub library: SyntheticLibrary,
+    pub jobs: usize,
+    pub sets: usize,
+    pub bytes: u64,
+    pub full_scan: ScanTiming,
+    pub rescan: ScanTiming,
+}
+
+impl Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            f,
+            "{} sets in {} projects ({:.1} MB), {} jobs",
+            self.sets,
+            self.library.projects,
+            self.bytes as f64 / 1_000_000.0,
+            self.jobs
+        )?;
+        writeln!(f, "full scan: {}", self.full_scan)?;
+        writeln!(f, "rescan:    {}", self.rescan)
+    }
+}
+
+/// Generates `library` under `directory` and times a full scan of it into a fresh index, then a
+/// rescan. Only the session root and database differ from `config`.
+pub async fn bench(
+    config: &Config,
+    library: &SyntheticLibrary,
+    directory: &Path,
+) -> anyhow::Result<BenchReport> {
+    let sessions = directory.join("sessions");
+    let database_path = directory.join("bench.sqlite");
+    if sessions.exists() || database_path.exists() {
+        anyhow::bail!(
+            "{} already has a benchmark library in it",
+            directory.display()
+        );
+    }
+    let (sets, bytes) = library.generate(&sessions)?;
+    let mut config = config.clone();
+    config.session_roots.value = vec![SessionRoot::new(
+        Some(String::from(BENCH_ROOT_LABEL)),
+        sessions,
+    )];
+    config.database_path.value = database_path;
+    config.ignore_patterns.value = vec![];
+    let mut db = Database::new(&config.database_path.value).await;
+    let started = Instant::now();
+    let mut state = ProgramState::new(&config);
+    update_index(&mut db, &mut state).await?;
+    let full_scan = ScanTiming::new(started, sets, bytes);
+    let started = Instant::now();
+    let mut state = ProgramState::new(&config);
+    update_index(&mut db, &mut state).await?;
+    let rescan = ScanTiming::new(started, sets, bytes);
+    Ok(BenchReport {
+        library: library.clone(),
+        jobs: rayon::current_num_threads(),
+        sets,
+        bytes,
+        full_scan,
+        rescan,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::config::ConfigFlags;
+    use sqlx::Row;
+
+    /// What a scan indexed, leaving out ids and access times, which differ between scans.
+    async fn indexed_rows(db: &mut Database) -> Vec<String> {
+        let queries = [
+            "SELECT json_array(path, name, project_path, root, created_at, modified_at, backup_of, backed_up_at, size, content_hash, removed_at, (SELECT path FROM project WHERE id = project_id)) FROM project_version",
+            "SELECT json_array(name, path, root, created_at, modified_at, version_count, loose, removed_at) FROM project",
+            "SELECT json_array(kind, text, project_path, version_path, track) FROM search_entry",
+        ];
+        let mut rows = vec![];
+        for query in queries {
+            let mut table: Vec<String> = db
+                .execute_fetchall(sqlx::query(query))
+                .await
+                .unwrap()
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            table.sort();
+            rows.extend(table);
+        }
+        rows
+    }
+
+    #[test]
+    fn scans_index_the_same_rows_whatever_the_number_of_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let sessions = dir.join("sessions");
+        let library = SyntheticLibrary {
+            projects: 6,
+            versions: 3,
+            backups: 2,
+            tracks: 4,
+        };
+        let (sets, _) = library.generate(&sessions).unwrap();
+        let config_file = dir.join("config.toml");
+        fs::write(&config_file, "").unwrap();
+        let flags = ConfigFlags {
+            config_file: Some(config_file),
+            session_roots: vec![SessionRoot::new(
+                Some(String::from(BENCH_ROOT_LABEL)),
+                sessions,
+            )],
+            user_library: Some(dir.join("User Library")),
+            ..ConfigFlags::default()
+        };
+        let config = Config::load_from(&flags, &|_| None).unwrap();
+
+        let scan_with = |jobs: usize| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(jobs)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(async {
+                        let mut db =
+                            Database::new(&dir.join(format!("{} jobs.sqlite", jobs))).await;
+                        let changes = update_index(&mut db, &mut ProgramState::new(&config))
+                            .await
+                            .unwrap();
+                        assert_eq!(changes.added, sets);
+                        indexed_rows(&mut db).await
+                    })
+            })
+        };
+
+        let serial = scan_with(1);
+        assert!(!serial.is_empty());
+        assert_eq!(serial, scan_with(4));
+    }
+}
@@ -0,0 +1,1268 @@
+use crate::archive::archive::{archive_project, unarchive_project, ARCHIVE_EXTENSION};
+use crate::archive::verify::{verify_archive, verify_project, Damaged};
+use crate::bench::bench::{bench, SyntheticLibrary};
+use crate::cli::output::{print_json, status, verbose};
+use crate::config::config::{Config, ConfigFlags};
+use crate::diff::diff::LiveSetDiff;
+use crate::library::library::{select_library_files, LibraryQuery};
+use crate::library::usage::preset_usage;
+use crate::lineage::lineage::{update_lineage, LineageGraph};
+use crate::live::live::LiveStatus;
+use crate::live::lock::{SafetyLock, SetInUse};
+use crate::parser::als::AbletonXmlParser;
+use crate::parser::structs::file_kind::FileKind;
+use crate::project::disk::{format_size, measure_projects};
+use crate::project::project::{
+    select_project_paths_named, select_projects, AbletonProjectDirectory, ProjectQuery,
+};
+use crate::project::quarantine::{
+    list_quarantines, quarantine_files, restore_quarantine, Quarantine,
+    MANIFEST_FILE as QUARANTINE_MANIFEST,
+};
+use crate::project::samples::{unused_samples, SampleReferences};
+use crate::project::status::{set_notes, set_status, ProjectStatus};
+use crate::project::todo::{add_todo, remove_todo, set_todo_done, ProjectTodo};
+use crate::search::search::{search, EntryKind, SearchQuery};
+use crate::state::database::Database;
+use crate::state::state::{count_root_projects, update_index, ProgramState};
+use crate::tui::tui::browse;
+use crate::version::tag::{add_tags, list_tags, normalise_tag, remove_tags};
+use crate::version::version::{
+    describe_version, list_versions, parse_date, select_previous_version, select_version,
+    VersionQuery,
+};
+use crate::watch::watch::SessionWatcher;
+use clap::{Args, Parser, Subcommand};
+use serde_json::json;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// The command succeeded.
+pub const EXIT_SUCCESS: u8 = 0;
+/// The command failed, e.g. the database or a set couldn't be read.
+pub const EXIT_FAILURE: u8 = 1;
+/// The command line couldn't be parsed.
+pub const EXIT_USAGE: u8 = 2;
+/// A set or project named on the command line doesn't exist.
+pub const EXIT_NOT_FOUND: u8 = 3;
+/// A set the command would change looks open in Live.
+pub const EXIT_IN_USE: u8 = 4;
+/// A project or archive failed verification.
+pub const EXIT_DAMAGED: u8 = 5;
+
+/// Manage Ableton Live projects and their versions from the terminal.
+#[derive(Debug, Parser)]
+#[command(name = "ableton-v", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub config: ConfigFlags,
+    /// Print results as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Only print results and errors
+    #[arg(long, short, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Also print diagnostic detail
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Scan the session roots and update the index
+    Scan {
+        #[command(flatten)]
+        jobs: JobsFlag,
+    },
+    /// List the session roots and whether they're online
+    Roots,
+    /// List indexed projects, most recently modified first
+    Projects {
+        #[command(flatten)]
+        filter: IndexFilter,
+        /// Only list projects with this status
+        #[arg(long, value_enum)]
+        status: Option<ProjectStatus>,
+        /// Only list folders of loose sets, which have no Ableton Project Info folder
+        #[arg(long)]
+        loose: bool,
+    },
+    /// List indexed versions, most recently modified first
+    Versions {
+        /// Only list versions of this project, by directory or name
+        project: Option<String>,
+        #[command(flatten)]
+        filter: IndexFilter,
+        /// Only list versions with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Include Live's backups and watch snapshots
+        #[arg(long)]
+        backups: bool,
+        /// Include versions whose files are gone since they were indexed
+        #[arg(long)]
+        removed: bool,
+    },
+    /// Summarise a set: tempo, tracks, clips, devices, locators and samples. Racks, presets, clip
+    /// files and grooves are summarised too
+    Info {
+        /// The .als file, or an .adg, .adv, .alc or .agr file
+        set: PathBuf,
+    },
+    /// List a set's tracks with their clips and devices
+    Tracks {
+        /// The .als file
+        set: PathBuf,
+    },
+    /// Show what changed between two sets, or between a set and its previous version
+    Diff {
+        /// The older .als file, or the only one to compare with its previous version
+        set: PathBuf,
+        /// The newer .als file
+        other: Option<PathBuf>,
+    },
+    /// Fuzzy search project, version, track, clip, locator, device and plugin names, descriptions
+    /// and tags, and the names of racks, presets, clip files and grooves
+    Search {
+        text: String,
+        /// Only search projects in this session root
+        #[arg(long)]
+        root: Option<String>,
+        /// Only return hits of this kind
+        #[arg(long, value_enum)]
+        kind: Option<EntryKind>,
+        /// The most hits to return
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// List indexed racks, presets, clip files and grooves, by kind and name
+    Library {
+        /// Only list files of this kind
+        #[arg(long, value_enum)]
+        kind: Option<FileKind>,
+        /// Only list files in this session root
+        #[arg(long)]
+        root: Option<String>,
+    },
+    /// Show which indexed sets use each rack and preset, to find the ones that are never used
+    Presets {
+        /// Only show the sets using this rack or preset file
+        file: Option<PathBuf>,
+        /// Only list racks and presets no indexed set uses
+        #[arg(long, conflicts_with = "file")]
+        unused: bool,
+        /// Only list racks or only presets
+        #[arg(long, value_enum)]
+        kind: Option<FileKind>,
+        /// Only list files in this session root, or `user-library` for the User Library
+        #[arg(long)]
+        root: Option<String>,
+    },
+    /// Infer and store which version each set in a project was derived from
+    Lineage {
+        /// The project directory
+        project: PathBuf,
+    },
+    /// Set the description of a version
+    Describe {
+        /// The .als file
+        set: PathBuf,
+        #[arg(required = true, num_args = 1..)]
+        message: Vec<String>,
+    },
+    /// Add tags to a version
+    Tag {
+        /// The .als file
+        set: PathBuf,
+        #[arg(required = true, num_args = 1..)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a version
+    Untag {
+        /// The .als file
+        set: PathBuf,
+        #[arg(required = true, num_args = 1..)]
+        tags: Vec<String>,
+    },
+    /// List the tags in use, with the number of versions carrying each
+    Tags,
+    /// Show or set where a project is at
+    Status {
+        /// The project, by directory or name
+        project: String,
+        #[arg(value_enum)]
+        status: Option<ProjectStatus>,
+        /// Clear the project's status
+        #[arg(long, conflicts_with = "status")]
+        clear: bool,
+    },
+    /// Show or replace a project's notes
+    Notes {
+        /// The project, by directory or name
+        project: String,
+        text: Vec<String>,
+        /// Clear the project's notes
+        #[arg(long, conflicts_with = "text")]
+        clear: bool,
+    },
+    /// Report how much disk space projects take, split into sets, backups, recorded audio,
+    /// imported samples, freeze/consolidate files and other files, largest first
+    Du {
+        /// Only report this project, by directory or name
+        project: Option<String>,
+        /// Only report projects in this session root
+        #[arg(long)]
+        root: Option<String>,
+        /// The most projects to report
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+    /// Find audio under a project's Samples folder that none of its sets or backups use, and move
+    /// it to quarantine
+    Samples {
+        #[command(subcommand)]
+        command: SamplesCommand,
+    },
+    /// Pack a project, and the samples its sets use from outside it, into a zip archive with a
+    /// manifest of its versions and a checksum of every file
+    Archive {
+        /// The project, by directory or name
+        project: String,
+        /// Where to write the archive, by default the project's folder name with `.zip` in the
+        /// current directory
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        /// Go ahead even if a set in the project looks open in Live
+        #[arg(long)]
+        force: bool,
+    },
+    /// Unpack a project archive into a session root, checking every file, and index it
+    Unarchive {
+        archive: PathBuf,
+        /// The session root to unpack into, by default the first one
+        #[arg(long)]
+        root: Option<String>,
+    },
+    /// Check a project folder or archive for damage: every file against its checksum, every set
+    /// decompresses and parses, and every sample it uses can be found
+    Verify {
+        /// A project, by directory or name, or an archive
+        path: String,
+        /// Record the checksums of a project's files in its folder, to check it and its copies
+        /// against later
+        #[arg(long)]
+        record: bool,
+    },
+    /// Keep a checklist of what's left to do on a project
+    Todo {
+        #[command(subcommand)]
+        command: TodoCommand,
+    },
+    /// Snapshot every set Live saves into a session root
+    Watch,
+    /// Show whether Live is running, which version, and which sets look open in it
+    Live {
+        /// Check whether this set, or a set in this project directory, looks open in Live
+        path: Option<PathBuf>,
+    },
+    /// Browse projects and versions interactively
+    Browse,
+    /// Time scanning a generated library of synthetic projects
+    Bench {
+        /// Number of projects to generate
+        #[arg(long, default_value_t = 200)]
+        projects: usize,
+        /// Sets per project
+        #[arg(long, default_value_t = 5)]
+        versions: usize,
+        /// Backups per set
+        #[arg(long, default_value_t = 2)]
+        backups: usize,
+        /// Tracks per set
+        #[arg(long, default_value_t = 16)]
+        tracks: usize,
+        /// Generate the library here and keep it, instead of in a temporary directory
+        #[arg(long, value_name = "DIR")]
+        directory: Option<PathBuf>,
+        #[command(flatten)]
+        jobs: JobsFlag,
+    },
+    /// Show the effective configuration
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommand>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TodoCommand {
+    /// List what's left to do on a project, or on every project
+    List {
+        /// The project, by directory or name
+        project: Option<String>,
+        /// Include items that are done
+        #[arg(long)]
+        all: bool,
+    },
+    /// Add an item to a project's checklist
+    Add {
+        /// The project, by directory or name
+        project: String,
+        #[arg(required = true, num_args = 1..)]
+        text: Vec<String>,
+    },
+    /// Check off an item
+    Done {
+        /// The project, by directory or name
+        project: String,
+        /// The item's number, as listed
+        number: usize,
+    },
+    /// Uncheck an item
+    Undo {
+        /// The project, by directory or name
+        project: String,
+        /// The item's number, as listed
+        number: usize,
+    },
+    /// Remove an item from a project's checklist
+    Remove {
+        /// The project, by directory or name
+        project: String,
+        /// The item's number, as listed
+        number: usize,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SamplesCommand {
+    /// List the audio files under a project's Samples folder that no set or backup refers to
+    Unused {
+        /// The project, by directory or name
+        project: String,
+    },
+    /// Move a project's unused audio files, and their analysis files, to the quarantine directory
+    Clean {
+        /// The project, by directory or name
+        project: String,
+        /// Only list what would be moved
+        #[arg(long)]
+        dry_run: bool,
+        /// Go ahead even if a set in the project looks open in Live
+        #[arg(long)]
+        force: bool,
+    },
+    /// Move quarantined files back into their project
+    Restore {
+        /// The quarantine folder, as listed
+        quarantine: PathBuf,
+        /// Go ahead even if a set in the project looks open in Live
+        #[arg(long)]
+        force: bool,
+    },
+    /// List the quarantine folders
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print each setting and where it came from
+    Show,
+}
+
+/// Filters shared by the listing commands.
+#[derive(Debug, Args)]
+pub struct IndexFilter {
+    /// Only list projects in this session root
+    #[arg(long)]
+    root: Option<String>,
+    /// Only list what was modified on or after this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date_argument)]
+    since: Option<SystemTime>,
+    /// Only list what was modified before this date (YYYY-MM-DD)
+    #[arg(long, value_parser = parse_date_argument)]
+    until: Option<SystemTime>,
+}
+
+/// The size of the thread pool that scans walk directories and read sets on.
+#[derive(Debug, Args)]
+pub struct JobsFlag {
+    /// Number of threads to scan with, one per CPU by default
+    #[arg(long, short, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: Option<u16>,
+}
+
+impl JobsFlag {
+    fn configure(&self) -> anyhow::Result<()> {
+        if let Some(jobs) = self.jobs {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(usize::from(jobs))
+                .build_global()?;
+        }
+        Ok(())
+    }
+}
+
+fn parse_date_argument(value: &str) -> Result<SystemTime, String> {
+    parse_date(value).map_err(|error| error.to_string())
+}
+
+/// A set or project named on the command line that doesn't exist.
+#[derive(Debug)]
+pub struct NotFound(pub String);
+
+impl Display for NotFound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NotFound {}
+
+/// The exit code for a command that failed with `error`.
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    if error.is::<NotFound>() {
+        EXIT_NOT_FOUND
+    } else if error.is::<SetInUse>() {
+        EXIT_IN_USE
+    } else if error.is::<Damaged>() {
+        EXIT_DAMAGED
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+pub async fn run(cli: Cli) -> anyhow::Result<()> {
+    let config = Config::load(&cli.config)?;
+    match cli.command {
+        Command::Scan { jobs } => {
+            jobs.configure()?;
+            let mut state = ProgramState::new(&config);
+            verbose!("{}", state);
+            let mut db = Database::new(&config.database_path.value).await;
+            let changes = update_index(&mut db, &mut state).await?;
+            let offline: Vec<&str> = state
+                .offline_roots()
+                .iter()
+                .map(|root| root.label.as_str())
+                .collect();
+            if cli.json {
+                print_json(&json!({
+                    "projects": state.count_projects(),
+                    "loose_projects": state.count_loose_projects(),
+                    "versions": state.count_versions(),
+                    "backups": state.count_backups(),
+                    "library_files": state.count_library_files(),
+                    "changes": changes,
+                    "offline_roots": offline,
+                }))?;
+            } else {
+                let loose = match state.count_loose_projects() {
+                    0 => String::new(),
+                    loose => format!(" ({} of them loose sets)", loose),
+                };
+                let backups_and_library_files = match state.count_library_files() {
+                    0 => format!(" and {} backups", state.count_backups()),
+                    library_files => format!(
+                        ", {} backups and {} library files",
+                        state.count_backups(),
+                        library_files
+                    ),
+                };
+                println!(
+                    "indexed {} projects{}, {} versions{} ({})",
+                    state.count_projects(),
+                    loose,
+                    state.count_versions(),
+                    backups_and_library_files,
+                    changes
+                );
+            }
+        }
+        Command::Roots => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let mut roots = vec![];
+            for root in &config.session_roots.value {
+                roots.push((root, count_root_projects(&mut db, root).await?));
+            }
+            if cli.json {
+                let roots: Vec<_> = roots
+                    .iter()
+                    .map(|(root, projects)| {
+                        json!({
+                            "label": root.label,
+                            "path": root.path,
+                            "online": root.is_online(),
+                            "indexed_projects": projects,
+                        })
+                    })
+                    .collect();
+                print_json(&roots)?;
+            } else {
+                for (root, projects) in roots {
+                    println!(
+                        "{} ({}, {} projects indexed)",
+                        root,
+                        if root.is_online() {
+                            "online"
+                        } else {
+                            "offline"
+                        },
+                        projects
+                    );
+                }
+            }
+        }
+        Command::Projects {
+            filter,
+            status,
+            loose,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let query = ProjectQuery {
+                path: None,
+                root: filter.root,
+                modified_since: filter.since,
+                modified_until: filter.until,
+                status,
+                loose,
+            };
+            let projects = select_projects(&mut db, &query).await?;
+            if cli.json {
+                print_json(&projects)?;
+            } else {
+                for project in projects {
+                    print!("{}", project);
+                }
+            }
+        }
+        Command::Versions {
+            project,
+            filter,
+            tag,
+            backups,
+            removed,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let project_path = match project {
+                Some(project) => Some(resolve_project(&mut db, &project).await?),
+                None => None,
+            };
+            let query = VersionQuery {
+                project_path,
+                root: filter.root,
+                tag: tag.as_deref().map(normalise_tag),
+                modified_since: filter.since,
+                modified_until: filter.until,
+                include_backups: backups,
+                include_removed: removed,
+            };
+            let versions = list_versions(&mut db, &query).await?;
+            if cli.json {
+                print_json(&versions)?;
+            } else {
+                for version in versions {
+                    println!("{}", version);
+                }
+            }
+        }
+        Command::Info { set } => {
+            let path = resolve_set(&set)?;
+            let contents = AbletonXmlParser::new().read_contents_of(&path)?;
+            let mut db = Database::new(&config.database_path.value).await;
+            let version = select_version(&mut db, &path).await?;
+            if cli.json {
+                print_json(&json!({
+                    "path": path,
+                    "version": version,
+                    "contents": contents,
+                }))?;
+            } else {
+                println!("{}", path.display());
+                if let Some(description) = version.and_then(|version| version.description) {
+                    println!("    description: {}", description);
+                }
+                print!("{}", contents);
+            }
+        }
+        Command::Tracks { set } => {
+            let path = resolve_set(&set)?;
+            let contents = AbletonXmlParser::new().read_contents_of(&path)?;
+            if cli.json {
+                let tracks: Vec<_> = contents
+                    .tracks
+                    .iter()
+                    .map(|track| {
+                        json!({
+                            "id": track.id,
+                            "kind": track.kind,
+                            "name": track.name,
+                            "clips": contents.clips_on(&track.id),
+                            "devices": contents.devices_on(&track.id),
+                        })
+                    })
+                    .collect();
+                print_json(&tracks)?;
+            } else {
+                for track in &contents.tracks {
+                    println!("{} {} {:?}", track.id, track.kind, track.name);
+                    let clips = contents.clips_on(&track.id);
+                    if !clips.is_empty() {
+                        let clips: Vec<String> = clips
+                            .iter()
+                            .map(|clip| format!("{:?} at {}", clip.name, clip.time))
+                            .collect();
+                        println!("    clips: {}", clips.join(", "));
+                    }
+                    let devices = contents.devices_on(&track.id);
+                    if !devices.is_empty() {
+                        let devices: Vec<&str> =
+                            devices.iter().map(|device| device.name.as_str()).collect();
+                        println!("    devices: {}", devices.join(", "));
+                    }
+                }
+            }
+        }
+        Command::Diff { set, other } => {
+            let path = resolve_set(&set)?;
+            let diff = match other {
+                Some(other) => LiveSetDiff::between(&path, &resolve_set(&other)?)?,
+                None => {
+                    let mut db = Database::new(&config.database_path.value).await;
+                    let version = select_version(&mut db, &path).await?.ok_or_else(|| {
+                        NotFound(format!(
+                            "{} isn't indexed; run scan or pass a second set to compare with",
+                            path.display()
+                        ))
+                    })?;
+                    let previous = select_previous_version(&mut db, &version)
+                        .await?
+                        .ok_or_else(|| {
+                            NotFound(format!(
+                                "no previous version of {} is indexed",
+                                path.display()
+                            ))
+                        })?;
+                    LiveSetDiff::between(&previous.path, &path)?
+                }
+            };
+            if cli.json {
+                print_json(&diff)?;
+            } else {
+                print!("{}", diff);
+            }
+        }
+        Command::Search {
+            text,
+            root,
+            kind,
+            limit,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let query = SearchQuery {
+                text,
+                root,
+                kind,
+                limit,
+            };
+            let hits = search(&mut db, &query).await?;
+            if cli.json {
+                print_json(&hits)?;
+            } else {
+                for hit in hits {
+                    println!("{}", hit);
+                }
+            }
+        }
+        Command::Library { kind, root } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let files = select_library_files(&mut db, &LibraryQuery { kind, root }).await?;
+            if cli.json {
+                print_json(&files)?;
+            } else {
+                for file in files {
+                    println!("{}", file);
+                }
+            }
+        }
+        Command::Presets {
+            file,
+            unused,
+            kind,
+            root,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let mut usages = preset_usage(
+                &mut db,
+                &LibraryQuery { kind, root },
+                &config.user_library.value,
+            )
+            .await?;
+            if let Some(file) = file {
+                let path = fs::canonicalize(&file)
+                    .map_err(|_| NotFound(format!("no preset at {}", file.display())))?;
+                let usage = usages
+                    .into_iter()
+                    .find(|usage| usage.file.path == path)
+                    .ok_or_else(|| {
+                        NotFound(format!(
+                            "{} isn't an indexed rack or preset; run scan first",
+                            path.display()
+                        ))
+                    })?;
+                if cli.json {
+                    print_json(&usage)?;
+                } else {
+                    println!("{}", usage);
+                    for user in &usage.used_by {
+                        println!("    {}", user);
+                    }
+                }
+            } else {
+                if unused {
+                    usages.retain(|usage| !usage.is_used());
+                }
+                if cli.json {
+                    print_json(&usages)?;
+                } else {
+                    for usage in usages {
+                        println!("{}", usage);
+                    }
+                }
+            }
+        }
+        Command::Lineage { project } => {
+            let path = resolve_directory(&project)?;
+            let mut db = Database::new(&config.database_path.value).await;
+            let root = config.root_of(&path).map(|root| root.label.clone());
+            let project = AbletonProjectDirectory::new(path, root);
+            let graph = LineageGraph::infer(&project);
+            update_lineage(&mut db, &graph).await?;
+            if cli.json {
+                print_json(&graph)?;
+            } else {
+                print!("{}", graph);
+            }
+        }
+        Command::Describe { set, message } => {
+            let path = resolve_set(&set)?;
+            let mut db = Database::new(&config.database_path.value).await;
+            describe_version(&mut db, &path, &message.join(" ")).await?;
+            status!("described {}", path.display());
+        }
+        Command::Tag { set, tags } => {
+            let path = resolve_set(&set)?;
+            let mut db = Database::new(&config.database_path.value).await;
+            add_tags(&mut db, &path, &tags).await?;
+            status!("tagged {}", path.display());
+        }
+        Command::Untag { set, tags } => {
+            let path = resolve_set(&set)?;
+            let mut db = Database::new(&config.database_path.value).await;
+            remove_tags(&mut db, &path, &tags).await?;
+            status!("untagged {}", path.display());
+        }
+        Command::Tags => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let tags = list_tags(&mut db).await?;
+            if cli.json {
+                let tags: Vec<_> = tags
+                    .iter()
+                    .map(|(tag, count)| json!({ "tag": tag, "versions": count }))
+                    .collect();
+                print_json(&tags)?;
+            } else {
+                for (tag, count) in tags {
+                    println!("{} ({})", tag, count);
+                }
+            }
+        }
+        Command::Status {
+            project,
+            status,
+            clear,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let project = select_project(&mut db, &project).await?;
+            let id = project.id.expect("stored project to have an id");
+            if status.is_some() || clear {
+                set_status(&mut db, id, status).await?;
+                status!("updated the status of {}", project.name);
+            } else if cli.json {
+                print_json(&json!({ "project": project.name, "status": project.status }))?;
+            } else if let Some(status) = project.status {
+                println!("{}", status);
+            }
+        }
+        Command::Notes {
+            project,
+            text,
+            clear,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let project = select_project(&mut db, &project).await?;
+            let id = project.id.expect("stored project to have an id");
+            if !text.is_empty() || clear {
+                let notes = text.join(" ");
+                set_notes(
+                    &mut db,
+                    id,
+                    Some(notes.trim()).filter(|notes| !notes.is_empty()),
+                )
+                .await?;
+                status!("updated the notes of {}", project.name);
+            } else if cli.json {
+                print_json(&json!({ "project": project.name, "notes": project.notes }))?;
+            } else if let Some(notes) = project.notes {
+                println!("{}", notes);
+            }
+        }
+        Command::Du {
+            project,
+            root,
+            limit,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let projects = match project {
+                Some(project) => vec![select_project(&mut db, &project).await?],
+                None => {
+                    let query = ProjectQuery {
+                        root,
+                        ..ProjectQuery::default()
+                    };
+                    select_projects(&mut db, &query).await?
+                }
+            };
+            // The index may be behind the disk, so each project's sets are listed again.
+            let projects: Vec<AbletonProjectDirectory> = projects
+                .into_iter()
+                .filter(|project| {
+                    let found = project.path.is_dir();
+                    if !found {
+                        status!("skipping {}: it isn't there", project.path.display());
+                    }
+                    found
+                })
+                .map(|project| AbletonProjectDirectory::new(project.path, project.root))
+                .collect();
+            let mut usages = measure_projects(&projects);
+            let measured = usages.len();
+            let total: u64 = usages.iter().map(|usage| usage.total).sum();
+            let reclaimable: u64 = usages.iter().map(|usage| usage.reclaimable).sum();
+            if let Some(limit) = limit {
+                usages.truncate(limit);
+            }
+            if cli.json {
+                print_json(&usages)?;
+            } else {
+                for usage in &usages {
+                    print!("{}", usage);
+                }
+                println!(
+                    "total {} in {} projects, {} reclaimable",
+                    format_size(total),
+                    measured,
+                    format_size(reclaimable)
+                );
+            }
+        }
+        Command::Samples { command } => match command {
+            SamplesCommand::Unused { project } => {
+                let mut db = Database::new(&config.database_path.value).await;
+                let project = select_project(&mut db, &project).await?;
+                let project = AbletonProjectDirectory::new(project.path, project.root);
+                let unused = unused_samples(&project, &SampleReferences::read(&project))?;
+                print_unused_samples(&unused, cli.json)?;
+            }
+            SamplesCommand::Clean {
+                project,
+                dry_run,
+                force,
+            } => {
+                let mut db = Database::new(&config.database_path.value).await;
+                let project = select_project(&mut db, &project).await?;
+                let project = AbletonProjectDirectory::new(project.path, project.root);
+                // Checked before any set is read, so the check sees the sets as Live left them.
+                if !dry_run {
+                    let live = LiveStatus::detect(&config);
+                    SafetyLock::new(&live, force).check_project(&project.path)?;
+                }
+                let unused = unused_samples(&project, &SampleReferences::read(&project))?;
+                if dry_run {
+                    print_unused_samples(&unused, cli.json)?;
+                } else if unused.is_empty() {
+                    status!("{} has no unused samples", project.name);
+                } else {
+                    let quarantine =
+                        quarantine_files(&project, &unused, &config.quarantine_directory.value)?;
+                    if cli.json {
+                        print_json(&quarantine)?;
+                    } else {
+                        status!(
+                            "moved {} files ({}) from {} to {}",
+                            quarantine.manifest.files.len(),
+                            format_size(quarantine.size()),
+                            project.name,
+                            quarantine.path.display()
+                        );
+                    }
+                }
+            }
+            SamplesCommand::Restore { quarantine, force } => {
+                if !quarantine.join(QUARANTINE_MANIFEST).is_file() {
+                    return Err(NotFound(format!(
+                        "no quarantine folder at {}",
+                        quarantine.display()
+                    ))
+                    .into());
+                }
+                let project_path = Quarantine::read(&quarantine)?.manifest.project_path;
+                let live = LiveStatus::detect(&config);
+                SafetyLock::new(&live, force).check_project(&project_path)?;
+                let restored = restore_quarantine(&quarantine)?;
+                if cli.json {
+                    print_json(&restored)?;
+                } else {
+                    status!(
+                        "restored {} files ({}) to {}",
+                        restored.manifest.files.len(),
+                        format_size(restored.size()),
+                        restored.manifest.project_path.display()
+                    );
+                }
+            }
+            SamplesCommand::List => {
+                let quarantines = list_quarantines(&config.quarantine_directory.value);
+                if cli.json {
+                    print_json(&quarantines)?;
+                } else {
+                    for quarantine in quarantines {
+                        println!("{}", quarantine);
+                    }
+                }
+            }
+        },
+        Command::Archive {
+            project,
+            output,
+            force,
+        } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let project = select_project(&mut db, &project).await?;
+            let project = AbletonProjectDirectory::new(project.path, project.root);
+            let live = LiveStatus::detect(&config);
+            SafetyLock::new(&live, force).check_project(&project.path)?;
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    let mut name = project
+                        .path
+                        .file_name()
+                        .map(|name| name.to_os_string())
+                        .unwrap_or_default();
+                    name.push(".");
+                    name.push(ARCHIVE_EXTENSION);
+                    PathBuf::from(name)
+                }
+            };
+            let manifest = archive_project(&project, &output)?;
+            if cli.json {
+                print_json(&json!({ "archive": output, "manifest": manifest }))?;
+            } else {
+                status!(
+                    "archived {} to {} ({})",
+                    manifest,
+                    output.display(),
+                    format_size(fs::metadata(&output)?.len())
+                );
+            }
+        }
+        Command::Unarchive { archive, root } => {
+            if !archive.is_file() {
+                return Err(NotFound(format!("no archive at {}", archive.display())).into());
+            }
+            let roots = &config.session_roots.value;
+            let root = match root {
+                Some(label) => roots
+                    .iter()
+                    .find(|root| root.label == label)
+                    .ok_or_else(|| NotFound(format!("no session root labelled {}", label)))?,
+                None => roots
+                    .first()
+                    .ok_or_else(|| anyhow::anyhow!("no session roots are configured"))?,
+            };
+            let (path, manifest) = unarchive_project(&archive, root)?;
+            status!("unarchived {} to {}", manifest, path.display());
+            let mut state = ProgramState::new(&config);
+            let mut db = Database::new(&config.database_path.value).await;
+            let changes = update_index(&mut db, &mut state).await?;
+            if cli.json {
+                print_json(&json!({ "path": path, "manifest": manifest, "changes": changes }))?;
+            } else {
+                status!("updated the index ({})", changes);
+            }
+        }
+        Command::Verify { path, record } => {
+            let report = if Path::new(&path).is_file() {
+                if record {
+                    anyhow::bail!("archives record their checksums when they're made");
+                }
+                verify_archive(Path::new(&path))?
+            } else {
+                let project = if Path::new(&path).is_dir() {
+                    AbletonProjectDirectory::new(resolve_directory(Path::new(&path))?, None)
+                } else {
+                    let mut db = Database::new(&config.database_path.value).await;
+                    let project = select_project(&mut db, &path).await?;
+                    AbletonProjectDirectory::new(project.path, project.root)
+                };
+                verify_project(&project, record)?
+            };
+            if cli.json {
+                print_json(&report)?;
+            } else {
+                print!("{}", report);
+            }
+            let failures = report.failures();
+            if failures > 0 {
+                return Err(Damaged {
+                    path: report.path,
+                    problems: failures,
+                }
+                .into());
+            }
+            if record {
+                status!("recorded the checksums of {} files", report.files);
+            } else {
+                status!("no damage found");
+            }
+        }
+        Command::Todo { command } => {
+            let mut db = Database::new(&config.database_path.value).await;
+            match command {
+                TodoCommand::List { project, all } => {
+                    let projects = match project {
+                        Some(project) => vec![select_project(&mut db, &project).await?],
+                        None => select_projects(&mut db, &ProjectQuery::default()).await?,
+                    };
+                    let checklists: Vec<(String, Vec<ProjectTodo>)> = projects
+                        .into_iter()
+                        .map(|project| {
+                            let todos: Vec<ProjectTodo> = project
+                                .todos
+                                .into_iter()
+                                .filter(|todo| all || !todo.is_done())
+                                .collect();
+                            (project.name, todos)
+                        })
+                        .filter(|(_, todos)| !todos.is_empty())
+                        .collect();
+                    if cli.json {
+                        let checklists: Vec<_> = checklists
+                            .iter()
+                            .map(|(project, todos)| json!({ "project": project, "todos": todos }))
+                            .collect();
+                        print_json(&checklists)?;
+                    } else {
+                        for (project, todos) in checklists {
+                            println!("{}", project);
+                            for todo in todos {
+                                println!("{}", todo);
+                            }
+                        }
+                    }
+                }
+                TodoCommand::Add { project, text } => {
+                    let project = select_project(&mut db, &project).await?;
+                    let id = project.id.expect("stored project to have an id");
+                    add_todo(&mut db, id, &text.join(" ")).await?;
+                    status!("added a to-do to {}", project.name);
+                }
+                TodoCommand::Done { project, number } => {
+                    let project = select_project(&mut db, &project).await?;
+                    let id = project.id.expect("stored project to have an id");
+                    let todo = set_todo_done(&mut db, id, number, true).await?;
+                    status!("checked off {:?} on {}", todo.text, project.name);
+                }
+                TodoCommand::Undo { project, number } => {
+                    let project = select_project(&mut db, &project).await?;
+                    let id = project.id.expect("stored project to have an id");
+                    let todo = set_todo_done(&mut db, id, number, false).await?;
+                    status!("unchecked {:?} on {}", todo.text, project.name);
+                }
+                TodoCommand::Remove { project, number } => {
+                    let project = select_project(&mut db, &project).await?;
+                    let id = project.id.expect("stored project to have an id");
+                    let todo = remove_todo(&mut db, id, number).await?;
+                    status!("removed {:?} from {}", todo.text, project.name);
+                }
+            }
+        }
+        Command::Watch => {
+            let mut db = Database::new(&config.database_path.value).await;
+            let mut watcher = SessionWatcher::new(&config);
+            watcher.run(&mut db).await?;
+        }
+        Command::Live { path } => {
+            if let Some(path) = path {
+                let live = LiveStatus::detect(&config);
+                let lock = SafetyLock::new(&live, false);
+                let path = fs::canonicalize(&path)
+                    .map_err(|_| NotFound(format!("no set or project at {}", path.display())))?;
+                if path.is_dir() {
+                    lock.check_project(&path)?;
+                } else {
+                    lock.check_set(&path)?;
+                }
+                status!("{} doesn't look open in Live", path.display());
+            } else {
+                // Sets saved since Live started are looked for in every project.
+                let state = ProgramState::new(&config);
+                if cli.json {
+                    print_json(&state.live)?;
+                } else {
+                    print!("{}", state.live);
+                }
+            }
+        }
+        Command::Browse => {
+            let mut db = Database::new(&config.database_path.value).await;
+            browse(&mut db, &config).await?;
+        }
+        Command::Bench {
+            projects,
+            versions,
+            backups,
+            tracks,
+            directory,
+            jobs,
+        } => {
+            jobs.configure()?;
+            let library = SyntheticLibrary {
+                projects,
+                versions,
+                backups,
+                tracks,
+            };
+            let report = match directory {
+                Some(directory) => bench(&config, &library, &directory).await?,
+                None => {
+                    let directory = std::env::temp_dir()
+                        .join(format!("ableton-v-bench-{}", std::process::id()));
+                    let report = bench(&config, &library, &directory).await;
+                    fs::remove_dir_all(&directory)?;
+                    report?
+                }
+            };
+            if cli.json {
+                print_json(&report)?;
+            } else {
+                print!("{}", report);
+            }
+        }
+        Command::Config { command } => match command {
+            Some(ConfigCommand::Show) | None => {
+                if cli.json {
+                    print_json(&config)?;
+                } else {
+                    print!("{}", config);
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Sets are stored by their canonical path.
+fn resolve_set(path: &Path) -> anyhow::Result<PathBuf> {
+    let resolved =
+        fs::canonicalize(path).map_err(|_| NotFound(format!("no set at {}", path.display())))?;
+    if !resolved.is_file() {
+        return Err(NotFound(format!("{} isn't a set", path.display())).into());
+    }
+    Ok(resolved)
+}
+
+fn resolve_directory(path: &Path) -> anyhow::Result<PathBuf> {
+    let resolved = fs::canonicalize(path)
+        .map_err(|_| NotFound(format!("no project at {}", path.display())))?;
+    if !resolved.is_dir() {
+        return Err(NotFound(format!("{} isn't a project directory", path.display())).into());
+    }
+    Ok(resolved)
+}
+
+/// Prints unused samples with their sizes and a total.
+fn print_unused_samples(unused: &[PathBuf], json: bool) -> anyhow::Result<()> {
+    let sizes: Vec<(&PathBuf, u64)> = unused
+        .iter()
+        .map(|path| {
+            (
+                path,
+                fs::metadata(path)
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0),
+            )
+        })
+        .collect();
+    if json {
+        let files: Vec<_> = sizes
+            .iter()
+            .map(|(path, size)| json!({ "path": path, "size": size }))
+            .collect();
+        return print_json(&files);
+    }
+    for (path, size) in &sizes {
+        println!("{} ({})", path.display(), format_size(*size));
+    }
+    println!(
+        "{} unused files, {}",
+        sizes.len(),
+        format_size(sizes.iter().map(|(_, size)| size).sum())
+    );
+    Ok(())
+}
+
+/// The indexed project named on the command line, with its versions, status, notes and to-dos.
+async fn select_project(
+    db: &mut Database,
+    project: &str,
+) -> anyhow::Result<AbletonProjectDirectory> {
+    let path = resolve_project(db, project).await?;
+    let query = ProjectQuery {
+        path: Some(path.clone()),
+        ..ProjectQuery::default()
+    };
+    select_projects(db, &query)
+        .await?
+        .pop()
+        .ok_or_else(|| NotFound(format!("{} isn't indexed; run scan first", path.display())).into())
+}
+
+/// Projects can be given as a directory or by name, with or without Live's ` Project` suffix.
+/// Names are matched case-insensitively against the indexed projects.
+async fn resolve_project(db: &mut Database, project: &str) -> anyhow::Result<PathBuf> {
+    if Path::new(project).is_dir() {
+        return resolve_directory(Path::new(project));
+    }
+    let matches = select_project_paths_named(db, project).await?;
+    match matches.as_slice() {
+        [] => Err(NotFound(format!("no indexed project named {}", project)).into()),
+        [found] => Ok(found.clone()),
+        _ => {
+            let paths: Vec<String> = matches
+                .iter()
+                .map(|found| found.display().to_string())
+                .collect();
+            anyhow::bail!(
+                "more than one project is named {}, give its directory instead: {}",
+                project,
+                paths.join(", ")
+            )
+        }
+    }
+}
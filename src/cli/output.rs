@@ -0,0 +1,66 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much the program reports besides a command's results. Results go to stdout; everything
+/// reported through the macros below goes to stderr so it never mixes with `--json` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    Quiet,
+    Normal,
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+/// Progress and warnings, hidden by `--quiet`.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if $crate::cli::output::verbosity() >= $crate::cli::output::Verbosity::Normal {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+/// Detail that's only useful when debugging, shown with `--verbose`.
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::cli::output::verbosity() >= $crate::cli::output::Verbosity::Verbose {
+            eprintln!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use status;
+pub(crate) use verbose;
+
+/// Writes a command's result to stdout as pretty-printed JSON.
+pub fn print_json<T: Serialize + ?Sized>(value: &T) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// A progress bar on stderr for `length` steps. It's hidden by `--quiet`, and when stderr isn't a
+/// terminal.
+pub fn progress_bar(length: u64, message: &'static str) -> ProgressBar {
+    if verbosity() == Verbosity::Quiet {
+        return ProgressBar::hidden();
+    }
+    ProgressBar::new(length).with_message(message).with_style(
+        ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} ({per_sec}, {eta} left)")
+            .expect("progress template to be valid")
+            .progress_chars("=> "),
+    )
+}
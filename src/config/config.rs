@@ -0,0 +1,679 @@
+use anyhow::Context;
+use clap::Args;
+use serde::{Deserialize, Serialize, Serializer};
+use std::env;
+use std::ffi::OsString;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const CONFIG_FILE_NAME: &str = "config.toml";
+const APPLICATION_DIRECTORY: &str = "ableton-v";
+
+const CONFIG_ENV: &str = "ABLETON_V_CONFIG";
+const SESSION_ROOTS_ENV: &str = "ABLETON_V_SESSION_ROOTS";
+const LIVE_APPLICATION_ENV: &str = "ABLETON_V_LIVE_PATH";
+const DATABASE_ENV: &str = "ABLETON_V_DATABASE";
+const SNAPSHOTS_ENV: &str = "ABLETON_V_SNAPSHOTS";
+const QUARANTINE_ENV: &str = "ABLETON_V_QUARANTINE";
+const IGNORE_ENV: &str = "ABLETON_V_IGNORE";
+const LIVE_PROCESS_NAMES_ENV: &str = "ABLETON_V_LIVE_PROCESS_NAMES";
+const LIVE_COMMAND_PATTERNS_ENV: &str = "ABLETON_V_LIVE_COMMAND_PATTERNS";
+const USER_LIBRARY_ENV: &str = "ABLETON_V_USER_LIBRARY";
+
+/// The label library files found in the User Library are stored under, in place of a session
+/// root's.
+pub const USER_LIBRARY_LABEL: &str = "user-library";
+
+/// Live's process is `Live` on macOS and named after the edition on Windows, e.g.
+/// `Ableton Live 11 Suite.exe`.
+const DEFAULT_LIVE_PROCESS_NAMES: [&str; 2] = ["Live", "Ableton Live*"];
+/// Under Wine the process is Wine's own, with Live's executable on its command line.
+const DEFAULT_LIVE_COMMAND_PATTERNS: [&str; 1] = ["*Ableton Live*.exe"];
+
+/// Where a configuration value came from, lowest precedence first.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigSource {
+    Default,
+    File(PathBuf),
+    Environment(&'static str),
+    Flag(&'static str),
+}
+
+impl Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "config file {}", path.display()),
+            ConfigSource::Environment(name) => write!(f, "environment variable {}", name),
+            ConfigSource::Flag(name) => write!(f, "flag {}", name),
+        }
+    }
+}
+
+impl Serialize for ConfigSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigValue<T> {
+    pub value: T,
+    pub source: ConfigSource,
+}
+
+impl<T> ConfigValue<T> {
+    fn new(value: T, source: ConfigSource) -> ConfigValue<T> {
+        ConfigValue { value, source }
+    }
+
+    fn set(&mut self, value: Option<T>, source: ConfigSource) {
+        if let Some(value) = value {
+            self.value = value;
+            self.source = source;
+        }
+    }
+}
+
+/// A directory of Live projects. Roots are labelled so projects can be filtered by where they live,
+/// e.g. `internal`, `ssd` or `shared`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionRoot {
+    pub label: String,
+    pub path: PathBuf,
+}
+
+impl SessionRoot {
+    /// Roots without a label are labelled with their directory name.
+    pub fn new(label: Option<String>, path: PathBuf) -> SessionRoot {
+        let label = label.unwrap_or_else(|| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| path.to_string_lossy().into_owned())
+        });
+        SessionRoot { label, path }
+    }
+
+    /// Parses `label=path`, or a bare path.
+    pub fn parse(value: &str) -> SessionRoot {
+        match value.split_once('=') {
+            Some((label, path)) if !label.is_empty() && !label.contains(['/', '\\']) => {
+                SessionRoot::new(Some(String::from(label)), PathBuf::from(path))
+            }
+            _ => SessionRoot::new(None, PathBuf::from(value)),
+        }
+    }
+
+    /// Roots on drives that aren't mounted are offline; their projects stay in the index.
+    pub fn is_online(&self) -> bool {
+        self.path.is_dir()
+    }
+}
+
+impl Display for SessionRoot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}: {}", self.label, self.path.display())
+    }
+}
+
+/// Session roots in the config file are either a path or a table with a label and a path.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum SessionRootEntry {
+    Path(PathBuf),
+    Labelled {
+        label: Option<String>,
+        path: PathBuf,
+    },
+}
+
+/// The effective configuration, layered from defaults, the config file, environment variables
+/// and command line flags, each overriding the last.
+#[derive(Debug, Clone, Serialize)]
+pub struct Config {
+    pub config_file: ConfigValue<PathBuf>,
+    pub session_roots: ConfigValue<Vec<SessionRoot>>,
+    pub live_application_path: ConfigValue<PathBuf>,
+    pub database_path: ConfigValue<PathBuf>,
+    pub snapshot_directory: ConfigValue<PathBuf>,
+    /// Where `samples clean` moves unused samples to, so they can be restored.
+    pub quarantine_directory: ConfigValue<PathBuf>,
+    pub ignore_patterns: ConfigValue<Vec<String>>,
+    /// Globs matched case-insensitively against process names to find a running Live.
+    pub live_process_names: ConfigValue<Vec<String>>,
+    /// Globs matched case-insensitively against a process's program and first argument to find a
+    /// running Live.
+    pub live_command_patterns: ConfigValue<Vec<String>>,
+    /// Live's User Library, whose racks, presets, clips and grooves are indexed along with the
+    /// session roots.
+    pub user_library: ConfigValue<PathBuf>,
+}
+
+/// The layout of `config.toml`. Every key is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    session_roots: Option<Vec<SessionRootEntry>>,
+    live_application_path: Option<PathBuf>,
+    database_path: Option<PathBuf>,
+    snapshot_directory: Option<PathBuf>,
+    quarantine_directory: Option<PathBuf>,
+    ignore_patterns: Option<Vec<String>>,
+    live_process_names: Option<Vec<String>>,
+    live_command_patterns: Option<Vec<String>>,
+    user_library: Option<PathBuf>,
+}
+
+/// Configuration given on the command line. These flags are accepted by every command.
+#[derive(Debug, Default, Args)]
+pub struct ConfigFlags {
+    /// Read settings from this file instead of the default config file
+    #[arg(long = "config", value_name = "FILE", global = true)]
+    pub config_file: Option<PathBuf>,
+    /// Scan this directory for projects, as `label=path` or a bare path (repeatable)
+    #[arg(long = "session-root", value_name = "[LABEL=]PATH", value_parser = parse_session_root, global = true)]
+    pub session_roots: Vec<SessionRoot>,
+    /// Path of the Live application
+    #[arg(long = "live-path", value_name = "PATH", global = true)]
+    pub live_application_path: Option<PathBuf>,
+    /// Path of the index database
+    #[arg(long = "database", value_name = "FILE", global = true)]
+    pub database_path: Option<PathBuf>,
+    /// Directory that `watch` writes snapshots to
+    #[arg(long = "snapshots", value_name = "DIR", global = true)]
+    pub snapshot_directory: Option<PathBuf>,
+    /// Directory that `samples clean` moves unused samples to
+    #[arg(long = "quarantine", value_name = "DIR", global = true)]
+    pub quarantine_directory: Option<PathBuf>,
+    /// Leave out files and directories matching this glob (repeatable)
+    #[arg(long = "ignore", value_name = "PATTERN", global = true)]
+    pub ignore_patterns: Vec<String>,
+    /// Path of Live's User Library
+    #[arg(long = "user-library", value_name = "DIR", global = true)]
+    pub user_library: Option<PathBuf>,
+}
+
+fn parse_session_root(value: &str) -> Result<SessionRoot, String> {
+    Ok(SessionRoot::parse(value))
+}
+
+impl Config {
+    pub fn load(flags: &ConfigFlags) -> anyhow::Result<Config> {
+        Config::load_from(flags, &|name| env::var_os(name))
+    }
+
+    /// Loads the configuration with environment variables read from `environment`. Relative
+    /// paths from the environment and flags are taken from the current directory, and online
+    /// session roots are canonicalised, since the paths of the sets scanned from them are.
+    pub fn load_from(
+        flags: &ConfigFlags,
+        environment: &dyn Fn(&str) -> Option<OsString>,
+    ) -> anyhow::Result<Config> {
+        let mut config = Config::defaults(environment);
+        let current_directory = env::current_dir()?;
+        let resolve = |path: PathBuf| expand_path(&path, &current_directory);
+        config.config_file.set(
+            environment(CONFIG_ENV).map(PathBuf::from).map(resolve),
+            ConfigSource::Environment(CONFIG_ENV),
+        );
+        config.config_file.set(
+            flags.config_file.clone().map(resolve),
+            ConfigSource::Flag("--config"),
+        );
+        config.apply_file()?;
+        config.apply_environment(environment, &resolve);
+        config.apply_flags(flags, &resolve);
+        for root in &mut config.session_roots.value {
+            // Offline roots keep their path as given, so their projects stay under it.
+            if let Ok(path) = fs::canonicalize(&root.path) {
+                root.path = path;
+            }
+        }
+        let roots = &config.session_roots.value;
+        for (index, root) in roots.iter().enumerate() {
+            if root.label == USER_LIBRARY_LABEL {
+                anyhow::bail!(
+                    "session root label {} is kept for the User Library ({})",
+                    root.label,
+                    config.session_roots.source
+                );
+            }
+            if roots[..index].iter().any(|other| other.label == root.label) {
+                anyhow::bail!(
+                    "session root label {} is used more than once ({})",
+                    root.label,
+                    config.session_roots.source
+                );
+            }
+        }
+        Ok(config)
+    }
+
+    fn defaults(environment: &dyn Fn(&str) -> Option<OsString>) -> Config {
+        let home = dirs::home_dir().unwrap_or_default();
+        let data_directory = dirs::data_dir()
+            .unwrap_or_else(|| home.join(".local/share"))
+            .join(APPLICATION_DIRECTORY);
+        // The config file lives in the XDG config directory on every platform.
+        let config_directory = environment("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| home.join(".config"))
+            .join(APPLICATION_DIRECTORY);
+        Config {
+            config_file: ConfigValue::new(
+                config_directory.join(CONFIG_FILE_NAME),
+                ConfigSource::Default,
+            ),
+            session_roots: ConfigValue::new(
+                vec![SessionRoot::new(None, home.join("Ableton Sessions"))],
+                ConfigSource::Default,
+            ),
+            live_application_path: ConfigValue::new(
+                default_live_application_path(),
+                ConfigSource::Default,
+            ),
+            database_path: ConfigValue::new(
+                data_directory.join("sqlite.db"),
+                ConfigSource::Default,
+            ),
+            snapshot_directory: ConfigValue::new(
+                data_directory.join("snapshots"),
+                ConfigSource::Default,
+            ),
+            quarantine_directory: ConfigValue::new(
+                data_directory.join("quarantine"),
+                ConfigSource::Default,
+            ),
+            ignore_patterns: ConfigValue::new(vec![], ConfigSource::Default),
+            live_process_names: ConfigValue::new(
+                DEFAULT_LIVE_PROCESS_NAMES.map(String::from).to_vec(),
+                ConfigSource::Default,
+            ),
+            live_command_patterns: ConfigValue::new(
+                DEFAULT_LIVE_COMMAND_PATTERNS.map(String::from).to_vec(),
+                ConfigSource::Default,
+            ),
+            user_library: ConfigValue::new(default_user_library(&home), ConfigSource::Default),
+        }
+    }
+
+    fn apply_file(&mut self) -> anyhow::Result<()> {
+        let path = self.config_file.value.clone();
+        if !path.is_file() {
+            // Only a config file that was asked for explicitly has to exist.
+            if self.config_file.source != ConfigSource::Default {
+                anyhow::bail!("config file {} doesn't exist", path.display());
+            }
+            return Ok(());
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("couldn't read config file {}", path.display()))?;
+        let file: ConfigFile = toml::from_str(&contents)
+            .with_context(|| format!("couldn't parse config file {}", path.display()))?;
+        let source = ConfigSource::File(path.clone());
+        let relative_to = path.parent().unwrap_or(Path::new("."));
+        let resolve = |path: PathBuf| expand_path(&path, relative_to);
+        self.session_roots.set(
+            file.session_roots.map(|roots| {
+                roots
+                    .into_iter()
+                    .map(|root| match root {
+                        SessionRootEntry::Path(path) => SessionRoot::new(None, resolve(path)),
+                        SessionRootEntry::Labelled { label, path } => {
+                            SessionRoot::new(label, resolve(path))
+                        }
+                    })
+                    .collect()
+            }),
+            source.clone(),
+        );
+        self.live_application_path
+            .set(file.live_application_path.map(resolve), source.clone());
+        self.database_path
+            .set(file.database_path.map(resolve), source.clone());
+        self.snapshot_directory
+            .set(file.snapshot_directory.map(resolve), source.clone());
+        self.quarantine_directory
+            .set(file.quarantine_directory.map(resolve), source.clone());
+        self.ignore_patterns
+            .set(file.ignore_patterns, source.clone());
+        self.live_process_names
+            .set(file.live_process_names, source.clone());
+        self.live_command_patterns
+            .set(file.live_command_patterns, source.clone());
+        self.user_library
+            .set(file.user_library.map(resolve), source);
+        Ok(())
+    }
+
+    fn apply_environment(
+        &mut self,
+        environment: &dyn Fn(&str) -> Option<OsString>,
+        resolve: &dyn Fn(PathBuf) -> PathBuf,
+    ) {
+        self.session_roots.set(
+            environment(SESSION_ROOTS_ENV).map(|roots| {
+                env::split_paths(&roots)
+                    .map(|root| SessionRoot::parse(&root.to_string_lossy()))
+                    .map(|root| SessionRoot {
+                        path: resolve(root.path),
+                        ..root
+                    })
+                    .collect()
+            }),
+            ConfigSource::Environment(SESSION_ROOTS_ENV),
+        );
+        self.live_application_path.set(
+            environment(LIVE_APPLICATION_ENV)
+                .map(PathBuf::from)
+                .map(resolve),
+            ConfigSource::Environment(LIVE_APPLICATION_ENV),
+        );
+        self.database_path.set(
+            environment(DATABASE_ENV).map(PathBuf::from).map(resolve),
+            ConfigSource::Environment(DATABASE_ENV),
+        );
+        self.snapshot_directory.set(
+            environment(SNAPSHOTS_ENV).map(PathBuf::from).map(resolve),
+            ConfigSource::Environment(SNAPSHOTS_ENV),
+        );
+        self.quarantine_directory.set(
+            environment(QUARANTINE_ENV).map(PathBuf::from).map(resolve),
+            ConfigSource::Environment(QUARANTINE_ENV),
+        );
+        self.ignore_patterns.set(
+            environment(IGNORE_ENV)
+                .and_then(|value| value.into_string().ok())
+                .map(|patterns| patterns.split(',').map(String::from).collect()),
+            ConfigSource::Environment(IGNORE_ENV),
+        );
+        self.live_process_names.set(
+            environment(LIVE_PROCESS_NAMES_ENV)
+                .and_then(|value| value.into_string().ok())
+                .map(|names| names.split(',').map(String::from).collect()),
+            ConfigSource::Environment(LIVE_PROCESS_NAMES_ENV),
+        );
+        self.live_command_patterns.set(
+            environment(LIVE_COMMAND_PATTERNS_ENV)
+                .and_then(|value| value.into_string().ok())
+                .map(|patterns| patterns.split(',').map(String::from).collect()),
+            ConfigSource::Environment(LIVE_COMMAND_PATTERNS_ENV),
+        );
+        self.user_library.set(
+            environment(USER_LIBRARY_ENV)
+                .map(PathBuf::from)
+                .map(resolve),
+            ConfigSource::Environment(USER_LIBRARY_ENV),
+        );
+    }
+
+    fn apply_flags(&mut self, flags: &ConfigFlags, resolve: &dyn Fn(PathBuf) -> PathBuf) {
+        self.session_roots.set(
+            Some(
+                flags
+                    .session_roots
+                    .iter()
+                    .map(|root| SessionRoot {
+                        label: root.label.clone(),
+                        path: resolve(root.path.clone()),
+                    })
+                    .collect(),
+            )
+            .filter(|roots: &Vec<SessionRoot>| !roots.is_empty()),
+            ConfigSource::Flag("--session-root"),
+        );
+        self.live_application_path.set(
+            flags.live_application_path.clone().map(resolve),
+            ConfigSource::Flag("--live-path"),
+        );
+        self.database_path.set(
+            flags.database_path.clone().map(resolve),
+            ConfigSource::Flag("--database"),
+        );
+        self.snapshot_directory.set(
+            flags.snapshot_directory.clone().map(resolve),
+            ConfigSource::Flag("--snapshots"),
+        );
+        self.quarantine_directory.set(
+            flags.quarantine_directory.clone().map(resolve),
+            ConfigSource::Flag("--quarantine"),
+        );
+        self.ignore_patterns.set(
+            Some(flags.ignore_patterns.clone()).filter(|patterns| !patterns.is_empty()),
+            ConfigSource::Flag("--ignore"),
+        );
+        self.user_library.set(
+            flags.user_library.clone().map(resolve),
+            ConfigSource::Flag("--user-library"),
+        );
+    }
+
+    /// The User Library as a root that library files are found and reconciled under.
+    pub fn user_library_root(&self) -> SessionRoot {
+        SessionRoot::new(
+            Some(String::from(USER_LIBRARY_LABEL)),
+            self.user_library.value.clone(),
+        )
+    }
+
+    /// The session root that `path` is inside of, if any.
+    pub fn root_of(&self, path: &Path) -> Option<&SessionRoot> {
+        self.session_roots
+            .value
+            .iter()
+            .find(|root| path.starts_with(&root.path))
+    }
+
+    /// Whether a file or directory should be left out of scans and watches.
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        self.ignore_patterns.value.iter().any(|pattern| {
+            glob::Pattern::new(pattern)
+                .is_ok_and(|pattern| pattern.matches(&name) || pattern.matches_path(path))
+        })
+    }
+}
+
+impl Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            f,
+            "config file: {} ({})",
+            self.config_file.value.display(),
+            self.config_file.source
+        )?;
+        writeln!(f, "session roots ({}):", self.session_roots.source)?;
+        for root in &self.session_roots.value {
+            writeln!(f, "    {}", root)?;
+        }
+        writeln!(
+            f,
+            "live application path: {} ({})",
+            self.live_application_path.value.display(),
+            self.live_application_path.source
+        )?;
+        writeln!(
+            f,
+            "database path: {} ({})",
+            self.database_path.value.display(),
+            self.database_path.source
+        )?;
+        writeln!(
+            f,
+            "snapshot directory: {} ({})",
+            self.snapshot_directory.value.display(),
+            self.snapshot_directory.source
+        )?;
+        writeln!(
+            f,
+            "quarantine directory: {} ({})",
+            self.quarantine_directory.value.display(),
+            self.quarantine_directory.source
+        )?;
+        writeln!(f, "ignore patterns ({}):", self.ignore_patterns.source)?;
+        for pattern in &self.ignore_patterns.value {
+            writeln!(f, "    {}", pattern)?;
+        }
+        writeln!(
+            f,
+            "live process names ({}):",
+            self.live_process_names.source
+        )?;
+        for name in &self.live_process_names.value {
+            writeln!(f, "    {}", name)?;
+        }
+        writeln!(
+            f,
+            "live command patterns ({}):",
+            self.live_command_patterns.source
+        )?;
+        for pattern in &self.live_command_patterns.value {
+            writeln!(f, "    {}", pattern)?;
+        }
+        writeln!(
+            f,
+            "user library: {} ({})",
+            self.user_library.value.display(),
+            self.user_library.source
+        )?;
+        Ok(())
+    }
+}
+
+fn default_live_application_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        PathBuf::from(r"C:\ProgramData\Ableton\Live 11 Suite\Program\Ableton Live 11 Suite.exe")
+    } else {
+        PathBuf::from("/Applications/Ableton Live 11 Suite.app")
+    }
+}
+
+/// Where Live puts the User Library unless told otherwise: in Documents on Windows and in Music
+/// everywhere else.
+fn default_user_library(home: &Path) -> PathBuf {
+    let parent = if cfg!(target_os = "windows") {
+        "Documents"
+    } else {
+        "Music"
+    };
+    home.join(parent).join("Ableton").join("User Library")
+}
+
+/// Expands a leading `~` and resolves relative paths against `relative_to`: the config file's
+/// directory for paths from the file, and the current directory for the rest.
+fn expand_path(path: &Path, relative_to: &Path) -> PathBuf {
+    if let Ok(rest) = path.strip_prefix("~") {
+        return dirs::home_dir().unwrap_or_default().join(rest);
+    }
+    if path.is_relative() {
+        return relative_to.join(path);
+    }
+    path.to_path_buf()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(flags: &ConfigFlags, environment: &[(&str, &Path)]) -> Config {
+        let environment: HashMap<String, OsString> = environment
+            .iter()
+            .map(|(name, value)| (String::from(*name), value.as_os_str().to_os_string()))
+            .collect();
+        Config::load_from(flags, &|name| environment.get(name).cloned()).unwrap()
+    }
+
+    #[test]
+    fn flags_override_the_environment_which_overrides_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        for root in ["file", "environment", "flag"] {
+            fs::create_dir(dir.join(root)).unwrap();
+        }
+        let config_file = dir.join("config.toml");
+        fs::write(
+            &config_file,
+            "session_roots = [\"file\"]\ndatabase_path = \"file.db\"\nsnapshot_directory = \"snapshots\"\n",
+        )
+        .unwrap();
+        let roots = dir.join("environment");
+        let database = dir.join("environment.db");
+        let environment = [
+            (CONFIG_ENV, config_file.as_path()),
+            (SESSION_ROOTS_ENV, roots.as_path()),
+            (DATABASE_ENV, database.as_path()),
+        ];
+
+        let config = load(&ConfigFlags::default(), &environment[..1]);
+        assert_eq!(config.session_roots.value[0].path, dir.join("file"));
+        assert_eq!(config.database_path.value, dir.join("file.db"));
+        assert_eq!(
+            config.database_path.source,
+            ConfigSource::File(config_file.clone())
+        );
+
+        let config = load(&ConfigFlags::default(), &environment);
+        assert_eq!(config.session_roots.value[0].path, dir.join("environment"));
+        assert_eq!(config.database_path.value, dir.join("environment.db"));
+        assert_eq!(
+            config.database_path.source,
+            ConfigSource::Environment(DATABASE_ENV)
+        );
+        assert_eq!(config.snapshot_directory.value, dir.join("snapshots"));
+
+        let flags = ConfigFlags {
+            session_roots: vec![SessionRoot::parse(&format!(
+                "main={}",
+                dir.join("flag").display()
+            ))],
+            database_path: Some(dir.join("flag.db")),
+            ..ConfigFlags::default()
+        };
+        let config = load(&flags, &environment);
+        assert_eq!(
+            config.session_roots.value,
+            vec![SessionRoot::new(
+                Some(String::from("main")),
+                dir.join("flag")
+            )]
+        );
+        assert_eq!(config.database_path.value, dir.join("flag.db"));
+        assert_eq!(
+            config.session_roots.source,
+            ConfigSource::Flag("--session-root")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn online_roots_are_canonicalised() {
+        let dir = tempfile::tempdir().unwrap();
+        let sessions = fs::canonicalize(dir.path()).unwrap().join("sessions");
+        fs::create_dir(&sessions).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&sessions, &link).unwrap();
+        let config_file = dir.path().join("config.toml");
+        fs::write(&config_file, "").unwrap();
+        let flags = ConfigFlags {
+            config_file: Some(config_file),
+            session_roots: vec![
+                SessionRoot::parse(&format!("main={}", link.join("..").join("link").display())),
+                SessionRoot::parse("offline=not mounted"),
+            ],
+            ..ConfigFlags::default()
+        };
+
+        let config = load(&flags, &[]);
+        assert_eq!(config.session_roots.value[0].path, sessions);
+        // Relative paths are taken from the current directory, even when they can't be found.
+        assert_eq!(
+            config.session_roots.value[1].path,
+            env::current_dir().unwrap().join("not mounted")
+        );
+    }
+}
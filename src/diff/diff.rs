@@ -0,0 +1,264 @@
+use crate::parser::als::AbletonXmlParser;
+use crate::parser::structs::live_set::{
+    ClipSummary, DeviceSummary, LiveSetContents, LocatorSummary, SampleReference, TrackSummary,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+/// A single difference between two versions of a set.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "change", rename_all = "snake_case")]
+pub enum SetChange {
+    TempoChanged {
+        from: Option<f64>,
+        to: Option<f64>,
+    },
+    TrackAdded {
+        track: TrackSummary,
+    },
+    TrackRemoved {
+        track: TrackSummary,
+    },
+    TrackRenamed {
+        id: String,
+        from: String,
+        to: String,
+    },
+    ClipAdded {
+        track: String,
+        clip: ClipSummary,
+    },
+    ClipRemoved {
+        track: String,
+        clip: ClipSummary,
+    },
+    DeviceAdded {
+        track: String,
+        device: DeviceSummary,
+    },
+    DeviceRemoved {
+        track: String,
+        device: DeviceSummary,
+    },
+    LocatorAdded {
+        locator: LocatorSummary,
+    },
+    LocatorRemoved {
+        locator: LocatorSummary,
+    },
+    SampleAdded {
+        sample: SampleReference,
+    },
+    SampleRemoved {
+        sample: SampleReference,
+    },
+}
+
+impl Display for SetChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        match self {
+            SetChange::TempoChanged { from, to } => write!(
+                f,
+                "~ tempo {} -> {} bpm",
+                from.map(|tempo| tempo.to_string()).unwrap_or_default(),
+                to.map(|tempo| tempo.to_string()).unwrap_or_default()
+            ),
+            SetChange::TrackAdded { track } => {
+                write!(f, "+ track {:?} ({})", track.name, track.kind)
+            }
+            SetChange::TrackRemoved { track } => {
+                write!(f, "- track {:?} ({})", track.name, track.kind)
+            }
+            SetChange::TrackRenamed { from, to, .. } => {
+                write!(f, "~ track {:?} renamed to {:?}", from, to)
+            }
+            SetChange::ClipAdded { track, clip } => {
+                write!(f, "+ clip {:?} on {} at {}", clip.name, track, clip.time)
+            }
+            SetChange::ClipRemoved { track, clip } => {
+                write!(f, "- clip {:?} on {} at {}", clip.name, track, clip.time)
+            }
+            SetChange::DeviceAdded { track, device } => {
+                write!(f, "+ device {} on {}", device.name, track)
+            }
+            SetChange::DeviceRemoved { track, device } => {
+                write!(f, "- device {} on {}", device.name, track)
+            }
+            SetChange::LocatorAdded { locator } => {
+                write!(f, "+ locator {:?} at {}", locator.name, locator.time)
+            }
+            SetChange::LocatorRemoved { locator } => {
+                write!(f, "- locator {:?} at {}", locator.name, locator.time)
+            }
+            SetChange::SampleAdded { sample } => write!(f, "+ sample {}", sample_name(sample)),
+            SetChange::SampleRemoved { sample } => write!(f, "- sample {}", sample_name(sample)),
+        }
+    }
+}
+
+fn sample_name(sample: &SampleReference) -> &str {
+    sample
+        .path
+        .as_deref()
+        .or(sample.relative_path.as_deref())
+        .unwrap_or("(unknown)")
+}
+
+/// What changed between an older and a newer version of a set.
+#[derive(Debug, Serialize)]
+pub struct LiveSetDiff {
+    pub old_path: PathBuf,
+    pub new_path: PathBuf,
+    pub changes: Vec<SetChange>,
+}
+
+impl LiveSetDiff {
+    /// Reads both sets and compares them.
+    pub fn between(old_path: &Path, new_path: &Path) -> anyhow::Result<LiveSetDiff> {
+        let parser = AbletonXmlParser::new();
+        let old = parser.read_contents_of(old_path)?;
+        let new = parser.read_contents_of(new_path)?;
+        Ok(LiveSetDiff {
+            old_path: old_path.to_path_buf(),
+            new_path: new_path.to_path_buf(),
+            changes: compare(&old, &new),
+        })
+    }
+}
+
+impl Display for LiveSetDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(f, "--- {}", self.old_path.display())?;
+        writeln!(f, "+++ {}", self.new_path.display())?;
+        if self.changes.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Tracks are matched by their ids, which Live keeps stable across saves. Clips, devices,
+/// locators and samples have no stable ids, so they're matched by their contents.
+pub fn compare(old: &LiveSetContents, new: &LiveSetContents) -> Vec<SetChange> {
+    let mut changes = vec![];
+    if old.tempo != new.tempo {
+        changes.push(SetChange::TempoChanged {
+            from: old.tempo,
+            to: new.tempo,
+        });
+    }
+    for track in &new.tracks {
+        match old.track(&track.id) {
+            None => changes.push(SetChange::TrackAdded {
+                track: track.clone(),
+            }),
+            Some(old_track) if old_track.name != track.name => {
+                changes.push(SetChange::TrackRenamed {
+                    id: track.id.clone(),
+                    from: old_track.name.clone(),
+                    to: track.name.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for track in &old.tracks {
+        if new.track(&track.id).is_none() {
+            changes.push(SetChange::TrackRemoved {
+                track: track.clone(),
+            });
+        }
+    }
+    let (removed, added) = difference(&old.clips, &new.clips, |clip| {
+        (
+            clip.track_id.clone(),
+            clip.kind.clone(),
+            clip.name.clone(),
+            clip.time.clone(),
+        )
+    });
+    changes.extend(added.into_iter().map(|clip| SetChange::ClipAdded {
+        track: new.track_name(&clip.track_id),
+        clip: clip.clone(),
+    }));
+    changes.extend(removed.into_iter().map(|clip| SetChange::ClipRemoved {
+        track: old.track_name(&clip.track_id),
+        clip: clip.clone(),
+    }));
+    let (removed, added) = difference(&old.devices, &new.devices, |device| {
+        (
+            device.track_id.clone(),
+            device.kind.clone(),
+            device.name.clone(),
+        )
+    });
+    changes.extend(added.into_iter().map(|device| SetChange::DeviceAdded {
+        track: new.track_name(&device.track_id),
+        device: device.clone(),
+    }));
+    changes.extend(removed.into_iter().map(|device| SetChange::DeviceRemoved {
+        track: old.track_name(&device.track_id),
+        device: device.clone(),
+    }));
+    let (removed, added) = difference(&old.locators, &new.locators, |locator| {
+        (locator.name.clone(), locator.time.clone())
+    });
+    changes.extend(added.into_iter().map(|locator| SetChange::LocatorAdded {
+        locator: locator.clone(),
+    }));
+    changes.extend(
+        removed
+            .into_iter()
+            .map(|locator| SetChange::LocatorRemoved {
+                locator: locator.clone(),
+            }),
+    );
+    let (removed, added) = difference(&old.samples, &new.samples, |sample| {
+        String::from(sample_name(sample))
+    });
+    changes.extend(added.into_iter().map(|sample| SetChange::SampleAdded {
+        sample: sample.clone(),
+    }));
+    changes.extend(removed.into_iter().map(|sample| SetChange::SampleRemoved {
+        sample: sample.clone(),
+    }));
+    changes
+}
+
+/// Compares two lists as multisets by `key`, returning the items only in `old` and the items only
+/// in `new`.
+fn difference<'a, T, K: Eq + Hash>(
+    old: &'a [T],
+    new: &'a [T],
+    key: impl Fn(&T) -> K,
+) -> (Vec<&'a T>, Vec<&'a T>) {
+    let mut counts: HashMap<K, i64> = HashMap::new();
+    for item in old {
+        *counts.entry(key(item)).or_default() += 1;
+    }
+    let mut added = vec![];
+    for item in new {
+        let count = counts.entry(key(item)).or_default();
+        if *count > 0 {
+            *count -= 1;
+        } else {
+            added.push(item);
+        }
+    }
+    let mut removed = vec![];
+    for item in old.iter().rev() {
+        let count = counts.entry(key(item)).or_default();
+        if *count > 0 {
+            *count -= 1;
+            removed.push(item);
+        }
+    }
+    removed.reverse();
+    (removed, added)
+}
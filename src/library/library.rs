@@ -0,0 +1,294 @@
+use crate::cli::output::{progress_bar, status};
+use crate::config::config::{Config, SessionRoot};
+use crate::parser::als::AbletonXmlParser;
+use crate::parser::structs::file_kind::FileKind;
+use crate::parser::structs::live_set::LiveSetContents;
+use crate::project::project::read_directory;
+use crate::search::search::{forget_library_file, index_library_file};
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use crate::state::state::IndexChanges;
+use crate::version::version::{
+    format_time, from_epoch_millis, hash_file, serialize_optional_time, serialize_time,
+    to_epoch_millis,
+};
+use rayon::prelude::*;
+use serde::Serialize;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// A rack, preset, clip or groove file.
+#[derive(Debug, Serialize)]
+pub struct LibraryFile {
+    pub path: PathBuf,
+    pub kind: FileKind,
+    /// The file's name without its extension, which is what Live's browser shows.
+    pub name: String,
+    pub root: Option<String>,
+    #[serde(serialize_with = "serialize_time")]
+    pub modified_at: SystemTime,
+    /// The file's size in bytes when it was last scanned.
+    pub size: Option<u64>,
+    /// A SHA-256 of the file's contents when it was last scanned.
+    pub content_hash: Option<String>,
+    /// The Live release that saved the file, e.g. `Ableton Live 11.2.11`.
+    pub creator: Option<String>,
+    /// When a scan stopped finding the file.
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub removed_at: Option<SystemTime>,
+}
+
+impl LibraryFile {
+    /// Builds a library file from its file metadata. Returns None for sets and other files that
+    /// aren't library files.
+    pub fn from_path(path: &Path, root: Option<String>) -> Option<LibraryFile> {
+        let kind = FileKind::from_path(path).filter(FileKind::is_library_file)?;
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(error) => {
+                status!("skipping {}: {}", path.display(), error);
+                return None;
+            }
+        };
+        Some(LibraryFile {
+            path: path.to_path_buf(),
+            kind,
+            name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            root,
+            modified_at: metadata.modified().ok()?,
+            size: Some(metadata.len()),
+            content_hash: None,
+            creator: None,
+            removed_at: None,
+        })
+    }
+
+    /// Whether the file still has the size and modified time it had when `self` was stored.
+    pub fn is_unchanged(&self, size: Option<u64>, modified_at: &SystemTime) -> bool {
+        self.removed_at.is_none()
+            && self.content_hash.is_some()
+            && self.size.is_some()
+            && self.size == size
+            && to_epoch_millis(&self.modified_at) == to_epoch_millis(modified_at)
+    }
+}
+
+impl DatabaseModel for LibraryFile {
+    /// A file that's stored again is no longer removed.
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        sqlx::query("INSERT INTO library_file (path, kind, name, root, modified_at, size, content_hash, creator) VALUES (?, ?, ?, ?, ?, ?, ?, ?) ON CONFLICT (path) DO UPDATE SET kind = excluded.kind, name = excluded.name, root = coalesce(excluded.root, library_file.root), modified_at = excluded.modified_at, size = excluded.size, content_hash = excluded.content_hash, creator = coalesce(excluded.creator, library_file.creator), removed_at = NULL")
+            .bind(self.path.to_string_lossy().into_owned())
+            .bind(self.kind.as_str())
+            .bind(&self.name)
+            .bind(&self.root)
+            .bind(to_epoch_millis(&self.modified_at))
+            .bind(self.size.map(|size| size as i64))
+            .bind(&self.content_hash)
+            .bind(&self.creator)
+    }
+}
+
+impl<'r> FromRow<'r, SqliteRow> for LibraryFile {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let kind: String = row.try_get("kind")?;
+        Ok(LibraryFile {
+            path: PathBuf::from(row.try_get::<String, &str>("path")?),
+            kind: kind
+                .parse()
+                .map_err(|error: anyhow::Error| sqlx::Error::Decode(error.into()))?,
+            name: row.try_get("name")?,
+            root: row.try_get("root")?,
+            modified_at: from_epoch_millis(row.try_get("modified_at")?),
+            size: row
+                .try_get::<Option<i64>, &str>("size")?
+                .map(|size| size as u64),
+            content_hash: row.try_get("content_hash")?,
+            creator: row.try_get("creator")?,
+            removed_at: row
+                .try_get::<Option<i64>, &str>("removed_at")?
+                .map(from_epoch_millis),
+        })
+    }
+}
+
+impl Display for LibraryFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{} {} (modified {})\n    {}",
+            self.kind,
+            self.name,
+            format_time(&self.modified_at),
+            self.path.display()
+        )
+    }
+}
+
+/// Finds the library files anywhere below `dir`, such as Live's User Library, searching
+/// subdirectories in parallel. Sets aren't library files, so the User Library's templates are
+/// left out. Symlinked directories are followed unless they lead back to a directory that's
+/// already being searched.
+pub fn find_library_files(dir: &Path, ancestors: &[PathBuf], config: &Config) -> Vec<PathBuf> {
+    let canonical = match fs::canonicalize(dir) {
+        Ok(canonical) => canonical,
+        Err(error) => {
+            status!("skipping {}: {}", dir.display(), error);
+            return vec![];
+        }
+    };
+    if ancestors.contains(&canonical) {
+        return vec![];
+    }
+    let ancestors = [ancestors, &[canonical]].concat();
+    read_directory(dir)
+        .into_par_iter()
+        .filter(|path| !config.is_ignored(path))
+        .flat_map(|path| {
+            if path.is_dir() {
+                find_library_files(&path, &ancestors, config)
+            } else if FileKind::from_path(&path).is_some_and(|kind| kind.is_library_file()) {
+                vec![path]
+            } else {
+                vec![]
+            }
+        })
+        .collect()
+}
+
+/// Filters for listing indexed library files.
+#[derive(Debug, Default)]
+pub struct LibraryQuery {
+    pub kind: Option<FileKind>,
+    pub root: Option<String>,
+}
+
+/// The indexed library files that haven't been removed, by kind and then name.
+pub async fn select_library_files(
+    db: &mut Database,
+    query: &LibraryQuery,
+) -> anyhow::Result<Vec<LibraryFile>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT * FROM library_file WHERE removed_at IS NULL AND (?1 IS NULL OR kind = ?1) AND (?2 IS NULL OR root = ?2) ORDER BY kind, name COLLATE NOCASE, path")
+                .bind(query.kind.as_ref().map(FileKind::as_str))
+                .bind(&query.root),
+        )
+        .await?;
+    rows.iter()
+        .map(|row| Ok(LibraryFile::from_row(row)?))
+        .collect()
+}
+
+/// Brings the indexed library files up to date with `files`, the ones the last scan found. Like
+/// sets, files whose size and modified time match what's stored are skipped and the rest are read
+/// in parallel. Files no longer found under the online `roots` are marked as removed and taken out
+/// of search.
+pub async fn update_library(
+    db: &mut Database,
+    files: &mut [LibraryFile],
+    roots: &[SessionRoot],
+    changes: &mut IndexChanges,
+) -> anyhow::Result<()> {
+    let stored: HashMap<String, LibraryFile> = db
+        .execute_fetchall(sqlx::query("SELECT * FROM library_file"))
+        .await?
+        .iter()
+        .map(|row| {
+            let file = LibraryFile::from_row(row)?;
+            Ok((file.path.to_string_lossy().into_owned(), file))
+        })
+        .collect::<Result<_, sqlx::Error>>()?;
+    let mut scanned = HashSet::new();
+    let mut pending: Vec<&mut LibraryFile> = vec![];
+    for file in files.iter_mut() {
+        let path = file.path.to_string_lossy().into_owned();
+        let unchanged = stored
+            .get(&path)
+            .is_some_and(|previous| previous.is_unchanged(file.size, &file.modified_at));
+        scanned.insert(path);
+        if unchanged {
+            changes.unchanged += 1;
+        } else {
+            pending.push(file);
+        }
+    }
+
+    let progress = progress_bar(pending.len() as u64, "reading library files");
+    let read_files: Vec<(anyhow::Result<String>, anyhow::Result<LiveSetContents>)> = pending
+        .par_iter()
+        .map(|file| {
+            let read = (
+                hash_file(&file.path),
+                AbletonXmlParser::new().read_contents_of(&file.path),
+            );
+            progress.inc(1);
+            read
+        })
+        .collect();
+    progress.finish_and_clear();
+
+    for (file, (content_hash, contents)) in pending.into_iter().zip(read_files) {
+        let path = file.path.to_string_lossy().into_owned();
+        let content_hash = match content_hash {
+            Ok(content_hash) => content_hash,
+            Err(error) => {
+                status!("couldn't read {}: {:#}", file.path.display(), error);
+                continue;
+            }
+        };
+        let previous = stored.get(&path);
+        // A removed file was taken out of search, so it's indexed again even if it's unchanged.
+        let contents_changed = previous.is_none_or(|previous| {
+            previous.removed_at.is_some() || previous.content_hash.as_ref() != Some(&content_hash)
+        });
+        if previous.is_some() {
+            changes.updated += 1;
+        } else {
+            changes.added += 1;
+        }
+        file.content_hash = Some(content_hash);
+        let contents = match contents {
+            Ok(contents) => Some(contents),
+            Err(error) => {
+                status!("couldn't read {}: {:#}", file.path.display(), error);
+                None
+            }
+        };
+        file.creator = contents
+            .as_ref()
+            .and_then(|contents| contents.creator.clone());
+        db.execute_insert(file.insert_query()).await?;
+        if let Some(contents) = contents.filter(|_| contents_changed) {
+            index_library_file(db, file, &contents).await?;
+        }
+    }
+
+    let removed_at = to_epoch_millis(&SystemTime::now());
+    for root in roots.iter().filter(|root| root.is_online()) {
+        let root_path = root.path.join("").to_string_lossy().into_owned();
+        for (path, file) in &stored {
+            if file.removed_at.is_some()
+                || scanned.contains(path)
+                || !path.starts_with(&root_path)
+                || file.root.as_ref().is_some_and(|label| *label != root.label)
+            {
+                continue;
+            }
+            db.execute_insert(
+                sqlx::query("UPDATE library_file SET removed_at = ? WHERE path = ?")
+                    .bind(removed_at)
+                    .bind(path),
+            )
+            .await?;
+            forget_library_file(db, &file.path).await?;
+            changes.removed += 1;
+        }
+    }
+    Ok(())
+}
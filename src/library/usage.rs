@@ -0,0 +1,212 @@
+use crate::library::library::{select_library_files, LibraryFile, LibraryQuery};
+use crate::parser::structs::file_kind::FileKind;
+use crate::parser::structs::live_set::LiveSetContents;
+use crate::project::project::AbletonProjectDirectory;
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use crate::version::version::ProjectVersion;
+use serde::Serialize;
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+/// The folder Live names its User Library, which preset paths saved on other machines, or before
+/// the library moved, still run through.
+const USER_LIBRARY_DIRECTORY: &str = "user library/";
+
+/// A device in an indexed set that was loaded from a rack or preset file.
+#[derive(Debug)]
+pub struct PresetUse {
+    pub version_path: PathBuf,
+    /// The preset's path as the set stores it, which may be from another machine.
+    pub preset_path: String,
+    pub device: String,
+    pub track: String,
+}
+
+impl DatabaseModel for PresetUse {
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        sqlx::query(
+            "INSERT INTO preset_use (version_path, preset_path, device, track) VALUES (?, ?, ?, ?)",
+        )
+        .bind(self.version_path.to_string_lossy().into_owned())
+        .bind(&self.preset_path)
+        .bind(&self.device)
+        .bind(&self.track)
+    }
+}
+
+/// Replaces the presets recorded for a version with those its set's devices were loaded from.
+pub async fn record_preset_uses(
+    db: &mut Database,
+    version: &ProjectVersion,
+    contents: &LiveSetContents,
+) -> anyhow::Result<()> {
+    db.execute_insert(
+        sqlx::query("DELETE FROM preset_use WHERE version_path = ?")
+            .bind(version.path.to_string_lossy().into_owned()),
+    )
+    .await?;
+    for device in &contents.devices {
+        if let Some(preset_path) = &device.preset {
+            let preset_use = PresetUse {
+                version_path: version.path.clone(),
+                preset_path: preset_path.clone(),
+                device: device.name.clone(),
+                track: contents.track_name(&device.track_id),
+            };
+            db.execute_insert(preset_use.insert_query()).await?;
+        }
+    }
+    Ok(())
+}
+
+/// A version using a library file, and where in the set.
+#[derive(Debug, Serialize)]
+pub struct PresetUser {
+    pub project: Option<String>,
+    pub project_path: Option<PathBuf>,
+    pub version: String,
+    pub version_path: PathBuf,
+    pub track: Option<String>,
+    pub device: String,
+}
+
+impl Display for PresetUser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.version)?;
+        if let Some(project) = &self.project {
+            write!(f, " ({})", project)?;
+        }
+        write!(f, ": {}", self.device)?;
+        if let Some(track) = &self.track {
+            write!(f, " on {}", track)?;
+        }
+        Ok(())
+    }
+}
+
+/// A rack or preset with the indexed versions that use it.
+#[derive(Debug, Serialize)]
+pub struct PresetUsage {
+    pub file: LibraryFile,
+    pub used_by: Vec<PresetUser>,
+}
+
+impl PresetUsage {
+    pub fn is_used(&self) -> bool {
+        !self.used_by.is_empty()
+    }
+
+    /// The names of the projects using the file, in the order they were found.
+    pub fn projects(&self) -> Vec<&str> {
+        let mut seen = HashSet::new();
+        self.used_by
+            .iter()
+            .filter_map(|user| user.project.as_deref())
+            .filter(|project| seen.insert(*project))
+            .collect()
+    }
+}
+
+impl Display for PresetUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{} {} ", self.file.kind, self.file.name)?;
+        if self.is_used() {
+            let projects = self.projects();
+            write!(
+                f,
+                "(used {} times in {} projects: {})",
+                self.used_by.len(),
+                projects.len(),
+                projects.join(", ")
+            )?;
+        } else {
+            write!(f, "(unused)")?;
+        }
+        write!(f, "\n    {}", self.file.path.display())
+    }
+}
+
+/// The indexed racks and presets matching `query`, each with the versions whose devices were
+/// loaded from it. Live only records where racks and presets came from, so clips and grooves
+/// can't be told apart as used or unused. A preset is matched by its path, or by its path inside
+/// a User Library: relative to `user_library`, or to a folder named `User Library`, which catches
+/// sets saved on another machine.
+pub async fn preset_usage(
+    db: &mut Database,
+    query: &LibraryQuery,
+    user_library: &Path,
+) -> anyhow::Result<Vec<PresetUsage>> {
+    if let Some(kind) = query.kind.filter(|kind| !is_preset_kind(*kind)) {
+        anyhow::bail!(
+            "Live doesn't record which {} files a set was made with, only racks and presets",
+            kind
+        );
+    }
+    let mut usages: Vec<PresetUsage> = select_library_files(db, query)
+        .await?
+        .into_iter()
+        .filter(|file| is_preset_kind(file.kind))
+        .map(|file| PresetUsage {
+            file,
+            used_by: vec![],
+        })
+        .collect();
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    for (index, usage) in usages.iter().enumerate() {
+        let path = normalise(&usage.file.path.to_string_lossy());
+        if let Ok(relative) = usage.file.path.strip_prefix(user_library) {
+            by_key.insert(normalise(&relative.to_string_lossy()), index);
+        }
+        if let Some(relative) = library_relative(&path) {
+            by_key.insert(String::from(relative), index);
+        }
+        by_key.insert(path, index);
+    }
+    let rows = db
+        .execute_fetchall(sqlx::query(
+            "SELECT preset_use.*, project_version.name AS version_name, project_version.project_path FROM preset_use JOIN project_version ON project_version.path = preset_use.version_path WHERE project_version.removed_at IS NULL ORDER BY project_version.modified_at DESC",
+        ))
+        .await?;
+    for row in rows.iter() {
+        let preset_path = normalise(&row.try_get::<String, &str>("preset_path")?);
+        let found = by_key
+            .get(&preset_path)
+            .or_else(|| library_relative(&preset_path).and_then(|relative| by_key.get(relative)));
+        let Some(&index) = found else {
+            continue;
+        };
+        let project_path = row
+            .try_get::<Option<String>, &str>("project_path")?
+            .map(PathBuf::from);
+        usages[index].used_by.push(PresetUser {
+            project: project_path
+                .as_deref()
+                .map(AbletonProjectDirectory::name_from_path),
+            project_path,
+            version: row.try_get("version_name")?,
+            version_path: PathBuf::from(row.try_get::<String, &str>("version_path")?),
+            track: row.try_get("track")?,
+            device: row.try_get("device")?,
+        });
+    }
+    Ok(usages)
+}
+
+fn is_preset_kind(kind: FileKind) -> bool {
+    matches!(kind, FileKind::Rack | FileKind::Preset)
+}
+
+/// Paths are compared with forward slashes and without case, since sets made on Windows use
+/// backslashes and both macOS and Windows ignore case.
+fn normalise(path: &str) -> String {
+    path.replace('\\', "/").to_lowercase()
+}
+
+/// The part of a normalised path after its last `User Library` folder.
+fn library_relative(path: &str) -> Option<&str> {
+    path.rfind(USER_LIBRARY_DIRECTORY)
+        .map(|index| &path[index + USER_LIBRARY_DIRECTORY.len()..])
+        .filter(|relative| !relative.is_empty())
+}
@@ -0,0 +1,340 @@
+use crate::cli::output::status;
+use crate::parser::als::AbletonXmlParser;
+use crate::parser::structs::live_set::LiveSetContents;
+use crate::project::project::AbletonProjectDirectory;
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use crate::version::version::serialize_time;
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Versions sharing less than this fraction of their contents are not considered related.
+const MINIMUM_SIMILARITY: f64 = 0.2;
+
+/// Records that `version_path` was derived from `parent_path`.
+#[derive(Debug, Clone, Serialize)]
+pub struct LineageLink {
+    pub project_path: PathBuf,
+    pub version_path: PathBuf,
+    pub parent_path: PathBuf,
+    pub similarity: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct LineageNode {
+    name: String,
+    path: PathBuf,
+    #[serde(serialize_with = "serialize_time")]
+    created_at: SystemTime,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LineageGraph {
+    pub project_name: String,
+    pub project_path: PathBuf,
+    nodes: Vec<LineageNode>,
+    pub links: Vec<LineageLink>,
+}
+
+impl LineageGraph {
+    /// Infers which version each `.als` in the project was derived from. Every version is compared
+    /// with the versions created before it, and its parent is the earlier version whose tracks and
+    /// clips it shares the most of.
+    pub fn infer(project: &AbletonProjectDirectory) -> LineageGraph {
+        let mut versions: Vec<_> = project.versions.iter().collect();
+        versions.sort_by_key(|version| version.created_at.min(version.modified_at));
+        let parser = AbletonXmlParser::new();
+        let contents: Vec<LiveSetContents> = versions
+            .iter()
+            .map(|version| {
+                fs::File::open(&version.path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| parser.read_contents(file))
+                    .unwrap_or_else(|error| {
+                        status!("couldn't read {:?}: {}", version.path, error);
+                        LiveSetContents::default()
+                    })
+            })
+            .collect();
+        let mut links = vec![];
+        for (index, version) in versions.iter().enumerate() {
+            let mut best: Option<(usize, f64)> = None;
+            for candidate in 0..index {
+                let similarity = contents[index].similarity(&contents[candidate]);
+                if similarity < MINIMUM_SIMILARITY {
+                    continue;
+                }
+                // Later candidates win ties, since a copy is usually made from the latest save.
+                if best.is_none_or(|(_, best_similarity)| similarity >= best_similarity) {
+                    best = Some((candidate, similarity));
+                }
+            }
+            if let Some((parent, similarity)) = best {
+                links.push(LineageLink {
+                    project_path: project.path.clone(),
+                    version_path: version.path.clone(),
+                    parent_path: versions[parent].path.clone(),
+                    similarity,
+                });
+            }
+        }
+        LineageGraph {
+            project_name: project.name.clone(),
+            project_path: project.path.clone(),
+            nodes: versions
+                .iter()
+                .map(|version| LineageNode {
+                    name: version.name.clone(),
+                    path: version.path.clone(),
+                    created_at: version.created_at,
+                })
+                .collect(),
+            links,
+        }
+    }
+
+    fn parent_of(&self, node: &LineageNode) -> Option<&LineageLink> {
+        self.links
+            .iter()
+            .find(|link| link.version_path == node.path)
+    }
+
+    fn children_of(&self, node: &LineageNode) -> Vec<(&LineageNode, f64)> {
+        self.links
+            .iter()
+            .filter(|link| link.parent_path == node.path)
+            .filter_map(|link| {
+                self.nodes
+                    .iter()
+                    .find(|child| child.path == link.version_path)
+                    .map(|child| (child, link.similarity))
+            })
+            .collect()
+    }
+
+    fn fmt_children(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        node: &LineageNode,
+        prefix: &str,
+    ) -> Result<(), fmt::Error> {
+        let children = self.children_of(node);
+        for (index, (child, similarity)) in children.iter().enumerate() {
+            let last = index == children.len() - 1;
+            writeln!(
+                f,
+                "{}{} {} ({:.0}% shared)",
+                prefix,
+                if last { "└──" } else { "├──" },
+                child.name,
+                similarity * 100.0
+            )?;
+            let child_prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+            self.fmt_children(f, child, &child_prefix)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for LineageGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            f,
+            "lineage of {} ({:?})",
+            self.project_name, self.project_path
+        )?;
+        for node in &self.nodes {
+            if self.parent_of(node).is_some() {
+                continue;
+            }
+            writeln!(
+                f,
+                "{} (created {})",
+                node.name,
+                DateTime::<Local>::from(node.created_at).format("%Y-%m-%d %H:%M")
+            )?;
+            self.fmt_children(f, node, "")?;
+        }
+        Ok(())
+    }
+}
+
+impl DatabaseModel for LineageLink {
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        sqlx::query("INSERT OR REPLACE INTO version_lineage (project_path, version_path, parent_path, similarity) VALUES (?, ?, ?, ?)")
+            .bind(self.project_path.to_string_lossy().into_owned())
+            .bind(self.version_path.to_string_lossy().into_owned())
+            .bind(self.parent_path.to_string_lossy().into_owned())
+            .bind(self.similarity)
+    }
+}
+
+/// Replaces the stored lineage of the graph's project with the links just inferred.
+pub async fn update_lineage(db: &mut Database, graph: &LineageGraph) -> anyhow::Result<()> {
+    db.execute_insert(
+        sqlx::query("DELETE FROM version_lineage WHERE project_path = ?")
+            .bind(graph.project_path.to_string_lossy().into_owned()),
+    )
+    .await?;
+    for link in &graph.links {
+        db.execute_insert(link.insert_query()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use std::path::Path;
+    use std::time::Duration;
+
+    fn write_set(path: &Path, tracks: &[&str]) {
+        let tracks: String = tracks
+            .iter()
+            .enumerate()
+            .map(|(id, name)| {
+                format!(
+                    r#"<AudioTrack Id="{}"><Name><EffectiveName Value="{}" /></Name></AudioTrack>"#,
+                    id, name
+                )
+            })
+            .collect();
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 11.3"><LiveSet><Tracks>{}</Tracks></LiveSet></Ableton>"#,
+            tracks
+        );
+        let mut encoder = GzEncoder::new(fs::File::create(path).unwrap(), Compression::default());
+        encoder.write_all(xml.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    /// A project whose sets were created a minute apart in the order given, with those tracks.
+    fn project(dir: &Path, sets: &[(&str, &[&str])]) -> AbletonProjectDirectory {
+        for (name, tracks) in sets {
+            write_set(&dir.join(format!("{}.als", name)), tracks);
+        }
+        let mut project = AbletonProjectDirectory::new(dir.to_path_buf(), None);
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        for version in &mut project.versions {
+            let order = sets
+                .iter()
+                .position(|(name, _)| version.path.ends_with(format!("{}.als", name)))
+                .unwrap();
+            version.created_at = start + Duration::from_secs(60 * order as u64);
+            version.modified_at = version.created_at + Duration::from_secs(3600);
+        }
+        project
+    }
+
+    fn parent_of<'a>(graph: &'a LineageGraph, name: &str) -> Option<&'a str> {
+        graph
+            .links
+            .iter()
+            .find(|link| link.version_path.ends_with(format!("{}.als", name)))
+            .map(|link| link.parent_path.file_stem().unwrap().to_str().unwrap())
+    }
+
+    #[test]
+    fn versions_descend_from_the_earlier_set_they_share_most_with() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = project(
+            dir.path(),
+            &[
+                ("Song", &["Drums", "Bass", "Keys"]),
+                ("Song 2", &["Drums", "Bass", "Keys", "Vox"]),
+                ("Song remix", &["Drums", "Bass", "Pads"]),
+                ("Other", &["Choir", "Strings"]),
+                ("Song 2 copy", &["Drums", "Bass", "Keys", "Vox"]),
+            ],
+        );
+
+        let graph = LineageGraph::infer(&project);
+        assert_eq!(parent_of(&graph, "Song"), None);
+        assert_eq!(parent_of(&graph, "Song 2"), Some("Song"));
+        assert_eq!(parent_of(&graph, "Song remix"), Some("Song"));
+        assert_eq!(parent_of(&graph, "Other"), None);
+        assert_eq!(parent_of(&graph, "Song 2 copy"), Some("Song 2"));
+        assert_eq!(graph.links.len(), 3);
+    }
+
+    #[test]
+    fn earlier_sets_never_descend_from_later_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = project(
+            dir.path(),
+            &[
+                ("Sketch", &["Drums", "Bass", "Keys", "Vox"]),
+                ("Song", &["Drums", "Bass", "Keys"]),
+            ],
+        );
+
+        let graph = LineageGraph::infer(&project);
+        assert_eq!(parent_of(&graph, "Sketch"), None);
+        assert_eq!(parent_of(&graph, "Song"), Some("Sketch"));
+    }
+
+    #[test]
+    fn later_sets_win_ties() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = project(
+            dir.path(),
+            &[
+                ("Song", &["Drums", "Bass"]),
+                ("Song copy", &["Drums", "Bass"]),
+                ("Song final", &["Drums", "Bass"]),
+            ],
+        );
+
+        let graph = LineageGraph::infer(&project);
+        assert_eq!(parent_of(&graph, "Song copy"), Some("Song"));
+        assert_eq!(parent_of(&graph, "Song final"), Some("Song copy"));
+    }
+
+    #[test]
+    fn lineage_is_rendered_as_a_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let project = project(
+            dir.path(),
+            &[
+                ("Song", &["Drums", "Bass", "Keys"]),
+                ("Song 2", &["Drums", "Bass", "Keys", "Vox"]),
+                ("Song remix", &["Drums", "Bass", "Pads"]),
+                ("Other", &["Choir", "Strings"]),
+                ("Song 2 copy", &["Drums", "Bass", "Keys", "Vox"]),
+            ],
+        );
+        let created = |name: &str| {
+            let version = project
+                .versions
+                .iter()
+                .find(|version| version.path.ends_with(format!("{}.als", name)))
+                .unwrap();
+            DateTime::<Local>::from(version.created_at)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        };
+
+        let graph = LineageGraph::infer(&project);
+        assert_eq!(
+            graph.to_string(),
+            format!(
+                "lineage of {} ({:?})\n\
+                 Song (created {})\n\
+                 ├── Song 2 (75% shared)\n\
+                 │   └── Song 2 copy (100% shared)\n\
+                 └── Song remix (50% shared)\n\
+                 Other (created {})\n",
+                project.name,
+                project.path,
+                created("Song"),
+                created("Other")
+            )
+        );
+    }
+}
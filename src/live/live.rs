@@ -0,0 +1,450 @@
+use crate::config::config::Config;
+use crate::project::project::{is_als_file, AbletonProjectDirectory};
+use crate::version::version::{format_time, serialize_optional_time, serialize_time};
+use glob::{MatchOptions, Pattern};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use sysinfo::{PidExt, ProcessExt, ProcessRefreshKind, System, SystemExt};
+
+/// Words that follow Live's version number in its application and executable names.
+const LIVE_EDITIONS: [&str; 6] = ["Suite", "Standard", "Intro", "Lite", "Trial", "Beta"];
+
+/// A running process, as far as Live detection is concerned.
+#[derive(Debug, Clone)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    /// The process's arguments, starting with the program.
+    pub command: Vec<String>,
+    pub executable: Option<PathBuf>,
+    pub started_at: SystemTime,
+}
+
+/// Somewhere to list the running processes from. Detection only goes through this, so it can be
+/// given a fixed process list instead of the system's.
+pub trait ProcessSource {
+    fn processes(&self) -> Vec<ProcessInfo>;
+}
+
+/// The processes running on this machine, read through `sysinfo`.
+pub struct SystemProcesses;
+
+impl ProcessSource for SystemProcesses {
+    fn processes(&self) -> Vec<ProcessInfo> {
+        let mut system = System::new();
+        system.refresh_processes_specifics(ProcessRefreshKind::new());
+        system
+            .processes()
+            .values()
+            .map(|process| ProcessInfo {
+                pid: process.pid().as_u32(),
+                name: String::from(process.name()),
+                command: process.cmd().to_vec(),
+                executable: Some(process.exe())
+                    .filter(|exe| !exe.as_os_str().is_empty())
+                    .map(Path::to_path_buf),
+                started_at: SystemTime::UNIX_EPOCH + Duration::from_secs(process.start_time()),
+            })
+            .collect()
+    }
+}
+
+/// A running instance of Live.
+#[derive(Debug, Serialize)]
+pub struct RunningLive {
+    pub pid: u32,
+    pub process_name: String,
+    /// The version from the application's name, e.g. `11 Suite`.
+    pub version: Option<String>,
+    #[serde(serialize_with = "serialize_time")]
+    pub started_at: SystemTime,
+    /// Sets Live was started with, e.g. by opening one from the Windows Explorer.
+    pub command_line_sets: Vec<PathBuf>,
+}
+
+impl Display for RunningLive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "Live {}(pid {}, {}) since {}",
+            self.version
+                .as_ref()
+                .map(|version| format!("{} ", version))
+                .unwrap_or_default(),
+            self.pid,
+            self.process_name,
+            format_time(&self.started_at)
+        )
+    }
+}
+
+/// Finds Live among the running processes by the configured process names and command line
+/// patterns.
+pub struct LiveDetector {
+    process_names: Vec<Pattern>,
+    command_patterns: Vec<Pattern>,
+}
+
+impl LiveDetector {
+    pub fn new(config: &Config) -> LiveDetector {
+        LiveDetector::with_patterns(
+            &config.live_process_names.value,
+            &config.live_command_patterns.value,
+        )
+    }
+
+    /// Patterns that aren't valid globs never match.
+    pub fn with_patterns(process_names: &[String], command_patterns: &[String]) -> LiveDetector {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .filter_map(|pattern| Pattern::new(pattern).ok())
+                .collect()
+        };
+        LiveDetector {
+            process_names: compile(process_names),
+            command_patterns: compile(command_patterns),
+        }
+    }
+
+    pub fn is_live(&self, process: &ProcessInfo) -> bool {
+        let options = MatchOptions {
+            case_sensitive: false,
+            ..MatchOptions::new()
+        };
+        // Only the program and its first argument, which is the program under Wine, so a shell
+        // or editor that merely mentions Live isn't mistaken for it.
+        self.process_names
+            .iter()
+            .any(|pattern| pattern.matches_with(&process.name, options))
+            || process.command.iter().take(2).any(|argument| {
+                self.command_patterns
+                    .iter()
+                    .any(|pattern| pattern.matches_with(argument, options))
+            })
+    }
+
+    /// The running Live started first, if any.
+    pub fn detect(&self, source: &dyn ProcessSource) -> Option<RunningLive> {
+        source
+            .processes()
+            .into_iter()
+            .filter(|process| self.is_live(process))
+            .min_by_key(|process| (process.started_at, process.pid))
+            .map(|process| RunningLive {
+                pid: process.pid,
+                version: live_version(&process),
+                command_line_sets: process
+                    .command
+                    .iter()
+                    .skip(1)
+                    .map(PathBuf::from)
+                    .filter(|path| is_als_file(path))
+                    // Compared with the canonical paths sets are indexed by.
+                    .map(|path| fs::canonicalize(&path).unwrap_or(path))
+                    .collect(),
+                process_name: process.name,
+                started_at: process.started_at,
+            })
+    }
+}
+
+/// Reads the version out of names like `Ableton Live 11 Suite.exe` or
+/// `/Applications/Ableton Live 12 Beta.app/Contents/MacOS/Live`.
+fn live_version(process: &ProcessInfo) -> Option<String> {
+    let executable = process
+        .executable
+        .as_ref()
+        .map(|path| path.to_string_lossy().into_owned());
+    // Process names can be truncated, e.g. to 15 characters on Linux, so they're read last.
+    executable
+        .into_iter()
+        .chain(process.command.iter().cloned())
+        .chain([process.name.clone()])
+        .find_map(|text| version_in(&text))
+}
+
+fn version_in(text: &str) -> Option<String> {
+    text.match_indices("Live ").find_map(|(index, _)| {
+        let after = &text[index + "Live ".len()..];
+        let number: String = after
+            .chars()
+            .take_while(|c| c.is_ascii_digit() || *c == '.')
+            .collect();
+        let number = number.trim_end_matches('.');
+        if number.is_empty() {
+            return None;
+        }
+        let edition = after[number.len()..]
+            .trim_start()
+            .split([' ', '.', '/'])
+            .next()
+            .filter(|word| LIVE_EDITIONS.contains(word));
+        Some(match edition {
+            Some(edition) => format!("{} {}", number, edition),
+            None => String::from(number),
+        })
+    })
+}
+
+/// Where Live is and which sets look open in it. Live doesn't say which set is open, so besides
+/// the sets it was started with, sets saved since it started are taken to be open.
+#[derive(Debug, Serialize)]
+pub struct LiveStatus {
+    pub live: Option<RunningLive>,
+    pub open_sets: Vec<OpenSet>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OpenSet {
+    pub path: PathBuf,
+    /// When the set was last saved, if it wasn't on Live's command line.
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub saved_at: Option<SystemTime>,
+}
+
+impl LiveStatus {
+    pub fn new(live: Option<RunningLive>, projects: &[AbletonProjectDirectory]) -> LiveStatus {
+        let Some(running) = &live else {
+            return LiveStatus {
+                live,
+                open_sets: vec![],
+            };
+        };
+        let mut open_sets: Vec<OpenSet> = running
+            .command_line_sets
+            .iter()
+            .map(|path| OpenSet {
+                path: path.clone(),
+                saved_at: None,
+            })
+            .collect();
+        let mut saved: Vec<OpenSet> = projects
+            .iter()
+            .flat_map(|project| &project.versions)
+            .filter(|version| version.modified_at >= running.started_at)
+            .filter(|version| !running.command_line_sets.contains(&version.path))
+            .map(|version| OpenSet {
+                path: version.path.clone(),
+                saved_at: Some(version.modified_at),
+            })
+            .collect();
+        saved.sort_by_key(|set| Reverse(set.saved_at));
+        open_sets.extend(saved);
+        LiveStatus { live, open_sets }
+    }
+
+    /// Looks for Live among the system's processes without looking through the session roots for
+    /// sets saved since it started, which is enough for `why_open`.
+    pub fn detect(config: &Config) -> LiveStatus {
+        LiveStatus::new(LiveDetector::new(config).detect(&SystemProcesses), &[])
+    }
+
+    /// Why the set at `path` looks open in Live, if it does. Besides the sets found when the status
+    /// was read, a set counts as open if it was saved since Live started.
+    pub fn why_open(&self, path: &Path) -> Option<String> {
+        let live = self.live.as_ref()?;
+        if let Some(set) = self.open_sets.iter().find(|set| set.path == path) {
+            return Some(match &set.saved_at {
+                Some(saved_at) => format!(
+                    "it was saved at {}, after Live started",
+                    format_time(saved_at)
+                ),
+                None => String::from("Live was started with it"),
+            });
+        }
+        let saved_at = fs::metadata(path).ok()?.modified().ok()?;
+        (saved_at >= live.started_at).then(|| {
+            format!(
+                "it was saved at {}, after Live started",
+                format_time(&saved_at)
+            )
+        })
+    }
+}
+
+impl Display for LiveStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        let Some(live) = &self.live else {
+            return writeln!(f, "Live isn't running");
+        };
+        writeln!(f, "{}", live)?;
+        for set in &self.open_sets {
+            match &set.saved_at {
+                Some(saved_at) => writeln!(
+                    f,
+                    "    probably open: {} (saved {})",
+                    set.path.display(),
+                    format_time(saved_at)
+                )?,
+                None => writeln!(f, "    open: {}", set.path.display())?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::version::ProjectVersion;
+
+    struct FakeProcesses(Vec<ProcessInfo>);
+
+    impl ProcessSource for FakeProcesses {
+        fn processes(&self) -> Vec<ProcessInfo> {
+            self.0.clone()
+        }
+    }
+
+    fn process(pid: u32, name: &str, command: &[&str], started_at: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: String::from(name),
+            command: command
+                .iter()
+                .map(|argument| argument.to_string())
+                .collect(),
+            executable: None,
+            started_at: SystemTime::UNIX_EPOCH + Duration::from_secs(started_at),
+        }
+    }
+
+    fn detector() -> LiveDetector {
+        LiveDetector::with_patterns(
+            &[String::from("Live"), String::from("Ableton Live*")],
+            &[String::from("*Ableton Live*.exe")],
+        )
+    }
+
+    #[test]
+    fn process_names_match_globs_ignoring_case() {
+        let detector = detector();
+        assert!(detector.is_live(&process(1, "Live", &[], 0)));
+        assert!(detector.is_live(&process(1, "ableton live 11 suite.exe", &[], 0)));
+        assert!(!detector.is_live(&process(1, "LiveCode", &[], 0)));
+        assert!(!detector.is_live(&process(1, "bash", &["bash"], 0)));
+    }
+
+    #[test]
+    fn wine_processes_match_on_their_command_line() {
+        let detector = detector();
+        let wine = process(
+            1,
+            "wine64-preloader",
+            &[
+                "wine64-preloader",
+                "C:\\ProgramData\\Ableton\\Ableton Live 11 Suite.exe",
+            ],
+            0,
+        );
+        assert!(detector.is_live(&wine));
+        // An editor that merely has Live's path further along its arguments isn't Live.
+        let editor = process(
+            2,
+            "vim",
+            &["vim", "notes.txt", "Ableton Live 11 Suite.exe"],
+            0,
+        );
+        assert!(!detector.is_live(&editor));
+    }
+
+    #[test]
+    fn versions_are_read_from_the_executable_or_command() {
+        let mut mac = process(1, "Live", &["Live"], 0);
+        mac.executable = Some(PathBuf::from(
+            "/Applications/Ableton Live 12 Beta.app/Contents/MacOS/Live",
+        ));
+        assert_eq!(live_version(&mac).as_deref(), Some("12 Beta"));
+        let windows = process(
+            1,
+            "Ableton Live 11",
+            &["C:\\Ableton Live 11.3.4 Suite.exe"],
+            0,
+        );
+        assert_eq!(live_version(&windows).as_deref(), Some("11.3.4 Suite"));
+        assert_eq!(live_version(&process(1, "Live", &["Live"], 0)), None);
+    }
+
+    #[test]
+    fn the_first_live_started_is_detected_with_its_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        let set = dir.path().join("Song.als");
+        fs::write(&set, "").unwrap();
+        let relative = dir.path().join(".").join("Song.als");
+        let processes = FakeProcesses(vec![
+            process(3, "bash", &["bash"], 10),
+            process(
+                7,
+                "Ableton Live 11 Suite.exe",
+                &[
+                    "Ableton Live 11 Suite.exe",
+                    relative.to_str().unwrap(),
+                    "--flag",
+                ],
+                200,
+            ),
+            process(5, "Live", &["Live"], 100),
+        ]);
+        let live = detector().detect(&processes).unwrap();
+        assert_eq!(live.pid, 5);
+        assert!(live.command_line_sets.is_empty());
+
+        let processes = FakeProcesses(processes.0.into_iter().skip(1).take(1).collect());
+        let live = detector().detect(&processes).unwrap();
+        assert_eq!(live.version.as_deref(), Some("11 Suite"));
+        assert_eq!(live.command_line_sets, [fs::canonicalize(&set).unwrap()]);
+        assert!(detector()
+            .detect(&FakeProcesses(vec![process(3, "bash", &["bash"], 10)]))
+            .is_none());
+    }
+
+    #[test]
+    fn sets_saved_since_live_started_look_open() {
+        let started_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1000);
+        let version = |name: &str, modified_at: u64| {
+            ProjectVersion::new(
+                PathBuf::from(format!("/sessions/Song Project/{}.als", name)),
+                started_at,
+                started_at,
+                SystemTime::UNIX_EPOCH + Duration::from_secs(modified_at),
+                None,
+                None,
+            )
+        };
+        let mut project =
+            AbletonProjectDirectory::new(PathBuf::from("/sessions/Song Project"), None);
+        project.versions = vec![version("Old", 500), version("Saved", 1500)];
+        let live = RunningLive {
+            pid: 1,
+            process_name: String::from("Live"),
+            version: None,
+            started_at,
+            command_line_sets: vec![PathBuf::from("/sessions/Other Project/Other.als")],
+        };
+        let status = LiveStatus::new(Some(live), &[project]);
+        let open: Vec<&Path> = status
+            .open_sets
+            .iter()
+            .map(|set| set.path.as_path())
+            .collect();
+        assert_eq!(
+            open,
+            [
+                Path::new("/sessions/Other Project/Other.als"),
+                Path::new("/sessions/Song Project/Saved.als")
+            ]
+        );
+        assert!(status
+            .why_open(Path::new("/sessions/Song Project/Saved.als"))
+            .is_some());
+        assert!(status
+            .why_open(Path::new("/sessions/Song Project/Old.als"))
+            .is_none());
+        assert!(LiveStatus::new(None, &[]).open_sets.is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+use crate::cli::output::status;
+use crate::live::live::LiveStatus;
+use crate::project::project::{is_als_file, read_directory, BACKUP_DIRECTORY};
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+/// A set that looks open in Live, which a command refused to change.
+#[derive(Debug)]
+pub struct SetInUse {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl Display for SetInUse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{} looks open in Live: {}",
+            self.path.display(),
+            self.reason
+        )
+    }
+}
+
+impl std::error::Error for SetInUse {}
+
+/// Stops commands from rewriting a set, or moving files of a project, while Live may have the set
+/// open: Live would later save over the changes, or fail to find files it still refers to.
+pub struct SafetyLock<'a> {
+    live: &'a LiveStatus,
+    /// Go ahead anyway, only warning about sets that look open.
+    force: bool,
+}
+
+impl<'a> SafetyLock<'a> {
+    pub fn new(live: &'a LiveStatus, force: bool) -> SafetyLock<'a> {
+        SafetyLock { live, force }
+    }
+
+    /// Fails with `SetInUse` if the set at `path` looks open in Live.
+    pub fn check_set(&self, path: &Path) -> anyhow::Result<()> {
+        let Some(reason) = self.live.why_open(path) else {
+            return Ok(());
+        };
+        let in_use = SetInUse {
+            path: path.to_path_buf(),
+            reason,
+        };
+        if self.force {
+            status!("warning: {}", in_use);
+            return Ok(());
+        }
+        Err(in_use.into())
+    }
+
+    /// Fails with `SetInUse` if any set in the project directory at `path`, or in its backups,
+    /// looks open in Live.
+    pub fn check_project(&self, path: &Path) -> anyhow::Result<()> {
+        if self.live.live.is_none() {
+            return Ok(());
+        }
+        let backups = Some(path.join(BACKUP_DIRECTORY)).filter(|backups| backups.is_dir());
+        read_directory(path)
+            .into_iter()
+            .chain(backups.iter().flat_map(|backups| read_directory(backups)))
+            .filter(|path| is_als_file(path))
+            .try_for_each(|set| self.check_set(&set))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::live::live::RunningLive;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    fn live_started(ago: Duration) -> LiveStatus {
+        let live = RunningLive {
+            pid: 1,
+            process_name: String::from("Live"),
+            version: None,
+            started_at: SystemTime::now() - ago,
+            command_line_sets: vec![],
+        };
+        LiveStatus::new(Some(live), &[])
+    }
+
+    #[test]
+    fn projects_with_sets_saved_since_live_started_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Song.als"), "").unwrap();
+        let live = live_started(Duration::from_secs(60));
+        let error = SafetyLock::new(&live, false)
+            .check_project(dir.path())
+            .unwrap_err();
+        assert!(error.downcast_ref::<SetInUse>().is_some());
+        assert!(SafetyLock::new(&live, true)
+            .check_project(dir.path())
+            .is_ok());
+    }
+
+    #[test]
+    fn sets_left_alone_since_live_started_are_allowed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("Song.als"), "").unwrap();
+        let live = live_started(Duration::ZERO);
+        std::thread::sleep(Duration::from_millis(10));
+        let lock = SafetyLock::new(&live, false);
+        // Reading a set doesn't make it look open.
+        fs::read(dir.path().join("Song.als")).unwrap();
+        assert!(lock.check_project(dir.path()).is_ok());
+        let not_running = LiveStatus::new(None, &[]);
+        assert!(SafetyLock::new(&not_running, false)
+            .check_project(dir.path())
+            .is_ok());
+    }
+}
@@ -1,24 +1,56 @@
+mod archive;
+mod bench;
+mod cli;
+mod config;
 mod debugging;
+mod diff;
+mod library;
+mod lineage;
+mod live;
 mod parser;
 mod project;
+mod search;
 mod state;
+mod tui;
 mod version;
+mod watch;
 
-use debugging::debugging::get_project_paths;
-use parser::als::AbletonXmlParser;
-use project::project::AbletonProjectDirectory;
-use state::database::Database;
-use std::path::Path;
-use std::fs;
+use clap::Parser;
+use cli::cli::{exit_code, run, Cli, EXIT_SUCCESS, EXIT_USAGE};
+use cli::output::{set_verbosity, Verbosity};
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() {
-    let db = Database::new().await;
-    // let state = ProgramState::new(None, None);
-    let mut paths = get_project_paths(db).await.unwrap();
-    let first = paths.pop().unwrap();
-    println!("reading from {}", first);
-    let file = fs::File::open(&Path::new(&first)).unwrap();
-    let mut parser = AbletonXmlParser::new();
-    parser.parse_xml(file).unwrap();
+async fn main() -> ExitCode {
+    // Exit quietly when piped into something like `head` instead of panicking on a closed stdout.
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGPIPE, libc::SIG_DFL);
+    }
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(error) => {
+            // --help and --version are reported as errors too, but aren't failures.
+            let _ = error.print();
+            return ExitCode::from(if error.use_stderr() {
+                EXIT_USAGE
+            } else {
+                EXIT_SUCCESS
+            });
+        }
+    };
+    set_verbosity(if cli.quiet {
+        Verbosity::Quiet
+    } else if cli.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    });
+    match run(cli).await {
+        Ok(()) => ExitCode::from(EXIT_SUCCESS),
+        Err(error) => {
+            eprintln!("error: {:#}", error);
+            ExitCode::from(exit_code(&error))
+        }
+    }
 }
@@ -1,10 +1,17 @@
 #![allow(dead_code)]
 use crate::parser::structs::ableton::ParserOutput;
+use crate::parser::structs::file_kind::FileKind;
+use crate::parser::structs::live_set::{
+    ClipSummary, DeviceSummary, LiveSetContents, LocatorSummary, SampleReference, TrackSummary,
+    CLIP_ELEMENTS, MAIN_TRACK_ELEMENTS, MAIN_TRACK_ID, PLUGIN_DEVICE_ELEMENTS, TRACK_ELEMENTS,
+};
 use anyhow::Result;
 use flate2::read::GzDecoder;
 use std::collections::LinkedList;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use xml::attribute::OwnedAttribute;
 use xml::reader::{EventReader, XmlEvent};
 
 pub struct AbletonXmlParser<'a> {
@@ -25,12 +32,10 @@ impl<'a> AbletonXmlParser<'a> {
         loop {
             let e = reader.next();
             if e.is_err() {
-                println!("no more events, breaking");
                 break;
             }
             match e {
                 Ok(e) => {
-                    println!("event: {:?}", e);
                     if push_to_tracks_events {
                         if let XmlEvent::EndElement { name, .. } = e.clone() {
                             if name.local_name == "Tracks" {
@@ -48,21 +53,218 @@ impl<'a> AbletonXmlParser<'a> {
                                     self.parse_xml_chunk(&e);
                                 }
                             }
-                            XmlEvent::EndDocument => {
-                                println!("end of document");
-                                break;
-                            }
+                            XmlEvent::EndDocument => break,
                             _ => self.parse_xml_chunk(&e),
                         }
                     }
                 }
                 Err(_) => break,
             }
-            println!("current depth: {}", self.tree.current_depth);
         }
         Ok(())
     }
 
+    /// Reads a summary of a set (tracks, clips, devices, locators and samples) without building
+    /// the full tree.
+    pub fn read_contents(&self, file: File) -> Result<LiveSetContents> {
+        self.read_contents_as(file, FileKind::Set)
+    }
+
+    /// Reads a summary of a file of the given kind. Racks and presets keep their devices under
+    /// `Device` elements rather than in a track's `Devices`, and a native device preset may hold
+    /// its device right under the document's root.
+    pub fn read_contents_as(&self, file: File, kind: FileKind) -> Result<LiveSetContents> {
+        self.read_xml_as(self.parse_to_xml_buffer(file)?, kind)
+    }
+
+    /// Summarises a file of the given kind from its XML, already decompressed.
+    pub fn read_xml_as<R: Read>(&self, xml: R, kind: FileKind) -> Result<LiveSetContents> {
+        let reader = EventReader::new(xml);
+        let mut contents = LiveSetContents {
+            kind,
+            ..LiveSetContents::default()
+        };
+        let mut stack: Vec<String> = vec![];
+        let mut current_track: Option<TrackSummary> = None;
+        let mut current_clip: Option<ClipSummary> = None;
+        // Racks nest devices inside devices, so open devices are kept with the depth they opened at.
+        let mut open_devices: Vec<(usize, DeviceSummary)> = vec![];
+        let mut current_locator: Option<LocatorSummary> = None;
+        let mut current_sample: Option<SampleReference> = None;
+        for e in reader {
+            match e? {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let local_name = name.local_name;
+                    let parent = stack.last().map(String::as_str);
+                    let value = || attribute(&attributes, "Value");
+                    if local_name == "Ableton" {
+                        contents.creator = attribute(&attributes, "Creator");
+                    } else if TRACK_ELEMENTS.contains(&local_name.as_str())
+                        && parent == Some("Tracks")
+                    {
+                        current_track = Some(TrackSummary {
+                            id: attribute(&attributes, "Id").unwrap_or_default(),
+                            kind: local_name.clone(),
+                            name: String::new(),
+                        });
+                    } else if CLIP_ELEMENTS.contains(&local_name.as_str()) {
+                        current_clip = Some(ClipSummary {
+                            track_id: current_track
+                                .as_ref()
+                                .map(|track| track.id.clone())
+                                .unwrap_or_default(),
+                            kind: local_name.clone(),
+                            name: String::new(),
+                            time: attribute(&attributes, "Time").unwrap_or_default(),
+                        });
+                    } else if parent == Some("Devices")
+                        || (kind.is_library_file() && parent == Some("Device"))
+                        || (kind == FileKind::Preset
+                            && parent == Some("Ableton")
+                            && !local_name.ends_with("Preset"))
+                    {
+                        open_devices.push((
+                            stack.len(),
+                            DeviceSummary {
+                                track_id: current_track
+                                    .as_ref()
+                                    .map(|track| track.id.clone())
+                                    .unwrap_or_else(|| String::from(MAIN_TRACK_ID)),
+                                kind: local_name.clone(),
+                                name: local_name.clone(),
+                                preset: None,
+                            },
+                        ));
+                    } else if local_name == "Locator" && parent == Some("Locators") {
+                        current_locator = Some(LocatorSummary {
+                            name: String::new(),
+                            time: String::new(),
+                        });
+                    } else if local_name == "FileRef" && parent == Some("SampleRef") {
+                        current_sample = Some(SampleReference {
+                            path: None,
+                            relative_path: None,
+                        });
+                    } else if local_name == "EffectiveName" && parent == Some("Name") {
+                        let grandparent = stack
+                            .len()
+                            .checked_sub(2)
+                            .and_then(|index| stack.get(index))
+                            .map(String::as_str);
+                        if let Some(track) = current_track.as_mut() {
+                            if grandparent == Some(track.kind.as_str()) {
+                                track.name = value().unwrap_or_default();
+                            }
+                        }
+                    } else if local_name == "Name" {
+                        if let Some(clip) = current_clip.as_mut() {
+                            if parent == Some(clip.kind.as_str()) {
+                                clip.name = value().unwrap_or_default();
+                            }
+                        }
+                        if let Some(locator) = current_locator.as_mut() {
+                            if parent == Some("Locator") {
+                                locator.name = value().unwrap_or_default();
+                            }
+                        }
+                        if matches!(parent, Some("Vst3PluginInfo") | Some("AuPluginInfo")) {
+                            if let Some((_, device)) = open_devices.last_mut() {
+                                device.name = value().unwrap_or_default();
+                            }
+                        }
+                    } else if local_name == "PlugName" {
+                        if let Some((_, device)) = open_devices.last_mut() {
+                            device.name = value().unwrap_or_default();
+                        }
+                    } else if local_name == "UserName" {
+                        if let Some((depth, device)) = open_devices.last_mut() {
+                            let user_name = value().unwrap_or_default();
+                            if *depth + 1 == stack.len()
+                                && !user_name.is_empty()
+                                && !PLUGIN_DEVICE_ELEMENTS.contains(&device.kind.as_str())
+                            {
+                                device.name = user_name;
+                            }
+                        }
+                    } else if local_name == "Time" && parent == Some("Locator") {
+                        if let Some(locator) = current_locator.as_mut() {
+                            locator.time = value().unwrap_or_default();
+                        }
+                    } else if local_name == "Manual"
+                        && parent == Some("Tempo")
+                        && contents.tempo.is_none()
+                        && stack
+                            .iter()
+                            .any(|element| MAIN_TRACK_ELEMENTS.contains(&element.as_str()))
+                    {
+                        contents.tempo = value().and_then(|tempo| tempo.parse().ok());
+                    } else if parent == Some("FileRef") {
+                        if let Some(sample) = current_sample.as_mut() {
+                            match local_name.as_str() {
+                                "Path" => sample.path = value().filter(|path| !path.is_empty()),
+                                "RelativePath" => {
+                                    sample.relative_path = value().filter(|path| !path.is_empty())
+                                }
+                                _ => {}
+                            }
+                        } else if let Some((depth, device)) = open_devices.last_mut() {
+                            // A device's own `LastPresetRef`, not one of a device nested in it.
+                            if local_name == "Path"
+                                && stack.get(*depth + 1).map(String::as_str)
+                                    == Some("LastPresetRef")
+                            {
+                                device.preset = value().filter(|path| !path.is_empty());
+                            }
+                        }
+                    }
+                    stack.push(local_name);
+                }
+                XmlEvent::EndElement { name } => {
+                    stack.pop();
+                    let parent = stack.last().map(String::as_str);
+                    if open_devices
+                        .last()
+                        .is_some_and(|(depth, _)| *depth == stack.len())
+                    {
+                        if let Some((_, device)) = open_devices.pop() {
+                            contents.devices.push(device);
+                        }
+                    } else if CLIP_ELEMENTS.contains(&name.local_name.as_str()) {
+                        if let Some(clip) = current_clip.take() {
+                            contents.clips.push(clip);
+                        }
+                    } else if TRACK_ELEMENTS.contains(&name.local_name.as_str())
+                        && parent == Some("Tracks")
+                    {
+                        if let Some(track) = current_track.take() {
+                            contents.tracks.push(track);
+                        }
+                    } else if name.local_name == "Locator" && parent == Some("Locators") {
+                        if let Some(locator) = current_locator.take() {
+                            contents.locators.push(locator);
+                        }
+                    } else if name.local_name == "FileRef" && parent == Some("SampleRef") {
+                        if let Some(sample) = current_sample.take() {
+                            contents.samples.push(sample);
+                        }
+                    }
+                }
+                XmlEvent::EndDocument => break,
+                _ => {}
+            }
+        }
+        Ok(contents)
+    }
+
+    /// Opens and summarises the set, rack, preset, clip or groove at `path`, telling which it is by
+    /// its extension.
+    pub fn read_contents_of(&self, path: &Path) -> Result<LiveSetContents> {
+        let kind = FileKind::from_path(path).unwrap_or_default();
+        self.read_contents_as(File::open(path)?, kind)
+    }
+
     fn parse_to_xml_buffer(&self, file: File) -> Result<BufReader<GzDecoder<File>>> {
         let decoded = GzDecoder::new(file);
         let buff_daddy = BufReader::new(decoded);
@@ -75,6 +277,13 @@ impl<'a> AbletonXmlParser<'a> {
     }
 }
 
+fn attribute(attributes: &[OwnedAttribute], name: &str) -> Option<String> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name.local_name == name)
+        .map(|attribute| attribute.value.clone())
+}
+
 #[derive(Debug)]
 struct AbletonXmlTree<'a> {
     current_depth: u32,
@@ -107,17 +316,15 @@ impl<'a> AbletonXmlTree<'a> {
 
     fn create_node(&mut self, parser_output: ParserOutput<'a>) {
         let mut parent: Option<&AbletonXmlTreeNode> = None;
-        match self.last_node {
-            Some(node) => {
-                if node.open {
-                    parent = Some(node);
-                } else if node.parent.is_some() {
-                    parent = node.parent;
-                }
+        if let Some(node) = self.last_node {
+            if node.open {
+                parent = Some(node);
+            } else if node.parent.is_some() {
+                parent = node.parent;
             }
-            None => {}
         }
-        let new_node = AbletonXmlTreeNode::new(parser_output, parent, self.nodes.len() as u32);
+        let new_node =
+            AbletonXmlTreeNode::from_output(parser_output, parent, self.nodes.len() as u32);
         match new_node {
             AbletonCreateNodeResponse::AbletonXmlTreeNode(node) => {
                 self.nodes.push(node);
@@ -154,7 +361,7 @@ enum AbletonCreateNodeResponse<'a> {
 }
 
 impl<'a> AbletonXmlTreeNode<'a> {
-    fn new(
+    fn from_output(
         parser_output: ParserOutput<'a>,
         parent: Option<&'a AbletonXmlTreeNode>,
         index: u32,
@@ -204,7 +411,263 @@ impl<'a> OpenNodeStack<'a> {
     }
 
     fn pop(&mut self) -> Option<&'a AbletonXmlTreeNode<'a>> {
-        let val = self.stack.pop_front();
-        val
+        self.stack.pop_front()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(xml: &str, kind: FileKind) -> LiveSetContents {
+        AbletonXmlParser::new()
+            .read_xml_as(xml.as_bytes(), kind)
+            .unwrap()
+    }
+
+    fn set(live_set: &str) -> LiveSetContents {
+        read(
+            &format!(
+                r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 11.3"><LiveSet>{}</LiveSet></Ableton>"#,
+                live_set
+            ),
+            FileKind::Set,
+        )
+    }
+
+    fn track(kind: &str, id: &str, name: &str, inner: &str) -> String {
+        format!(
+            r#"<{kind} Id="{id}"><Name><EffectiveName Value="{name}" /><UserName Value="" /></Name><DeviceChain>{inner}</DeviceChain></{kind}>"#
+        )
+    }
+
+    #[test]
+    fn tracks_are_read_with_their_kind_and_name() {
+        let contents = set(&format!(
+            "<Tracks>{}{}{}{}</Tracks>",
+            track("AudioTrack", "8", "Vox", ""),
+            track("MidiTrack", "9", "Keys", ""),
+            track("GroupTrack", "10", "Drums", ""),
+            track("ReturnTrack", "11", "A-Reverb", "")
+        ));
+
+        assert_eq!(contents.creator.as_deref(), Some("Ableton Live 11.3"));
+        let tracks: Vec<_> = contents
+            .tracks
+            .iter()
+            .map(|track| (track.id.as_str(), track.kind.as_str(), track.name.as_str()))
+            .collect();
+        assert_eq!(
+            tracks,
+            [
+                ("8", "AudioTrack", "Vox"),
+                ("9", "MidiTrack", "Keys"),
+                ("10", "GroupTrack", "Drums"),
+                ("11", "ReturnTrack", "A-Reverb"),
+            ]
+        );
+    }
+
+    #[test]
+    fn clips_are_read_with_their_track_name_and_time() {
+        let contents = set(&format!(
+            "<Tracks>{}{}</Tracks>",
+            track(
+                "AudioTrack",
+                "8",
+                "Vox",
+                r#"<MainSequencer><Sample><ArrangerAutomation><Events><AudioClip Id="0" Time="0"><Name Value="Verse" /></AudioClip><AudioClip Id="1" Time="32"><Name Value="Chorus" /></AudioClip></Events></ArrangerAutomation></Sample></MainSequencer>"#
+            ),
+            track(
+                "MidiTrack",
+                "9",
+                "Keys",
+                r#"<MainSequencer><ClipSlotsListWrapper /><ClipSlotList><ClipSlot Id="0"><ClipSlot><Value><MidiClip Id="0" Time="16"><Name Value="Chords" /></MidiClip></Value></ClipSlot></ClipSlot></ClipSlotList></MainSequencer>"#
+            )
+        ));
+
+        let clips: Vec<_> = contents
+            .clips
+            .iter()
+            .map(|clip| {
+                (
+                    clip.track_id.as_str(),
+                    clip.kind.as_str(),
+                    clip.name.as_str(),
+                    clip.time.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            clips,
+            [
+                ("8", "AudioClip", "Verse", "0"),
+                ("8", "AudioClip", "Chorus", "32"),
+                ("9", "MidiClip", "Chords", "16"),
+            ]
+        );
+        // Clip names don't leak into the track's name.
+        assert_eq!(contents.tracks[0].name, "Vox");
+    }
+
+    #[test]
+    fn tempo_is_read_from_the_main_track() {
+        let tempo = |main: &str| {
+            set(&format!(
+                r#"<Tracks>{}</Tracks><{main}><DeviceChain><Mixer><Tempo><LomId Value="0" /><Manual Value="124.5" /></Tempo></Mixer></DeviceChain></{main}>"#,
+                track(
+                    "AudioTrack",
+                    "8",
+                    "Vox",
+                    r#"<Mixer><Tempo><Manual Value="90" /></Tempo></Mixer>"#
+                )
+            ))
+            .tempo
+        };
+
+        assert_eq!(tempo("MasterTrack"), Some(124.5));
+        assert_eq!(tempo("MainTrack"), Some(124.5));
+        assert_eq!(set("<Tracks />").tempo, None);
+    }
+
+    #[test]
+    fn locators_are_read_with_their_name_and_time() {
+        let contents = set(
+            r#"<Tracks /><Locators><Locators><Locator Id="0"><LomId Value="0" /><Time Value="32" /><Name Value="Drop" /><Annotation Value="" /></Locator><Locator Id="1"><Time Value="64" /><Name Value="Outro" /></Locator></Locators></Locators>"#,
+        );
+
+        let locators: Vec<_> = contents
+            .locators
+            .iter()
+            .map(|locator| (locator.name.as_str(), locator.time.as_str()))
+            .collect();
+        assert_eq!(locators, [("Drop", "32"), ("Outro", "64")]);
+    }
+
+    #[test]
+    fn samples_are_read_with_their_paths() {
+        let contents = set(&format!(
+            "<Tracks>{}</Tracks>",
+            track(
+                "AudioTrack",
+                "8",
+                "Vox",
+                r#"<AudioClip Id="0" Time="0"><Name Value="Take" /><SampleRef><FileRef><RelativePathType Value="3" /><RelativePath Value="Samples/Recorded/take.wav" /><Path Value="/Music/Song Project/Samples/Recorded/take.wav" /></FileRef></SampleRef></AudioClip><AudioClip Id="1" Time="8"><Name Value="Loop" /><SampleRef><FileRef><RelativePath Value="" /><Path Value="/Library/loop.wav" /></FileRef></SampleRef></AudioClip>"#
+            )
+        ));
+
+        let samples: Vec<_> = contents
+            .samples
+            .iter()
+            .map(|sample| (sample.path.as_deref(), sample.relative_path.as_deref()))
+            .collect();
+        assert_eq!(
+            samples,
+            [
+                (
+                    Some("/Music/Song Project/Samples/Recorded/take.wav"),
+                    Some("Samples/Recorded/take.wav")
+                ),
+                (Some("/Library/loop.wav"), None),
+            ]
+        );
+    }
+
+    fn devices(contents: &LiveSetContents) -> Vec<(&str, &str, &str, Option<&str>)> {
+        contents
+            .devices
+            .iter()
+            .map(|device| {
+                (
+                    device.track_id.as_str(),
+                    device.kind.as_str(),
+                    device.name.as_str(),
+                    device.preset.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn devices_are_read_from_tracks_racks_and_plugins() {
+        let contents = set(&format!(
+            r#"<Tracks>{}</Tracks><MainTrack><DeviceChain><DeviceChain><Devices><Limiter Id="0"><UserName Value="" /></Limiter></Devices></DeviceChain></DeviceChain></MainTrack>"#,
+            track(
+                "AudioTrack",
+                "8",
+                "Vox",
+                r#"<DeviceChain><Devices><AudioEffectGroupDevice Id="0"><UserName Value="Vocal Chain" /><Branches><AudioEffectBranch Id="0"><Name Value="Dry" /><DeviceChain><AudioToAudioDeviceChain><Devices><Compressor2 Id="1"><UserName Value="" /><LastPresetRef><Value><FilePresetRef Id="0"><FileRef><RelativePath Value="" /><Path Value="/Presets/Glue.adv" /></FileRef></FilePresetRef></Value></LastPresetRef></Compressor2><Eq8 Id="2"><UserName Value="Air" /></Eq8></Devices></AudioToAudioDeviceChain></DeviceChain></AudioEffectBranch></Branches></AudioEffectGroupDevice><PluginDevice Id="3"><UserName Value="My Synth" /><PluginDesc><Vst3PluginInfo Id="0"><Name Value="Serum" /></Vst3PluginInfo></PluginDesc></PluginDevice><PluginDevice Id="4"><PluginDesc><VstPluginInfo Id="0"><PlugName Value="ValhallaRoom" /></VstPluginInfo></PluginDesc></PluginDevice><AuPluginDevice Id="5"><PluginDesc><AuPluginInfo Id="0"><Name Value="Pro-Q 3" /></AuPluginInfo></PluginDesc></AuPluginDevice></Devices></DeviceChain>"#
+            )
+        ));
+
+        assert_eq!(
+            devices(&contents),
+            [
+                ("8", "Compressor2", "Compressor2", Some("/Presets/Glue.adv")),
+                ("8", "Eq8", "Air", None),
+                ("8", "AudioEffectGroupDevice", "Vocal Chain", None),
+                ("8", "PluginDevice", "Serum", None),
+                ("8", "PluginDevice", "ValhallaRoom", None),
+                ("8", "AuPluginDevice", "Pro-Q 3", None),
+                (MAIN_TRACK_ID, "Limiter", "Limiter", None),
+            ]
+        );
+        // A preset path isn't taken for a sample.
+        assert!(contents.samples.is_empty());
+    }
+
+    #[test]
+    fn racks_are_read_with_the_devices_in_them() {
+        let contents = read(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 11.3"><GroupDevicePreset><OverwriteProtectionNumber Value="2816" /><Device><InstrumentGroupDevice Id="0"><UserName Value="Big Pad" /><Branches><InstrumentBranch Id="0"><DeviceChain><MidiToAudioDeviceChain><Devices><OriginalSimpler Id="0"><UserName Value="" /><Player><MultiSampleMap><SampleParts><MultiSamplePart Id="0"><SampleRef><FileRef><RelativePath Value="Samples/pad.wav" /><Path Value="/Racks/Samples/pad.wav" /></FileRef></SampleRef></MultiSamplePart></SampleParts></MultiSampleMap></Player></OriginalSimpler></Devices></MidiToAudioDeviceChain></DeviceChain></InstrumentBranch></Branches></InstrumentGroupDevice></Device></GroupDevicePreset></Ableton>"#,
+            FileKind::Rack,
+        );
+
+        assert_eq!(contents.kind, FileKind::Rack);
+        assert_eq!(
+            devices(&contents),
+            [
+                (MAIN_TRACK_ID, "OriginalSimpler", "OriginalSimpler", None),
+                (MAIN_TRACK_ID, "InstrumentGroupDevice", "Big Pad", None),
+            ]
+        );
+        assert_eq!(
+            contents.samples[0].path.as_deref(),
+            Some("/Racks/Samples/pad.wav")
+        );
+    }
+
+    #[test]
+    fn presets_are_read_whether_their_device_is_wrapped_or_not() {
+        let wrapped = read(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 11.3"><AbletonDevicePreset><OverwriteProtectionNumber Value="2816" /><Device><Reverb Id="0"><UserName Value="Hall" /></Reverb></Device></AbletonDevicePreset></Ableton>"#,
+            FileKind::Preset,
+        );
+        let bare = read(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 10.1"><Reverb Id="0"><UserName Value="" /></Reverb></Ableton>"#,
+            FileKind::Preset,
+        );
+
+        assert_eq!(devices(&wrapped), [(MAIN_TRACK_ID, "Reverb", "Hall", None)]);
+        assert_eq!(devices(&bare), [(MAIN_TRACK_ID, "Reverb", "Reverb", None)]);
+        assert_eq!(bare.creator.as_deref(), Some("Ableton Live 10.1"));
+    }
+
+    #[test]
+    fn clip_files_and_grooves_are_read_with_their_clips() {
+        let clip = read(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 11.3"><ClipPreset><Value><MidiClip Id="0" Time="0"><Name Value="Bassline" /></MidiClip></Value></ClipPreset></Ableton>"#,
+            FileKind::Clip,
+        );
+        let groove = read(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 11.3"><Groove><Clip><Value><MidiClip Id="0" Time="0"><Name Value="MPC 16 Swing-58" /></MidiClip></Value></Clip><Quantization Value="3" /></Groove></Ableton>"#,
+            FileKind::Groove,
+        );
+
+        assert_eq!(clip.kind, FileKind::Clip);
+        assert_eq!(clip.clips[0].name, "Bassline");
+        assert_eq!(groove.kind, FileKind::Groove);
+        assert_eq!(groove.clips[0].name, "MPC 16 Swing-58");
+        assert!(groove.devices.is_empty() && groove.tracks.is_empty());
     }
 }
@@ -23,10 +23,7 @@ impl<'a> From<&XmlEvent> for ParserOutput<'a> {
                 "Ableton" => ParserOutput::Ableton(Ableton::from(event)),
                 "LiveSet" => ParserOutput::LiveSet(LiveSet::from(event)),
                 "Tracks" => ParserOutput::TracksStart(event.clone()),
-                _ => {
-                    println!("undefined: \n{}\n{:?}", name.local_name, event);
-                    ParserOutput::UnchangedChunk(event.clone())
-                }
+                _ => ParserOutput::UnchangedChunk(event.clone()),
             },
             XmlEvent::EndElement { name, .. } => match name.local_name.as_str() {
                 "Ableton" => ParserOutput::AbletonEnd(event.clone()),
@@ -81,7 +78,7 @@ impl From<&XmlEvent> for Ableton<'_> {
     fn from(event: &XmlEvent) -> Self {
         match event {
             XmlEvent::StartElement { attributes, .. } => Ableton {
-                major_version: attributes.get(0).unwrap().value.clone(),
+                major_version: attributes.first().unwrap().value.clone(),
                 minor_version: attributes.get(1).unwrap().value.clone(),
                 schema_change_count: attributes.get(2).unwrap().value.clone(),
                 creator: attributes.get(3).unwrap().value.clone(),
@@ -0,0 +1,91 @@
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fmt::{self, Display};
+use std::path::Path;
+use std::str::FromStr;
+
+/// The kinds of file Live saves in its gzipped XML format, told apart by their extension.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    /// A Live set, `.als`.
+    #[default]
+    Set,
+    /// A device group or rack, `.adg`.
+    Rack,
+    /// A device preset, `.adv`.
+    Preset,
+    /// A clip, `.alc`.
+    Clip,
+    /// A groove, `.agr`.
+    Groove,
+}
+
+const FILE_KINDS: [FileKind; 5] = [
+    FileKind::Set,
+    FileKind::Rack,
+    FileKind::Preset,
+    FileKind::Clip,
+    FileKind::Groove,
+];
+
+impl FileKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileKind::Set => "set",
+            FileKind::Rack => "rack",
+            FileKind::Preset => "preset",
+            FileKind::Clip => "clip",
+            FileKind::Groove => "groove",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            FileKind::Set => "als",
+            FileKind::Rack => "adg",
+            FileKind::Preset => "adv",
+            FileKind::Clip => "alc",
+            FileKind::Groove => "agr",
+        }
+    }
+
+    /// The kind of file `path` names. Only the last extension counts, so `My.Song.als` is a set
+    /// and `Song.als.bak` isn't anything. The `._Song.als` files macOS leaves on drives without
+    /// extended attributes aren't Live's either.
+    pub fn from_path(path: &Path) -> Option<FileKind> {
+        if path
+            .file_name()
+            .and_then(OsStr::to_str)
+            .is_some_and(|name| name.starts_with("._"))
+        {
+            return None;
+        }
+        let extension = path.extension()?;
+        FILE_KINDS
+            .into_iter()
+            .find(|kind| extension.eq_ignore_ascii_case(kind.extension()))
+    }
+
+    /// Whether this is one of the files Live keeps in a library rather than a set.
+    pub fn is_library_file(&self) -> bool {
+        *self != FileKind::Set
+    }
+}
+
+impl FromStr for FileKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        FILE_KINDS
+            .into_iter()
+            .find(|candidate| candidate.as_str() == kind)
+            .ok_or_else(|| anyhow::anyhow!("unknown file kind {}", kind))
+    }
+}
+
+impl Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.as_str())
+    }
+}
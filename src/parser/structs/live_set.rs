@@ -0,0 +1,242 @@
+#![allow(dead_code)]
+use crate::parser::structs::file_kind::FileKind;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const TRACK_ELEMENTS: [&str; 4] = ["AudioTrack", "MidiTrack", "GroupTrack", "ReturnTrack"];
+pub const CLIP_ELEMENTS: [&str; 2] = ["AudioClip", "MidiClip"];
+/// Live 12 renamed the master track to the main track.
+pub const MAIN_TRACK_ELEMENTS: [&str; 2] = ["MasterTrack", "MainTrack"];
+/// Devices on the main track are recorded with this track id.
+pub const MAIN_TRACK_ID: &str = "main";
+pub const PLUGIN_DEVICE_ELEMENTS: [&str; 2] = ["PluginDevice", "AuPluginDevice"];
+
+/// The parts of a parsed set that describe its contents, used to summarise sets and to compare
+/// them with each other. Racks, presets, clips and grooves are summarised the same way.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LiveSetContents {
+    pub kind: FileKind,
+    /// The Live release that saved the set, e.g. `Ableton Live 11.2.11`.
+    pub creator: Option<String>,
+    pub tempo: Option<f64>,
+    pub tracks: Vec<TrackSummary>,
+    pub clips: Vec<ClipSummary>,
+    pub devices: Vec<DeviceSummary>,
+    pub locators: Vec<LocatorSummary>,
+    pub samples: Vec<SampleReference>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackSummary {
+    pub id: String,
+    pub kind: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipSummary {
+    pub track_id: String,
+    pub kind: String,
+    pub name: String,
+    pub time: String,
+}
+
+/// A native device or plugin. Plugins are named after the plugin, native devices after their
+/// user-given name if they have one.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceSummary {
+    pub track_id: String,
+    pub kind: String,
+    pub name: String,
+    /// The rack or preset file the device was last loaded from, as Live stored its path.
+    pub preset: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LocatorSummary {
+    pub name: String,
+    pub time: String,
+}
+
+/// A sample used by a clip or instrument, as Live stored it.
+#[derive(Debug, Clone, Serialize)]
+pub struct SampleReference {
+    pub path: Option<String>,
+    pub relative_path: Option<String>,
+}
+
+impl SampleReference {
+    /// The file the reference points to: relative to the folder of the project at `project_path`
+    /// first, as Live stores it for sets in the project and in its `Backup` folder alike, then at
+    /// its absolute path. None if neither is a file.
+    pub fn resolve(&self, project_path: &Path) -> Option<PathBuf> {
+        let relative = self
+            .relative_path
+            .as_ref()
+            .map(|relative| project_path.join(relative));
+        relative
+            .into_iter()
+            .chain(self.path.as_ref().map(PathBuf::from))
+            .find(|path| path.is_file())
+            .map(|path| fs::canonicalize(&path).unwrap_or(path))
+    }
+}
+
+impl LiveSetContents {
+    /// Flattens the set into comparable features: track names and clip contents. Track ids are
+    /// small sequential numbers that nearly every set shares, so they're only used alongside a
+    /// track's name.
+    pub fn features(&self) -> HashSet<String> {
+        let mut features = HashSet::new();
+        for track in &self.tracks {
+            features.insert(format!("track:{}:{}", track.kind, track.name));
+        }
+        for clip in &self.clips {
+            features.insert(format!(
+                "clip:{}#{}:{}:{}@{}",
+                self.track_name(&clip.track_id),
+                clip.track_id,
+                clip.kind,
+                clip.name,
+                clip.time
+            ));
+        }
+        features
+    }
+
+    /// Jaccard similarity of the two sets' features, between 0 and 1.
+    pub fn similarity(&self, other: &LiveSetContents) -> f64 {
+        let ours = self.features();
+        let theirs = other.features();
+        let union = ours.union(&theirs).count();
+        if union == 0 {
+            return 0.0;
+        }
+        ours.intersection(&theirs).count() as f64 / union as f64
+    }
+
+    /// The set's track with the given id.
+    pub fn track(&self, id: &str) -> Option<&TrackSummary> {
+        self.tracks.iter().find(|track| track.id == id)
+    }
+
+    /// Display name of the track with the given id, which for devices may be the main track.
+    pub fn track_name(&self, id: &str) -> String {
+        match self.track(id) {
+            Some(track) => track.name.clone(),
+            None if id == MAIN_TRACK_ID => String::from("Main"),
+            None => format!("track {}", id),
+        }
+    }
+
+    pub fn clips_on(&self, track_id: &str) -> Vec<&ClipSummary> {
+        self.clips
+            .iter()
+            .filter(|clip| clip.track_id == track_id)
+            .collect()
+    }
+
+    pub fn devices_on(&self, track_id: &str) -> Vec<&DeviceSummary> {
+        self.devices
+            .iter()
+            .filter(|device| device.track_id == track_id)
+            .collect()
+    }
+
+    fn count_tracks(&self, kind: &str) -> usize {
+        self.tracks
+            .iter()
+            .filter(|track| track.kind == kind)
+            .count()
+    }
+}
+
+impl Display for LiveSetContents {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        if self.kind.is_library_file() {
+            return write!(
+                f,
+                "    kind: {}\n    saved by: {}\n    clips: {}\n    devices: {}\n    samples: {}\n",
+                self.kind,
+                self.creator.as_deref().unwrap_or("unknown"),
+                self.clips.len(),
+                self.devices.len(),
+                self.samples.len()
+            );
+        }
+        writeln!(
+            f,
+            "    saved by: {}",
+            self.creator.as_deref().unwrap_or("unknown")
+        )?;
+        match self.tempo {
+            Some(tempo) => writeln!(f, "    tempo: {} bpm", tempo)?,
+            None => writeln!(f, "    tempo: unknown")?,
+        }
+        writeln!(
+            f,
+            "    tracks: {} ({} audio, {} midi, {} group, {} return)",
+            self.tracks.len(),
+            self.count_tracks("AudioTrack"),
+            self.count_tracks("MidiTrack"),
+            self.count_tracks("GroupTrack"),
+            self.count_tracks("ReturnTrack")
+        )?;
+        writeln!(f, "    clips: {}", self.clips.len())?;
+        writeln!(f, "    devices: {}", self.devices.len())?;
+        writeln!(f, "    samples: {}", self.samples.len())?;
+        write!(f, "    locators: {}", self.locators.len())?;
+        for locator in &self.locators {
+            write!(f, "\n        {} at {}", locator.name, locator.time)?;
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(tracks: &[(&str, &str)], clips: &[(&str, &str)]) -> LiveSetContents {
+        LiveSetContents {
+            tracks: tracks
+                .iter()
+                .map(|(id, name)| TrackSummary {
+                    id: id.to_string(),
+                    kind: String::from("AudioTrack"),
+                    name: name.to_string(),
+                })
+                .collect(),
+            clips: clips
+                .iter()
+                .map(|(track_id, name)| ClipSummary {
+                    track_id: track_id.to_string(),
+                    kind: String::from("AudioClip"),
+                    name: name.to_string(),
+                    time: String::from("0"),
+                })
+                .collect(),
+            ..LiveSetContents::default()
+        }
+    }
+
+    #[test]
+    fn shared_track_ids_alone_are_not_similar() {
+        let ours = set(&[("8", "Drums"), ("9", "Bass")], &[("8", "Loop")]);
+        let theirs = set(&[("8", "Pads"), ("9", "Vocals")], &[("8", "Loop")]);
+        assert_eq!(ours.similarity(&theirs), 0.0);
+    }
+
+    #[test]
+    fn a_saved_copy_is_similar() {
+        let ours = set(&[("8", "Drums"), ("9", "Bass")], &[("8", "Loop")]);
+        let theirs = set(
+            &[("8", "Drums"), ("9", "Bass"), ("10", "Keys")],
+            &[("8", "Loop")],
+        );
+        assert_eq!(ours.similarity(&theirs), 0.75);
+    }
+}
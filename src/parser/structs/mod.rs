@@ -1 +1,3 @@
 pub mod ableton;
+pub mod file_kind;
+pub mod live_set;
@@ -14,15 +14,13 @@ pub fn parse_xml_event_names(xml: BufReader<GzDecoder<File>>) -> std::io::Result
         match e {
             Ok(XmlEvent::StartElement { name, .. }) => {
                 current_depth += 1;
-                let entry = depth_map.entry(current_depth).or_insert_with(Vec::new);
+                let entry = depth_map.entry(current_depth).or_default();
                 if !entry.contains(&name.local_name) {
                     entry.push(name.local_name.clone());
                 }
             }
             Ok(XmlEvent::EndElement { .. }) => {
-                if current_depth > 0 {
-                    current_depth -= 1;
-                }
+                current_depth = current_depth.saturating_sub(1);
             }
             Err(e) => {
                 panic!("Error: {}", e);
@@ -0,0 +1,230 @@
+use crate::cli::output::{progress_bar, status};
+use crate::project::project::{
+    is_als_file, read_directory, AbletonProjectDirectory, BACKUP_DIRECTORY, PROJECT_INFO_DIRECTORY,
+    SAMPLES_DIRECTORY,
+};
+use crate::project::samples::{files_below, unused_samples, SampleReferences};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Where Live keeps recorded audio, below the project's Samples folder.
+const RECORDED_DIRECTORY: &str = "Recorded";
+/// Where Live copies samples into a project, `Collected` before Live 9.
+const IMPORTED_DIRECTORIES: [&str; 2] = ["Imported", "Collected"];
+/// Where Live renders frozen tracks and consolidated, cropped and reversed clips.
+const PROCESSED_DIRECTORY: &str = "Processed";
+
+/// How much disk space a project takes, split by what the files are for.
+#[derive(Debug, Default, Serialize)]
+pub struct DiskUsage {
+    pub project: String,
+    pub path: PathBuf,
+    /// Everything in the project's folder, in bytes.
+    pub total: u64,
+    pub sets: u64,
+    pub backups: u64,
+    pub recorded: u64,
+    pub imported: u64,
+    /// Freeze and consolidate files, and the other audio Live renders into `Samples/Processed`.
+    pub processed: u64,
+    pub other: u64,
+    /// Samples the project's sets refer to outside its folder, each counted once.
+    pub external_samples: u64,
+    /// Samples the project's sets refer to that can't be found.
+    pub missing_samples: usize,
+    /// Audio under `Samples` that no set or backup refers to, which could be cleaned up.
+    pub reclaimable: u64,
+}
+
+impl DiskUsage {
+    /// Measures the files of the project, as `project_files` finds them, as they are now.
+    pub fn measure(project: &AbletonProjectDirectory) -> anyhow::Result<DiskUsage> {
+        let path = fs::canonicalize(&project.path)?;
+        let mut usage = DiskUsage {
+            project: project.name.clone(),
+            path: path.clone(),
+            ..DiskUsage::default()
+        };
+        let files = project_files(project, &path);
+        for file in &files {
+            let size = fs::symlink_metadata(file)
+                .map(|metadata| metadata.len())
+                .unwrap_or(0);
+            usage.total += size;
+            let relative = file.strip_prefix(&path).unwrap_or(file);
+            *usage.category(relative) += size;
+        }
+
+        let references = SampleReferences::read(project);
+        usage.external_samples = references
+            .external(&path)
+            .filter_map(|sample| fs::metadata(sample).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        usage.missing_samples = references.missing.len();
+        match unused_samples(project, &references) {
+            Ok(unused) => {
+                usage.reclaimable = unused
+                    .iter()
+                    .filter_map(|sample| fs::metadata(sample).ok())
+                    .map(|metadata| metadata.len())
+                    .sum()
+            }
+            Err(error) => status!("{}: {:#}", project.name, error),
+        }
+        Ok(usage)
+    }
+
+    /// The count a file at `relative` to the project's folder adds to.
+    fn category(&mut self, relative: &Path) -> &mut u64 {
+        let parts: Vec<&str> = relative
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(part) => part.to_str(),
+                _ => None,
+            })
+            .collect();
+        match parts.as_slice() {
+            [BACKUP_DIRECTORY, ..] if is_als_file(relative) => &mut self.backups,
+            [SAMPLES_DIRECTORY, RECORDED_DIRECTORY, ..] => &mut self.recorded,
+            [SAMPLES_DIRECTORY, directory, ..] if IMPORTED_DIRECTORIES.contains(directory) => {
+                &mut self.imported
+            }
+            [SAMPLES_DIRECTORY, PROCESSED_DIRECTORY, ..] => &mut self.processed,
+            _ if is_als_file(relative) => &mut self.sets,
+            _ => &mut self.other,
+        }
+    }
+}
+
+impl Display for DiskUsage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        writeln!(
+            f,
+            "{} ({}, {} reclaimable)\n    {}",
+            self.project,
+            format_size(self.total),
+            format_size(self.reclaimable),
+            self.path.display()
+        )?;
+        writeln!(
+            f,
+            "    sets {}, backups {}, recorded {}, imported {}, freeze/consolidate {}, other {}",
+            format_size(self.sets),
+            format_size(self.backups),
+            format_size(self.recorded),
+            format_size(self.imported),
+            format_size(self.processed),
+            format_size(self.other)
+        )?;
+        write!(
+            f,
+            "    external samples {}",
+            format_size(self.external_samples)
+        )?;
+        if self.missing_samples > 0 {
+            write!(f, " ({} missing)", self.missing_samples)?;
+        }
+        writeln!(f)
+    }
+}
+
+/// The files that make up the project whose folder is at `path`. A folder of loose sets only has
+/// its own files and Live's folders in it, since its other subfolders may hold projects of their
+/// own.
+pub fn project_files(project: &AbletonProjectDirectory, path: &Path) -> Vec<PathBuf> {
+    if !project.is_loose() {
+        return files_below(path);
+    }
+    let mut files = vec![];
+    for entry in read_directory(path) {
+        let name = entry.file_name().and_then(|name| name.to_str());
+        if entry.is_file() {
+            files.push(entry);
+        } else if matches!(
+            name,
+            Some(BACKUP_DIRECTORY | SAMPLES_DIRECTORY | PROJECT_INFO_DIRECTORY)
+        ) {
+            files.extend(files_below(&entry));
+        }
+    }
+    files
+}
+
+/// Measures the projects whose folders are there in parallel, largest first. Projects that can't
+/// be measured, e.g. on a drive that isn't mounted, are reported and left out.
+pub fn measure_projects(projects: &[AbletonProjectDirectory]) -> Vec<DiskUsage> {
+    let progress = progress_bar(projects.len() as u64, "measuring projects");
+    let mut usages: Vec<DiskUsage> = projects
+        .par_iter()
+        .filter_map(|project| {
+            let usage = DiskUsage::measure(project);
+            progress.inc(1);
+            match usage {
+                Ok(usage) => Some(usage),
+                Err(error) => {
+                    status!("skipping {}: {:#}", project.path.display(), error);
+                    None
+                }
+            }
+        })
+        .collect();
+    progress.finish_and_clear();
+    usages.sort_by(|a, b| b.total.cmp(&a.total).then_with(|| a.path.cmp(&b.path)));
+    usages
+}
+
+/// Formats a size in bytes with decimal units, e.g. `1.2 GB`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1000 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1000.0 {
+            break;
+        }
+        size /= 1000.0;
+        unit = next;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::samples::tests::write_set;
+
+    #[test]
+    fn samples_only_backups_use_are_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Song Project");
+        fs::create_dir_all(path.join("Samples/Recorded")).unwrap();
+        fs::write(path.join("Samples/Recorded/take.wav"), "take").unwrap();
+        fs::write(path.join("Samples/Recorded/first take.wav"), "first take").unwrap();
+        write_set(
+            &path.join("Song.als"),
+            &[(Path::new("/nowhere/take.wav"), "Samples/Recorded/take.wav")],
+        );
+        fs::create_dir_all(path.join("Backup")).unwrap();
+        write_set(
+            &path.join("Backup/Song [2023-06-01 153012].als"),
+            &[(
+                Path::new("/nowhere/first take.wav"),
+                "Samples/Recorded/first take.wav",
+            )],
+        );
+        let project = AbletonProjectDirectory::new(fs::canonicalize(&path).unwrap(), None);
+
+        let usage = DiskUsage::measure(&project).unwrap();
+        assert_eq!(usage.missing_samples, 0);
+        assert_eq!(usage.reclaimable, 0);
+        assert_eq!(usage.recorded, 14);
+        assert!(usage.backups > 0 && usage.sets > 0);
+    }
+}
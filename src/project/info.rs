@@ -0,0 +1,68 @@
+use crate::project::project::{read_directory, PROJECT_INFO_DIRECTORY};
+use crate::version::version::serialize_optional_time;
+use serde::Serialize;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Audio formats Live can keep a project preview in.
+const PREVIEW_EXTENSIONS: [&str; 6] = ["wav", "aif", "aiff", "flac", "ogg", "mp3"];
+
+/// What Live keeps in a project's `Ableton Project Info` folder. Live writes the folder when it
+/// creates a project, so it's what sets a project apart from a folder that sets were copied into.
+#[derive(Debug, Default, Serialize)]
+pub struct ProjectInfo {
+    /// The project format named by the `.cfg` file, e.g. `8_1` for `Project8_1.cfg`. Live has
+    /// kept writing `8_1` since Live 8, so it dates the project rather than the Live it was made in.
+    /// The file's contents are an undocumented binary format, so its name is all that's read
+    /// from it.
+    pub format: Option<String>,
+    /// When Live created the project: when it wrote the `.cfg` file.
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub created_at: Option<SystemTime>,
+    /// An audio preview of the project, if one was saved with it.
+    pub preview: Option<PathBuf>,
+}
+
+impl ProjectInfo {
+    /// Reads the info folder of the project at `project_path`, if it has one.
+    pub fn read(project_path: &Path) -> Option<ProjectInfo> {
+        let directory = project_path.join(PROJECT_INFO_DIRECTORY);
+        if !directory.is_dir() {
+            return None;
+        }
+        let mut info = ProjectInfo::default();
+        let mut entries = read_directory(&directory);
+        entries.sort();
+        for path in entries {
+            let extension = path
+                .extension()
+                .and_then(OsStr::to_str)
+                .map(str::to_lowercase);
+            match extension.as_deref() {
+                Some("cfg") if info.format.is_none() => {
+                    info.format = path
+                        .file_stem()
+                        .and_then(OsStr::to_str)
+                        .and_then(|stem| stem.strip_prefix("Project"))
+                        .filter(|format| !format.is_empty())
+                        .map(String::from);
+                    info.created_at = fs::metadata(&path).ok().and_then(|metadata| {
+                        [metadata.created(), metadata.modified()]
+                            .into_iter()
+                            .filter_map(Result::ok)
+                            .min()
+                    });
+                }
+                Some(extension)
+                    if info.preview.is_none() && PREVIEW_EXTENSIONS.contains(&extension) =>
+                {
+                    info.preview = Some(path);
+                }
+                _ => {}
+            }
+        }
+        Some(info)
+    }
+}
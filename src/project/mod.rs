@@ -1 +1,8 @@
+pub mod disk;
+pub mod info;
+#[allow(clippy::module_inception)]
 pub mod project;
+pub mod quarantine;
+pub mod samples;
+pub mod status;
+pub mod todo;
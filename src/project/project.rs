@@ -1,79 +1,563 @@
-use crate::version::version::ProjectVersion;
+use crate::cli::output::status;
+use crate::parser::structs::file_kind::FileKind;
+use crate::project::info::ProjectInfo;
+use crate::project::status::ProjectStatus;
+use crate::project::todo::{select_todos, ProjectTodo};
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use crate::version::version::{
+    format_time, from_epoch_millis, select_versions, to_epoch_millis, ProjectVersion, VersionQuery,
+};
+use serde::Serialize;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::ffi::OsStr;
 use std::fmt::{self, Display};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-pub fn has_als_files(path: PathBuf) -> bool {
-    if !path.as_path().is_dir() {
-        return false;
-    }
-    let paths = fs::read_dir(path).unwrap();
-    for path in paths {
-        let path_str = path.unwrap().file_name().into_string().unwrap();
-        let parts = path_str.split(".").collect::<Vec<&str>>();
-        if parts.len() == 1 {
-            continue;
+pub const BACKUP_DIRECTORY: &str = "Backup";
+pub const SAMPLES_DIRECTORY: &str = "Samples";
+pub const PROJECT_INFO_DIRECTORY: &str = "Ableton Project Info";
+
+/// Directories Live creates inside a project. They never hold projects of their own, and Samples
+/// can be large, so discovery doesn't look inside them.
+const PROJECT_SUBDIRECTORIES: [&str; 3] =
+    [BACKUP_DIRECTORY, SAMPLES_DIRECTORY, PROJECT_INFO_DIRECTORY];
+
+/// Whether `path` names a Live set.
+pub fn is_als_file(path: &Path) -> bool {
+    FileKind::from_path(path) == Some(FileKind::Set)
+}
+
+pub fn is_project_subdirectory(path: &Path) -> bool {
+    path.file_name()
+        .and_then(OsStr::to_str)
+        .is_some_and(|name| PROJECT_SUBDIRECTORIES.contains(&name))
+}
+
+/// The paths of the entries in `dir`. Entries that can't be read and names that aren't valid
+/// UTF-8, which couldn't be stored in the index, are reported and left out.
+pub fn read_directory(dir: &Path) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(error) => {
+            status!("skipping {}: {}", dir.display(), error);
+            return vec![];
         }
-        if parts[1] == "als" {
-            return true;
+    };
+    let mut paths = vec![];
+    for entry in entries {
+        match entry {
+            Ok(entry) if entry.file_name().to_str().is_none() => {
+                status!(
+                    "skipping {}: the name isn't valid UTF-8",
+                    entry.path().display()
+                );
+            }
+            Ok(entry) => paths.push(entry.path()),
+            Err(error) => status!("skipping an entry in {}: {}", dir.display(), error),
         }
     }
-    false
+    paths
 }
 
+#[derive(Serialize)]
 pub struct AbletonProjectDirectory {
+    /// The id of the stored project, which stays the same when its folder is renamed or moved.
+    pub id: Option<i64>,
     pub name: String,
     pub path: PathBuf,
+    /// The label of the session root the project was found in.
+    pub root: Option<String>,
     pub versions: Vec<ProjectVersion>,
+    pub backups: Vec<ProjectVersion>,
+    /// What the project's `Ableton Project Info` folder says, if it has one.
+    pub info: Option<ProjectInfo>,
+    pub status: Option<ProjectStatus>,
+    pub notes: Option<String>,
+    pub todos: Vec<ProjectTodo>,
 }
 
 impl AbletonProjectDirectory {
-    pub fn new(path_buf: PathBuf) -> AbletonProjectDirectory {
+    pub fn new(path_buf: PathBuf, root: Option<String>) -> AbletonProjectDirectory {
         let path = path_buf.as_path();
-        let mut name = String::from(path.file_name().unwrap().to_str().unwrap());
-        if name.ends_with(" Project") {
-            name = String::from(name.strip_suffix(" Project").unwrap());
-        }
-        let dir = fs::read_dir(path);
-        let mut versions = vec![];
-        for entry in dir.unwrap() {
-            let path = entry.as_ref().unwrap().path();
-            let parts: Vec<&str> = path.to_str().unwrap().split(".").collect();
-            if parts.len() == 1 {
+        let name = AbletonProjectDirectory::name_from_path(path);
+        let versions = read_als_files(path)
+            .into_iter()
+            .map(|(path, created_at, accessed_at, modified_at, size)| {
+                let mut version =
+                    ProjectVersion::new(path, created_at, accessed_at, modified_at, None, None);
+                version.root = root.clone();
+                version.size = Some(size);
+                version
+            })
+            .collect();
+        let mut backups: Vec<ProjectVersion> = read_als_files(&path.join(BACKUP_DIRECTORY))
+            .into_iter()
+            .filter_map(|(path, created_at, accessed_at, modified_at, size)| {
+                let mut backup =
+                    ProjectVersion::from_backup(path, created_at, accessed_at, modified_at)?;
+                backup.root = root.clone();
+                backup.size = Some(size);
+                Some(backup)
+            })
+            .collect();
+        backups.sort_by_key(|backup| backup.backed_up_at);
+        AbletonProjectDirectory {
+            id: None,
+            name,
+            info: ProjectInfo::read(path),
+            path: path_buf,
+            root,
+            versions,
+            backups,
+            status: None,
+            notes: None,
+            todos: vec![],
+        }
+    }
+
+    /// Live names project folders `<name> Project`; the project's name drops the suffix.
+    pub fn name_from_path(path: &Path) -> String {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match name.strip_suffix(" Project") {
+            Some(name) => String::from(name),
+            None => name,
+        }
+    }
+
+    /// Whether the directory is a folder of loose sets rather than a project Live created, which
+    /// would have an `Ableton Project Info` folder.
+    pub fn is_loose(&self) -> bool {
+        self.info.is_none()
+    }
+
+    /// Backups Live took of the version with the given name, oldest first.
+    pub fn backups_of(&self, version_name: &str) -> Vec<&ProjectVersion> {
+        self.backups
+            .iter()
+            .filter(|backup| backup.backup_of.as_deref() == Some(version_name))
+            .collect()
+    }
+
+    /// Backups whose base version is no longer in the project directory.
+    pub fn orphaned_backups(&self) -> Vec<&ProjectVersion> {
+        self.backups
+            .iter()
+            .filter(|backup| {
+                !self
+                    .versions
+                    .iter()
+                    .any(|version| backup.backup_of.as_deref() == Some(version.name.as_str()))
+            })
+            .collect()
+    }
+}
+
+/// Filters for selecting stored projects, which are returned most recently modified first.
+#[derive(Debug, Default)]
+pub struct ProjectQuery {
+    /// Only select the project stored at this path.
+    pub path: Option<PathBuf>,
+    pub root: Option<String>,
+    pub modified_since: Option<SystemTime>,
+    pub modified_until: Option<SystemTime>,
+    pub status: Option<ProjectStatus>,
+    /// Only select folders of loose sets.
+    pub loose: bool,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for AbletonProjectDirectory {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(AbletonProjectDirectory {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            path: PathBuf::from(row.try_get::<String, &str>("path")?),
+            root: row.try_get("root")?,
+            versions: vec![],
+            backups: vec![],
+            info: match row.try_get("loose")? {
+                true => None,
+                false => Some(ProjectInfo {
+                    format: row.try_get("project_format")?,
+                    created_at: row
+                        .try_get::<Option<i64>, &str>("created_at")?
+                        .map(from_epoch_millis),
+                    preview: row
+                        .try_get::<Option<String>, &str>("preview_path")?
+                        .map(PathBuf::from),
+                }),
+            },
+            status: row
+                .try_get::<Option<String>, &str>("status")?
+                .map(|status| status.parse())
+                .transpose()
+                .map_err(|error: anyhow::Error| sqlx::Error::Decode(error.into()))?,
+            notes: row.try_get("notes")?,
+            todos: vec![],
+        })
+    }
+}
+
+/// Loads the stored projects matching `query`, with their versions and backups.
+pub async fn select_projects(
+    db: &mut Database,
+    query: &ProjectQuery,
+) -> anyhow::Result<Vec<AbletonProjectDirectory>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT * FROM project WHERE removed_at IS NULL AND (?1 IS NULL OR modified_at >= ?1) AND (?2 IS NULL OR modified_at < ?2) AND (?3 IS NULL OR root = ?3) AND (?4 IS NULL OR status = ?4) AND (NOT ?5 OR loose) AND (?6 IS NULL OR path = ?6) ORDER BY modified_at DESC")
+                .bind(query.modified_since.as_ref().map(to_epoch_millis))
+                .bind(query.modified_until.as_ref().map(to_epoch_millis))
+                .bind(&query.root)
+                .bind(query.status.as_ref().map(ProjectStatus::as_str))
+                .bind(query.loose)
+                .bind(
+                    query
+                        .path
+                        .as_ref()
+                        .map(|path| path.to_string_lossy().into_owned()),
+                ),
+        )
+        .await?;
+    let mut projects = vec![];
+    for row in rows.iter() {
+        let mut project = AbletonProjectDirectory::from_row(row)?;
+        let versions = select_versions(
+            db,
+            &VersionQuery {
+                project_path: Some(project.path.clone()),
+                include_backups: true,
+                ..Default::default()
+            },
+        )
+        .await?;
+        (project.backups, project.versions) = versions
+            .into_iter()
+            .partition(|version| version.backup_of.is_some());
+        if let Some(id) = project.id {
+            project.todos = select_todos(db, id).await?;
+        }
+        projects.push(project);
+    }
+    Ok(projects)
+}
+
+impl DatabaseModel for AbletonProjectDirectory {
+    /// Storing a project again keeps its notes, and its root while the root is unknown. A project
+    /// that's stored again is no longer removed.
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        let created_at = self
+            .versions
+            .iter()
+            .chain(self.backups.iter())
+            .map(|version| version.created_at)
+            .chain(self.info.as_ref().and_then(|info| info.created_at))
+            .min();
+        let info = self.info.as_ref();
+        sqlx::query("INSERT INTO project (name, path, root, created_at, loose, project_format, preview_path) VALUES (?, ?, ?, ?, ?, ?, ?) ON CONFLICT (path) DO UPDATE SET name = excluded.name, root = coalesce(excluded.root, project.root), created_at = min(coalesce(excluded.created_at, project.created_at), coalesce(project.created_at, excluded.created_at)), loose = excluded.loose, project_format = excluded.project_format, preview_path = excluded.preview_path, removed_at = NULL RETURNING id")
+            .bind(&self.name)
+            .bind(self.path.to_string_lossy().into_owned())
+            .bind(&self.root)
+            .bind(created_at.as_ref().map(to_epoch_millis))
+            .bind(self.is_loose())
+            .bind(info.and_then(|info| info.format.as_ref()))
+            .bind(
+                info.and_then(|info| info.preview.as_ref())
+                    .map(|path| path.to_string_lossy().into_owned()),
+            )
+    }
+}
+
+/// Stores `project`, returning its id.
+pub async fn store_project(
+    db: &mut Database,
+    project: &AbletonProjectDirectory,
+) -> anyhow::Result<i64> {
+    Ok(db.execute_fetchone(project.insert_query()).await?.get(0))
+}
+
+/// Stores the project a version at `project_path` is in, if it isn't stored yet, so that the
+/// version can reference it.
+pub async fn store_project_of(
+    db: &mut Database,
+    project_path: &Path,
+    root: Option<&str>,
+) -> anyhow::Result<i64> {
+    let row = db
+        .execute_fetchone(
+            sqlx::query("INSERT INTO project (name, path, root) VALUES (?, ?, ?) ON CONFLICT (path) DO UPDATE SET root = coalesce(excluded.root, project.root), removed_at = NULL RETURNING id")
+                .bind(AbletonProjectDirectory::name_from_path(project_path))
+                .bind(project_path.to_string_lossy().into_owned())
+                .bind(root),
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+/// The id of the project stored at `path`.
+pub async fn select_project_id(db: &mut Database, path: &Path) -> anyhow::Result<Option<i64>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT id FROM project WHERE path = ?")
+                .bind(path.to_string_lossy().into_owned()),
+        )
+        .await?;
+    Ok(rows.first().map(|row| row.get(0)))
+}
+
+/// The paths of the stored projects named `name`, or whose folder is, ignoring ASCII case.
+pub async fn select_project_paths_named(
+    db: &mut Database,
+    name: &str,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT path FROM project WHERE removed_at IS NULL AND (lower(name) = lower(?1) OR lower(substr(path, -length(?1) - 1)) = lower('/' || ?1)) ORDER BY path")
+                .bind(name),
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(|row| PathBuf::from(row.get::<String, usize>(0)))
+        .collect())
+}
+
+/// Points the project stored as `id` at the folder it was renamed or moved to, keeping its id and
+/// notes. Its versions follow as the scan finds them under the new path.
+pub async fn move_project(
+    db: &mut Database,
+    id: i64,
+    to: &AbletonProjectDirectory,
+) -> anyhow::Result<()> {
+    let from: String = db
+        .execute_fetchone(sqlx::query("SELECT path FROM project WHERE id = ?").bind(id))
+        .await?
+        .get(0);
+    let to_path = to.path.to_string_lossy().into_owned();
+    db.execute_insert(
+        sqlx::query("UPDATE project SET name = ?, path = ?, root = coalesce(?, root), removed_at = NULL WHERE id = ?")
+            .bind(&to.name)
+            .bind(&to_path)
+            .bind(&to.root)
+            .bind(id),
+    )
+    .await?;
+    for statement in [
+        "UPDATE version_lineage SET project_path = ?2 WHERE project_path = ?1",
+        "UPDATE search_entry SET project_path = ?2 WHERE project_path = ?1",
+    ] {
+        db.execute_insert(sqlx::query(statement).bind(&from).bind(&to_path))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Recounts the versions of the project stored as `id`, or of every project, and updates when they
+/// were last modified. Projects none of whose versions are left are marked as removed.
+pub async fn refresh_projects(db: &mut Database, id: Option<i64>) -> anyhow::Result<()> {
+    db.execute_insert(
+        sqlx::query("UPDATE project SET version_count = (SELECT count(*) FROM project_version WHERE project_id = project.id AND backup_of IS NULL AND removed_at IS NULL), modified_at = (SELECT max(modified_at) FROM project_version WHERE project_id = project.id AND removed_at IS NULL) WHERE ?1 IS NULL OR id = ?1")
+            .bind(id),
+    )
+    .await?;
+    db.execute_insert(
+        sqlx::query("UPDATE project SET removed_at = ?2 WHERE (?1 IS NULL OR id = ?1) AND removed_at IS NULL AND NOT EXISTS (SELECT 1 FROM project_version WHERE project_id = project.id AND removed_at IS NULL)")
+            .bind(id)
+            .bind(to_epoch_millis(&SystemTime::now())),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Lists the `.als` files directly inside `dir` along with their created, accessed and modified
+/// times and their size.
+/// Files whose metadata can't be read are reported and left out. Symlinked sets are followed.
+fn read_als_files(dir: &Path) -> Vec<(PathBuf, SystemTime, SystemTime, SystemTime, u64)> {
+    let mut files = vec![];
+    if !dir.is_dir() {
+        return files;
+    }
+    for path in read_directory(dir) {
+        if !is_als_file(&path) {
+            continue;
+        }
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => continue,
+            Err(error) => {
+                status!("skipping {}: {}", path.display(), error);
                 continue;
             }
-            if parts[1] != "als" {
+        };
+        let modified_at = match metadata.modified() {
+            Ok(modified_at) => modified_at,
+            Err(error) => {
+                status!("skipping {}: {}", path.display(), error);
                 continue;
             }
-            let metadata = entry.as_ref().unwrap().metadata();
-            let created_at = metadata.as_ref().unwrap().created().unwrap();
-            let modified_at = metadata.as_ref().unwrap().modified().unwrap();
-            let accessed_at = metadata.as_ref().unwrap().accessed().unwrap();
-            let version = ProjectVersion::new(
-                entry.as_ref().unwrap().path(),
-                created_at,
-                modified_at,
-                accessed_at,
-                None,
-                None,
-            );
-            versions.push(version);
-        }
-        return AbletonProjectDirectory {
-            name,
-            path: path_buf,
-            versions,
         };
+        // Not every filesystem records creation or access times.
+        let created_at = metadata.created().unwrap_or(modified_at);
+        let accessed_at = metadata.accessed().unwrap_or(modified_at);
+        files.push((path, created_at, accessed_at, modified_at, metadata.len()));
     }
+    files
 }
 
 impl Display for AbletonProjectDirectory {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(f, "project: {}\nproject path: {:?}\n", self.name, self.path).unwrap();
-        write!(f, "{:?} versions:\n", self.versions.len()).unwrap();
+        writeln!(f, "project: {}\nproject path: {:?}", self.name, self.path)?;
+        if let Some(root) = &self.root {
+            writeln!(f, "session root: {}", root)?;
+        }
+        match &self.info {
+            Some(info) => {
+                if let Some(created_at) = &info.created_at {
+                    writeln!(f, "created: {}", format_time(created_at))?;
+                }
+                if let Some(preview) = &info.preview {
+                    writeln!(f, "preview: {}", preview.display())?;
+                }
+            }
+            None => writeln!(f, "loose sets: no Ableton Project Info folder")?,
+        }
+        if let Some(status) = &self.status {
+            writeln!(f, "status: {}", status)?;
+        }
+        if let Some(notes) = &self.notes {
+            writeln!(f, "notes: {}", notes)?;
+        }
+        if !self.todos.is_empty() {
+            writeln!(f, "to-do:")?;
+            for todo in &self.todos {
+                writeln!(f, "{}", todo)?;
+            }
+        }
+        writeln!(
+            f,
+            "{:?} versions, {:?} backups:",
+            self.versions.len(),
+            self.backups.len()
+        )?;
         for version in &self.versions {
-            write!(f, "{}", version).unwrap();
+            write!(f, "{}", version)?;
+            for backup in self.backups_of(&version.name) {
+                write!(f, "{}", backup)?;
+            }
         }
-        write!(f, "\n")
+        for backup in self.orphaned_backups() {
+            write!(f, "{}", backup)?;
+        }
+        writeln!(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::version::index_version;
+
+    #[tokio::test]
+    async fn projects_are_found_by_name_or_folder() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db")).await;
+        for path in [
+            "/home/Song Project",
+            "/studio/Song Project",
+            "/studio/Other Project",
+            "/studio/Sketches",
+        ] {
+            store_project_of(&mut db, Path::new(path), None)
+                .await
+                .unwrap();
+        }
+        let paths = |paths: &[&str]| paths.iter().map(PathBuf::from).collect::<Vec<_>>();
+        assert_eq!(
+            select_project_paths_named(&mut db, "song").await.unwrap(),
+            paths(&["/home/Song Project", "/studio/Song Project"])
+        );
+        assert_eq!(
+            select_project_paths_named(&mut db, "OTHER project")
+                .await
+                .unwrap(),
+            paths(&["/studio/Other Project"])
+        );
+        assert_eq!(
+            select_project_paths_named(&mut db, "sketches")
+                .await
+                .unwrap(),
+            paths(&["/studio/Sketches"])
+        );
+        assert!(select_project_paths_named(&mut db, "Project")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    /// The name and version count of the project stored as `id`, and whether it's still there.
+    async fn stored_project(db: &mut Database, id: i64) -> (String, i64, bool) {
+        let row = db
+            .execute_fetchone(
+                sqlx::query(
+                    "SELECT name, version_count, removed_at IS NULL FROM project WHERE id = ?",
+                )
+                .bind(id),
+            )
+            .await
+            .unwrap();
+        (row.get(0), row.get(1), row.get(2))
+    }
+
+    #[tokio::test]
+    async fn moved_projects_keep_their_id_and_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = fs::canonicalize(dir.path()).unwrap().join("Song Project");
+        fs::create_dir(&path).unwrap();
+        for set in ["Song.als", "Song 2.als"] {
+            fs::write(path.join(set), set).unwrap();
+        }
+        let mut db = Database::new(&dir.path().join("test.db")).await;
+        for set in ["Song.als", "Song 2.als"] {
+            index_version(&mut db, &path.join(set)).await.unwrap();
+        }
+        refresh_projects(&mut db, None).await.unwrap();
+        let id = select_project_id(&mut db, &path).await.unwrap().unwrap();
+        assert_eq!(
+            stored_project(&mut db, id).await,
+            (String::from("Song"), 2, true)
+        );
+
+        let renamed = path.with_file_name("Song Final Project");
+        fs::rename(&path, &renamed).unwrap();
+        move_project(
+            &mut db,
+            id,
+            &AbletonProjectDirectory::new(renamed.clone(), None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            select_project_id(&mut db, &renamed).await.unwrap(),
+            Some(id)
+        );
+        assert_eq!(select_project_id(&mut db, &path).await.unwrap(), None);
+        assert_eq!(
+            stored_project(&mut db, id).await,
+            (String::from("Song Final"), 2, true)
+        );
+
+        // A project none of whose versions are left is marked as removed.
+        db.execute_insert(sqlx::query("UPDATE project_version SET removed_at = 1"))
+            .await
+            .unwrap();
+        refresh_projects(&mut db, Some(id)).await.unwrap();
+        assert_eq!(
+            stored_project(&mut db, id).await,
+            (String::from("Song Final"), 0, false)
+        );
     }
 }
@@ -0,0 +1,326 @@
+use crate::cli::output::status;
+use crate::project::project::{read_directory, AbletonProjectDirectory};
+use crate::project::samples::files_below;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The file in each quarantine folder that records where its files came from.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// What a quarantine folder holds, and where to put it back.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantineManifest {
+    /// The project folder the files were moved out of.
+    pub project_path: PathBuf,
+    pub quarantined_at: DateTime<Local>,
+    pub files: Vec<QuarantinedFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantinedFile {
+    /// The file's path relative to the project folder, which it keeps inside the quarantine folder.
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// A folder of files moved out of a project, with its manifest.
+#[derive(Debug, Serialize)]
+pub struct Quarantine {
+    pub path: PathBuf,
+    pub manifest: QuarantineManifest,
+}
+
+impl Quarantine {
+    /// Reads the quarantine folder at `path`.
+    pub fn read(path: &Path) -> anyhow::Result<Quarantine> {
+        let manifest_path = path.join(MANIFEST_FILE);
+        let manifest = fs::read_to_string(&manifest_path).map_err(|error| {
+            anyhow::anyhow!("couldn't read {}: {}", manifest_path.display(), error)
+        })?;
+        Ok(Quarantine {
+            path: path.to_path_buf(),
+            manifest: serde_json::from_str(&manifest)?,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        self.manifest.files.iter().map(|file| file.size).sum()
+    }
+
+    fn write_manifest(&self) -> anyhow::Result<()> {
+        fs::write(
+            self.path.join(MANIFEST_FILE),
+            serde_json::to_string_pretty(&self.manifest)?,
+        )?;
+        Ok(())
+    }
+}
+
+impl Display for Quarantine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{} ({} files from {}, {})",
+            self.path.display(),
+            self.manifest.files.len(),
+            self.manifest.project_path.display(),
+            self.manifest.quarantined_at.format("%Y-%m-%d %H:%M")
+        )
+    }
+}
+
+/// Moves `files` out of `project` into a new folder in `quarantine_directory`, keeping their paths
+/// inside the project, and records them in the folder's manifest. Each file is recorded before it's
+/// moved, so that whatever stops the move partway, the files already moved can be restored.
+pub fn quarantine_files(
+    project: &AbletonProjectDirectory,
+    files: &[PathBuf],
+    quarantine_directory: &Path,
+) -> anyhow::Result<Quarantine> {
+    let project_path = fs::canonicalize(&project.path)?;
+    let quarantined_at = Local::now();
+    let path = quarantine_directory.join(format!(
+        "{} {}",
+        project.name,
+        quarantined_at.format("%Y-%m-%d %H%M%S")
+    ));
+    if path.exists() {
+        anyhow::bail!("{} already exists", path.display());
+    }
+    fs::create_dir_all(&path)?;
+    let mut quarantine = Quarantine {
+        path,
+        manifest: QuarantineManifest {
+            project_path: project_path.clone(),
+            quarantined_at,
+            files: vec![],
+        },
+    };
+    quarantine.write_manifest()?;
+    let mut result = Ok(());
+    for file in files {
+        let Ok(relative) = file.strip_prefix(&project_path) else {
+            result = Err(anyhow::anyhow!(
+                "{} isn't in {}",
+                file.display(),
+                project_path.display()
+            ));
+            break;
+        };
+        let size = match fs::metadata(file) {
+            Ok(metadata) => metadata.len(),
+            Err(error) => {
+                result =
+                    Err(anyhow::Error::from(error)
+                        .context(format!("couldn't read {}", file.display())));
+                break;
+            }
+        };
+        quarantine.manifest.files.push(QuarantinedFile {
+            path: relative.to_path_buf(),
+            size,
+        });
+        if let Err(error) = quarantine.write_manifest() {
+            quarantine.manifest.files.pop();
+            result = Err(error);
+            break;
+        }
+        if let Err(error) = move_file(file, &quarantine.path.join(relative)) {
+            quarantine.manifest.files.pop();
+            result = Err(error.context(format!("couldn't move {}", file.display())));
+            break;
+        }
+    }
+    quarantine.write_manifest()?;
+    result.map(|_| quarantine)
+}
+
+/// Moves the files in the quarantine folder at `path` back into their project. Files whose place
+/// has been taken since are left in quarantine, and files recorded but never moved out, because
+/// quarantining stopped partway, are dropped from the manifest. Once every file is back, the
+/// folder is removed.
+pub fn restore_quarantine(path: &Path) -> anyhow::Result<Quarantine> {
+    let mut quarantine = Quarantine::read(path)?;
+    let mut restored = vec![];
+    let mut kept = vec![];
+    for file in quarantine.manifest.files.drain(..) {
+        let target = quarantine.manifest.project_path.join(&file.path);
+        let source = quarantine.path.join(&file.path);
+        if !source.exists() && target.exists() {
+            continue;
+        }
+        if target.exists() {
+            status!(
+                "keeping {} in quarantine: {} exists",
+                file.path.display(),
+                target.display()
+            );
+            kept.push(file);
+            continue;
+        }
+        match move_file(&source, &target) {
+            Ok(()) => restored.push(file),
+            Err(error) => {
+                status!("couldn't restore {}: {:#}", file.path.display(), error);
+                kept.push(file);
+            }
+        }
+    }
+    quarantine.manifest.files = kept;
+    if quarantine.manifest.files.is_empty() {
+        fs::remove_file(quarantine.path.join(MANIFEST_FILE))?;
+        remove_empty_directories(&quarantine.path)?;
+    } else {
+        quarantine.write_manifest()?;
+    }
+    quarantine.manifest.files = restored;
+    Ok(quarantine)
+}
+
+/// The quarantine folders in `quarantine_directory`, oldest first.
+pub fn list_quarantines(quarantine_directory: &Path) -> Vec<Quarantine> {
+    if !quarantine_directory.is_dir() {
+        return vec![];
+    }
+    let mut quarantines: Vec<Quarantine> = read_directory(quarantine_directory)
+        .into_iter()
+        .filter(|path| path.join(MANIFEST_FILE).is_file())
+        .filter_map(|path| match Quarantine::read(&path) {
+            Ok(quarantine) => Some(quarantine),
+            Err(error) => {
+                status!("skipping {}: {:#}", path.display(), error);
+                None
+            }
+        })
+        .collect();
+    quarantines.sort_by_key(|quarantine| quarantine.manifest.quarantined_at);
+    quarantines
+}
+
+/// Moves a file, creating the directory it goes in. Files are copied and then removed when they
+/// move to another drive, which can't be done by renaming.
+fn move_file(from: &Path, to: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    if fs::metadata(to)?.len() != fs::metadata(from)?.len() {
+        fs::remove_file(to)?;
+        anyhow::bail!("the copy at {} is incomplete", to.display());
+    }
+    fs::remove_file(from)?;
+    Ok(())
+}
+
+/// Removes `dir` and the directories below it, as long as they hold no files.
+fn remove_empty_directories(dir: &Path) -> anyhow::Result<()> {
+    if !files_below(dir).is_empty() {
+        anyhow::bail!("{} still holds files", dir.display());
+    }
+    fs::remove_dir_all(dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project_with(files: &[&str]) -> (tempfile::TempDir, AbletonProjectDirectory) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Song Project");
+        for file in files {
+            let file = path.join(file);
+            fs::create_dir_all(file.parent().unwrap()).unwrap();
+            fs::write(&file, file.to_string_lossy().as_bytes()).unwrap();
+        }
+        let project = AbletonProjectDirectory::new(fs::canonicalize(&path).unwrap(), None);
+        (dir, project)
+    }
+
+    #[test]
+    fn quarantined_files_are_restored() {
+        let (dir, project) = project_with(&["Samples/Recorded/a.wav", "Samples/b.aif", "Song.als"]);
+        let unused = vec![
+            project.path.join("Samples/Recorded/a.wav"),
+            project.path.join("Samples/b.aif"),
+        ];
+        let quarantine_directory = dir.path().join("quarantine");
+        let quarantine = quarantine_files(&project, &unused, &quarantine_directory).unwrap();
+        assert!(unused.iter().all(|file| !file.exists()));
+        assert!(project.path.join("Song.als").exists());
+        assert_eq!(list_quarantines(&quarantine_directory).len(), 1);
+        assert_eq!(
+            Quarantine::read(&quarantine.path)
+                .unwrap()
+                .manifest
+                .files
+                .len(),
+            2
+        );
+
+        let restored = restore_quarantine(&quarantine.path).unwrap();
+        assert_eq!(restored.manifest.files.len(), 2);
+        for file in &unused {
+            assert_eq!(fs::read(file).unwrap(), file.to_string_lossy().as_bytes());
+        }
+        assert!(!quarantine.path.exists());
+        assert!(list_quarantines(&quarantine_directory).is_empty());
+    }
+
+    #[test]
+    fn files_moved_before_a_failure_stay_restorable() {
+        let (dir, project) = project_with(&["Samples/a.wav", "Samples/c.wav"]);
+        let files = vec![
+            project.path.join("Samples/a.wav"),
+            project.path.join("Samples/missing.wav"),
+            project.path.join("Samples/c.wav"),
+        ];
+        let quarantine_directory = dir.path().join("quarantine");
+        assert!(quarantine_files(&project, &files, &quarantine_directory).is_err());
+        assert!(!files[0].exists());
+        assert!(files[2].exists());
+
+        let quarantines = list_quarantines(&quarantine_directory);
+        assert_eq!(quarantines.len(), 1);
+        let moved: Vec<&Path> = quarantines[0]
+            .manifest
+            .files
+            .iter()
+            .map(|file| file.path.as_path())
+            .collect();
+        assert_eq!(moved, [Path::new("Samples/a.wav")]);
+        restore_quarantine(&quarantines[0].path).unwrap();
+        assert!(files[0].exists());
+    }
+
+    #[test]
+    fn files_recorded_but_never_moved_are_dropped_on_restore() {
+        let (dir, project) = project_with(&["Samples/a.wav", "Samples/b.wav"]);
+        let quarantine_directory = dir.path().join("quarantine");
+        let quarantine = quarantine_files(
+            &project,
+            &[project.path.join("Samples/a.wav")],
+            &quarantine_directory,
+        )
+        .unwrap();
+        // As if quarantining had stopped between recording b.wav and moving it.
+        let mut recorded = Quarantine::read(&quarantine.path).unwrap();
+        recorded.manifest.files.push(QuarantinedFile {
+            path: PathBuf::from("Samples/b.wav"),
+            size: 1,
+        });
+        recorded.write_manifest().unwrap();
+
+        let restored = restore_quarantine(&quarantine.path).unwrap();
+        assert_eq!(restored.manifest.files.len(), 1);
+        assert!(project.path.join("Samples/a.wav").exists());
+        assert!(project.path.join("Samples/b.wav").exists());
+        assert!(!quarantine.path.exists());
+    }
+}
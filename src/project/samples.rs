@@ -0,0 +1,188 @@
+use crate::parser::als::AbletonXmlParser;
+use crate::project::project::{read_directory, AbletonProjectDirectory, SAMPLES_DIRECTORY};
+use rayon::prelude::*;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Audio formats Live can use as samples.
+pub const AUDIO_EXTENSIONS: [&str; 7] = ["wav", "aif", "aiff", "flac", "ogg", "mp3", "m4a"];
+/// The analysis file Live writes next to a sample, e.g. `Kick.wav.asd`.
+pub const ANALYSIS_EXTENSION: &str = "asd";
+
+pub fn is_audio_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(OsStr::to_str)
+        .is_some_and(|extension| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .any(|audio| extension.eq_ignore_ascii_case(audio))
+        })
+}
+
+/// The samples a project's sets and backups refer to.
+#[derive(Debug, Default)]
+pub struct SampleReferences {
+    /// The files the references resolve to, canonicalised.
+    pub found: HashSet<PathBuf>,
+    /// References that don't resolve to a file, as Live stored them.
+    pub missing: HashSet<String>,
+    /// Sets that couldn't be read, whose samples aren't known.
+    pub unreadable: Vec<PathBuf>,
+}
+
+impl SampleReferences {
+    /// Reads the sample references of every version and backup in `project`, in parallel.
+    pub fn read(project: &AbletonProjectDirectory) -> SampleReferences {
+        let sets: Vec<&Path> = project
+            .versions
+            .iter()
+            .chain(&project.backups)
+            .map(|version| version.path.as_path())
+            .collect();
+        sets.into_par_iter()
+            .map(|set| {
+                let mut references = SampleReferences::default();
+                match AbletonXmlParser::new().read_contents_of(set) {
+                    Ok(contents) => {
+                        for sample in &contents.samples {
+                            match sample.resolve(&project.path) {
+                                Some(path) => {
+                                    references.found.insert(path);
+                                }
+                                None => {
+                                    if let Some(stored) =
+                                        sample.path.as_ref().or(sample.relative_path.as_ref())
+                                    {
+                                        references.missing.insert(stored.clone());
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    Err(_) => references.unreadable.push(set.to_path_buf()),
+                }
+                references
+            })
+            .reduce(SampleReferences::default, |mut all, references| {
+                all.found.extend(references.found);
+                all.missing.extend(references.missing);
+                all.unreadable.extend(references.unreadable);
+                all
+            })
+    }
+
+    /// The referenced files that are outside the project's folder at `project_path`.
+    pub fn external<'a>(&'a self, project_path: &'a Path) -> impl Iterator<Item = &'a PathBuf> {
+        self.found
+            .iter()
+            .filter(move |path| !path.starts_with(project_path))
+    }
+}
+
+/// The audio files under a project's Samples folder that none of its sets or backups refer to,
+/// each followed by Live's analysis file for it, if there is one. Fails if a set couldn't be read,
+/// since any of the files might be its.
+pub fn unused_samples(
+    project: &AbletonProjectDirectory,
+    references: &SampleReferences,
+) -> anyhow::Result<Vec<PathBuf>> {
+    if let Some(set) = references.unreadable.first() {
+        anyhow::bail!(
+            "couldn't read {}, so which samples it uses isn't known",
+            set.display()
+        );
+    }
+    let samples = project.path.join(SAMPLES_DIRECTORY);
+    if !samples.is_dir() {
+        return Ok(vec![]);
+    }
+    let samples = fs::canonicalize(&samples)?;
+    let mut unused = vec![];
+    for path in files_below(&samples) {
+        if !is_audio_file(&path) || references.found.contains(&path) {
+            continue;
+        }
+        let mut analysis = path.clone().into_os_string();
+        analysis.push(".");
+        analysis.push(ANALYSIS_EXTENSION);
+        let analysis = PathBuf::from(analysis);
+        unused.push(path);
+        if analysis.is_file() {
+            unused.push(analysis);
+        }
+    }
+    unused.sort();
+    Ok(unused)
+}
+
+/// The files below `dir`, not following symlinked directories.
+pub fn files_below(dir: &Path) -> Vec<PathBuf> {
+    let mut files = vec![];
+    for path in read_directory(dir) {
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => files.extend(files_below(&path)),
+            Ok(metadata) if metadata.is_file() => files.push(path),
+            _ => {}
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs::File;
+    use std::io::Write;
+
+    /// A set whose only contents are references to `samples`, given as (absolute, relative) paths.
+    pub fn write_set(path: &Path, samples: &[(&Path, &str)]) {
+        let references: String = samples
+            .iter()
+            .map(|(absolute, relative)| {
+                format!(
+                    r#"<SampleRef><FileRef><RelativePath Value="{}" /><Path Value="{}" /></FileRef></SampleRef>"#,
+                    relative,
+                    absolute.display()
+                )
+            })
+            .collect();
+        let xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?><Ableton Creator="Ableton Live 11.3"><LiveSet><Tracks><AudioTrack Id="8"><Name><EffectiveName Value="Vox" /></Name><Clips>{}</Clips></AudioTrack></Tracks></LiveSet></Ableton>"#,
+            references
+        );
+        let mut encoder = GzEncoder::new(File::create(path).unwrap(), Compression::default());
+        encoder.write_all(xml.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+    }
+
+    #[test]
+    fn samples_only_a_backup_uses_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Song Project");
+        for sample in ["take.wav", "take.wav.asd", "unused.wav"] {
+            let sample = path.join("Samples/Recorded").join(sample);
+            fs::create_dir_all(sample.parent().unwrap()).unwrap();
+            fs::write(&sample, "audio").unwrap();
+        }
+        write_set(&path.join("Song.als"), &[]);
+        fs::create_dir_all(path.join("Backup")).unwrap();
+        // Live stores the relative path from the project's folder, and the absolute path is stale.
+        write_set(
+            &path.join("Backup/Song [2023-06-01 153012].als"),
+            &[(Path::new("/nowhere/take.wav"), "Samples/Recorded/take.wav")],
+        );
+        let project = AbletonProjectDirectory::new(fs::canonicalize(&path).unwrap(), None);
+        assert_eq!(project.backups.len(), 1);
+
+        let references = SampleReferences::read(&project);
+        assert!(references.missing.is_empty(), "{:?}", references.missing);
+        assert_eq!(
+            unused_samples(&project, &references).unwrap(),
+            vec![project.path.join("Samples/Recorded/unused.wav")]
+        );
+    }
+}
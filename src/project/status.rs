@@ -0,0 +1,83 @@
+use crate::state::database::Database;
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Where a project is at, from first idea to release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProjectStatus {
+    Idea,
+    InProgress,
+    Mixing,
+    Mastered,
+    Released,
+    Abandoned,
+}
+
+impl ProjectStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProjectStatus::Idea => "idea",
+            ProjectStatus::InProgress => "in-progress",
+            ProjectStatus::Mixing => "mixing",
+            ProjectStatus::Mastered => "mastered",
+            ProjectStatus::Released => "released",
+            ProjectStatus::Abandoned => "abandoned",
+        }
+    }
+}
+
+impl FromStr for ProjectStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(status: &str) -> Result<Self, Self::Err> {
+        [
+            ProjectStatus::Idea,
+            ProjectStatus::InProgress,
+            ProjectStatus::Mixing,
+            ProjectStatus::Mastered,
+            ProjectStatus::Released,
+            ProjectStatus::Abandoned,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == status)
+        .ok_or_else(|| anyhow::anyhow!("unknown project status {}", status))
+    }
+}
+
+impl Display for ProjectStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Sets the status of the project stored as `project_id`, or clears it.
+pub async fn set_status(
+    db: &mut Database,
+    project_id: i64,
+    status: Option<ProjectStatus>,
+) -> anyhow::Result<()> {
+    db.execute_insert(
+        sqlx::query("UPDATE project SET status = ? WHERE id = ?")
+            .bind(status.as_ref().map(ProjectStatus::as_str))
+            .bind(project_id),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Replaces the notes of the project stored as `project_id`, or clears them.
+pub async fn set_notes(
+    db: &mut Database,
+    project_id: i64,
+    notes: Option<&str>,
+) -> anyhow::Result<()> {
+    db.execute_insert(
+        sqlx::query("UPDATE project SET notes = ? WHERE id = ?")
+            .bind(notes)
+            .bind(project_id),
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use crate::version::version::{
+    from_epoch_millis, serialize_optional_time, serialize_time, to_epoch_millis,
+};
+use serde::Serialize;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::fmt::{self, Display};
+use std::time::SystemTime;
+
+/// An item on a project's to-do checklist. Items are numbered from 1 in the order they were added
+/// to their project.
+#[derive(Debug, Serialize)]
+pub struct ProjectTodo {
+    pub number: usize,
+    pub project_id: i64,
+    pub text: String,
+    #[serde(serialize_with = "serialize_time")]
+    pub created_at: SystemTime,
+    /// When the item was checked off.
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub done_at: Option<SystemTime>,
+    #[serde(skip)]
+    id: i64,
+}
+
+impl ProjectTodo {
+    pub fn new(project_id: i64, text: &str) -> ProjectTodo {
+        ProjectTodo {
+            number: 0,
+            project_id,
+            text: String::from(text.trim()),
+            created_at: SystemTime::now(),
+            done_at: None,
+            id: 0,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done_at.is_some()
+    }
+}
+
+impl DatabaseModel for ProjectTodo {
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        sqlx::query("INSERT INTO project_todo (project_id, text, created_at) VALUES (?, ?, ?)")
+            .bind(self.project_id)
+            .bind(&self.text)
+            .bind(to_epoch_millis(&self.created_at))
+    }
+}
+
+/// Rows are read without their number, which `select_todos` fills in.
+impl<'r> FromRow<'r, SqliteRow> for ProjectTodo {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(ProjectTodo {
+            number: 0,
+            project_id: row.try_get("project_id")?,
+            text: row.try_get("text")?,
+            created_at: from_epoch_millis(row.try_get("created_at")?),
+            done_at: row
+                .try_get::<Option<i64>, &str>("done_at")?
+                .map(from_epoch_millis),
+            id: row.try_get("id")?,
+        })
+    }
+}
+
+impl Display for ProjectTodo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{:>3}. [{}] {}",
+            self.number,
+            if self.is_done() { "x" } else { " " },
+            self.text
+        )
+    }
+}
+
+/// The to-do checklist of the project stored as `project_id`, in the order the items were added.
+pub async fn select_todos(db: &mut Database, project_id: i64) -> anyhow::Result<Vec<ProjectTodo>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT * FROM project_todo WHERE project_id = ? ORDER BY id")
+                .bind(project_id),
+        )
+        .await?;
+    rows.iter()
+        .enumerate()
+        .map(|(index, row)| {
+            let mut todo = ProjectTodo::from_row(row)?;
+            todo.number = index + 1;
+            Ok(todo)
+        })
+        .collect()
+}
+
+/// Adds an item to the end of a project's checklist.
+pub async fn add_todo(db: &mut Database, project_id: i64, text: &str) -> anyhow::Result<()> {
+    let todo = ProjectTodo::new(project_id, text);
+    if todo.text.is_empty() {
+        anyhow::bail!("a to-do needs some text");
+    }
+    db.execute_insert(todo.insert_query()).await?;
+    Ok(())
+}
+
+/// Checks off the item numbered `number` on a project's checklist, or unchecks it.
+pub async fn set_todo_done(
+    db: &mut Database,
+    project_id: i64,
+    number: usize,
+    done: bool,
+) -> anyhow::Result<ProjectTodo> {
+    let mut todo = select_todo(db, project_id, number).await?;
+    todo.done_at = done.then(SystemTime::now);
+    db.execute_insert(
+        sqlx::query("UPDATE project_todo SET done_at = ? WHERE id = ?")
+            .bind(todo.done_at.as_ref().map(to_epoch_millis))
+            .bind(todo.id),
+    )
+    .await?;
+    Ok(todo)
+}
+
+/// Removes the item numbered `number` from a project's checklist. The items after it move up.
+pub async fn remove_todo(
+    db: &mut Database,
+    project_id: i64,
+    number: usize,
+) -> anyhow::Result<ProjectTodo> {
+    let todo = select_todo(db, project_id, number).await?;
+    db.execute_insert(sqlx::query("DELETE FROM project_todo WHERE id = ?").bind(todo.id))
+        .await?;
+    Ok(todo)
+}
+
+async fn select_todo(
+    db: &mut Database,
+    project_id: i64,
+    number: usize,
+) -> anyhow::Result<ProjectTodo> {
+    select_todos(db, project_id)
+        .await?
+        .into_iter()
+        .find(|todo| todo.number == number)
+        .ok_or_else(|| anyhow::anyhow!("the project has no to-do numbered {}", number))
+}
@@ -0,0 +1,456 @@
+use crate::library::library::LibraryFile;
+use crate::parser::structs::file_kind::FileKind;
+use crate::parser::structs::live_set::{LiveSetContents, PLUGIN_DEVICE_ELEMENTS};
+use crate::project::project::AbletonProjectDirectory;
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use crate::version::version::ProjectVersion;
+use serde::Serialize;
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::collections::HashSet;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Candidates fetched from the full-text index before they're scored.
+const CANDIDATE_LIMIT: i64 = 500;
+/// The fraction of the query's trigrams a hit has to contain to count as a fuzzy match.
+const MINIMUM_SCORE: f64 = 0.5;
+
+/// What a piece of searchable text is the name of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    Project,
+    Version,
+    Description,
+    Tag,
+    Track,
+    Clip,
+    Locator,
+    Device,
+    Plugin,
+    Rack,
+    Preset,
+    Groove,
+}
+
+/// Entries read from a set's contents, replaced whenever the set is scanned.
+const CONTENT_KINDS: [EntryKind; 5] = [
+    EntryKind::Track,
+    EntryKind::Clip,
+    EntryKind::Locator,
+    EntryKind::Device,
+    EntryKind::Plugin,
+];
+
+impl EntryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EntryKind::Project => "project",
+            EntryKind::Version => "version",
+            EntryKind::Description => "description",
+            EntryKind::Tag => "tag",
+            EntryKind::Track => "track",
+            EntryKind::Clip => "clip",
+            EntryKind::Locator => "locator",
+            EntryKind::Device => "device",
+            EntryKind::Plugin => "plugin",
+            EntryKind::Rack => "rack",
+            EntryKind::Preset => "preset",
+            EntryKind::Groove => "groove",
+        }
+    }
+
+    /// The kind of entry a library file's name is indexed as. Clip files share the kind of the
+    /// clips in sets.
+    fn of_library_file(kind: FileKind) -> EntryKind {
+        match kind {
+            FileKind::Rack => EntryKind::Rack,
+            FileKind::Preset => EntryKind::Preset,
+            FileKind::Groove => EntryKind::Groove,
+            FileKind::Clip | FileKind::Set => EntryKind::Clip,
+        }
+    }
+}
+
+impl FromStr for EntryKind {
+    type Err = anyhow::Error;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        [
+            EntryKind::Project,
+            EntryKind::Version,
+            EntryKind::Description,
+            EntryKind::Tag,
+            EntryKind::Track,
+            EntryKind::Clip,
+            EntryKind::Locator,
+            EntryKind::Device,
+            EntryKind::Plugin,
+            EntryKind::Rack,
+            EntryKind::Preset,
+            EntryKind::Groove,
+        ]
+        .into_iter()
+        .find(|candidate| candidate.as_str() == kind)
+        .ok_or_else(|| anyhow::anyhow!("unknown search entry kind {}", kind))
+    }
+}
+
+impl Display for EntryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A row of `search_entry`: a name and where it was found.
+#[derive(Debug)]
+pub struct SearchEntry {
+    pub kind: EntryKind,
+    pub text: String,
+    pub project_path: Option<PathBuf>,
+    pub version_path: Option<PathBuf>,
+    pub track: Option<String>,
+}
+
+impl DatabaseModel for SearchEntry {
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        sqlx::query("INSERT INTO search_entry (kind, text, project_path, version_path, track) VALUES (?, ?, ?, ?, ?)")
+            .bind(self.kind.as_str())
+            .bind(&self.text)
+            .bind(
+                self.project_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().into_owned()),
+            )
+            .bind(
+                self.version_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().into_owned()),
+            )
+            .bind(&self.track)
+    }
+}
+
+/// Makes the project's name searchable.
+pub async fn index_project(
+    db: &mut Database,
+    project: &AbletonProjectDirectory,
+) -> anyhow::Result<()> {
+    let project_path = project.path.to_string_lossy().into_owned();
+    db.execute_insert(
+        sqlx::query("DELETE FROM search_entry WHERE kind = ? AND project_path = ?")
+            .bind(EntryKind::Project.as_str())
+            .bind(&project_path),
+    )
+    .await?;
+    let entry = SearchEntry {
+        kind: EntryKind::Project,
+        text: project.name.clone(),
+        project_path: Some(project.path.clone()),
+        version_path: None,
+        track: None,
+    };
+    db.execute_insert(entry.insert_query()).await?;
+    Ok(())
+}
+
+/// Replaces the searchable track, clip, locator and device names of a version with those read
+/// from its set.
+pub async fn index_contents(
+    db: &mut Database,
+    version: &ProjectVersion,
+    contents: &LiveSetContents,
+) -> anyhow::Result<()> {
+    let version_path = version.path.to_string_lossy().into_owned();
+    for kind in CONTENT_KINDS {
+        db.execute_insert(
+            sqlx::query("DELETE FROM search_entry WHERE kind = ? AND version_path = ?")
+                .bind(kind.as_str())
+                .bind(&version_path),
+        )
+        .await?;
+    }
+    let entry = |kind: EntryKind, text: &str, track: Option<String>| SearchEntry {
+        kind,
+        text: String::from(text),
+        project_path: version.project_path.clone(),
+        version_path: Some(version.path.clone()),
+        track,
+    };
+    let mut entries = vec![];
+    for track in &contents.tracks {
+        entries.push(entry(
+            EntryKind::Track,
+            &track.name,
+            Some(track.name.clone()),
+        ));
+    }
+    for clip in &contents.clips {
+        let track = contents.track_name(&clip.track_id);
+        entries.push(entry(EntryKind::Clip, &clip.name, Some(track)));
+    }
+    for locator in &contents.locators {
+        entries.push(entry(EntryKind::Locator, &locator.name, None));
+    }
+    for device in &contents.devices {
+        let kind = if PLUGIN_DEVICE_ELEMENTS.contains(&device.kind.as_str()) {
+            EntryKind::Plugin
+        } else {
+            EntryKind::Device
+        };
+        let track = contents.track_name(&device.track_id);
+        entries.push(entry(kind, &device.name, Some(track)));
+    }
+    for entry in entries.iter().filter(|entry| !entry.text.is_empty()) {
+        db.execute_insert(entry.insert_query()).await?;
+    }
+    Ok(())
+}
+
+/// Replaces the searchable names of a library file: its own and those of the devices and plugins
+/// in it.
+pub async fn index_library_file(
+    db: &mut Database,
+    file: &LibraryFile,
+    contents: &LiveSetContents,
+) -> anyhow::Result<()> {
+    forget_library_file(db, &file.path).await?;
+    let entry = |kind: EntryKind, text: &str| SearchEntry {
+        kind,
+        text: String::from(text),
+        project_path: None,
+        version_path: Some(file.path.clone()),
+        track: None,
+    };
+    let mut entries = vec![entry(EntryKind::of_library_file(file.kind), &file.name)];
+    for device in &contents.devices {
+        let kind = if PLUGIN_DEVICE_ELEMENTS.contains(&device.kind.as_str()) {
+            EntryKind::Plugin
+        } else {
+            EntryKind::Device
+        };
+        entries.push(entry(kind, &device.name));
+    }
+    for entry in entries.iter().filter(|entry| !entry.text.is_empty()) {
+        db.execute_insert(entry.insert_query()).await?;
+    }
+    Ok(())
+}
+
+/// Takes a library file that's gone out of search.
+pub async fn forget_library_file(db: &mut Database, path: &Path) -> anyhow::Result<()> {
+    db.execute_insert(
+        sqlx::query("DELETE FROM search_entry WHERE version_path = ?")
+            .bind(path.to_string_lossy().into_owned()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// A fuzzy search of the index. Hits containing the query rank first, followed by hits sharing
+/// most of its trigrams, which catches typos like `wierd bass`.
+#[derive(Debug)]
+pub struct SearchQuery {
+    pub text: String,
+    pub root: Option<String>,
+    pub kind: Option<EntryKind>,
+    pub limit: usize,
+}
+
+/// Something in the index matching a search, with the project, version and track it came from.
+#[derive(Debug, Serialize)]
+pub struct SearchHit {
+    pub kind: EntryKind,
+    pub text: String,
+    pub project: Option<String>,
+    pub project_path: Option<PathBuf>,
+    pub version: Option<String>,
+    pub version_path: Option<PathBuf>,
+    pub track: Option<String>,
+    pub score: f64,
+    #[serde(skip)]
+    rank: f64,
+}
+
+impl<'r> FromRow<'r, SqliteRow> for SearchHit {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        let kind: String = row.try_get("kind")?;
+        let project_path = row
+            .try_get::<Option<String>, &str>("project_path")?
+            .map(PathBuf::from);
+        Ok(SearchHit {
+            kind: kind
+                .parse()
+                .map_err(|error: anyhow::Error| sqlx::Error::Decode(error.into()))?,
+            text: row.try_get("text")?,
+            project: project_path
+                .as_deref()
+                .map(AbletonProjectDirectory::name_from_path),
+            project_path,
+            version: row.try_get("version_name")?,
+            version_path: row
+                .try_get::<Option<String>, &str>("version_path")?
+                .map(PathBuf::from),
+            track: row.try_get("track")?,
+            score: 0.0,
+            rank: row.try_get("rank")?,
+        })
+    }
+}
+
+impl Display for SearchHit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(f, "{} {:?}", self.kind, self.text)?;
+        if let Some(track) = self
+            .track
+            .as_ref()
+            .filter(|_| self.kind != EntryKind::Track)
+        {
+            write!(f, " on {}", track)?;
+        }
+        match (&self.version, &self.project) {
+            (Some(version), Some(project)) if self.kind != EntryKind::Version => {
+                write!(f, " in {} ({})", version, project)?
+            }
+            (_, Some(project)) if self.kind != EntryKind::Project => write!(f, " in {}", project)?,
+            _ => {}
+        }
+        if let Some(path) = self.version_path.as_ref().or(self.project_path.as_ref()) {
+            write!(f, "\n    {}", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+pub async fn search(db: &mut Database, query: &SearchQuery) -> anyhow::Result<Vec<SearchHit>> {
+    let text = query.text.trim().to_lowercase();
+    let query_trigrams = trigrams(&text);
+    // The trigram tokenizer can't match fewer than three characters, so short queries are
+    // matched as substrings instead.
+    let (condition, pattern) = if query_trigrams.is_empty() {
+        ("instr(lower(search_entry.text), ?1) > 0", text.clone())
+    } else {
+        (
+            "search_entry.id IN (SELECT rowid FROM search_entry_text WHERE search_entry_text MATCH ?1)",
+            query_trigrams
+                .iter()
+                .map(|trigram| format!("\"{}\"", trigram.replace('"', "\"\"")))
+                .collect::<Vec<String>>()
+                .join(" OR "),
+        )
+    };
+    // Only the best candidates are scored: those containing the query first, then the closest
+    // fuzzy matches by the index's rank.
+    let sql = format!(
+        "SELECT search_entry.*, project_version.name AS version_name, coalesce((SELECT rank FROM search_entry_text WHERE search_entry_text MATCH ?1 AND rowid = search_entry.id), 0.0) AS rank FROM search_entry LEFT JOIN project_version ON project_version.path = search_entry.version_path WHERE {} AND project_version.removed_at IS NULL AND (?2 IS NULL OR search_entry.project_path IN (SELECT project_path FROM project_version WHERE root = ?2) OR search_entry.version_path IN (SELECT path FROM library_file WHERE root = ?2)) AND (?3 IS NULL OR search_entry.kind = ?3) ORDER BY instr(lower(search_entry.text), ?5) > 0 DESC, rank LIMIT ?4",
+        condition
+    );
+    let rows = db
+        .execute_fetchall(
+            sqlx::query(&sql)
+                .bind(&pattern)
+                .bind(&query.root)
+                .bind(query.kind.as_ref().map(EntryKind::as_str))
+                .bind(CANDIDATE_LIMIT)
+                .bind(&text),
+        )
+        .await?;
+    let mut hits = vec![];
+    for row in rows.iter() {
+        let mut hit = SearchHit::from_row(row)?;
+        hit.score = score(&text, &query_trigrams, &hit.text);
+        if hit.score >= MINIMUM_SCORE {
+            hits.push(hit);
+        }
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.rank.total_cmp(&b.rank)));
+    hits.truncate(query.limit);
+    Ok(hits)
+}
+
+/// 1 for text containing the query, otherwise the fraction of the query's trigrams it contains.
+fn score(query: &str, query_trigrams: &HashSet<String>, text: &str) -> f64 {
+    let text = text.to_lowercase();
+    if text.contains(query) {
+        // Prefer exact names over longer ones that merely contain the query.
+        return 1.0 + query.len() as f64 / text.len().max(1) as f64;
+    }
+    if query_trigrams.is_empty() {
+        return 0.0;
+    }
+    let text_trigrams = trigrams(&text);
+    query_trigrams.intersection(&text_trigrams).count() as f64 / query_trigrams.len() as f64
+}
+
+fn trigrams(text: &str) -> HashSet<String> {
+    let characters: Vec<char> = text.chars().collect();
+    characters
+        .windows(3)
+        .map(|window| window.iter().collect())
+        .collect()
+}
+
+/// Forgets the names of projects whose versions have all been removed.
+pub async fn prune_projects(db: &mut Database) -> anyhow::Result<()> {
+    db.execute_insert(sqlx::query(
+        "DELETE FROM search_entry WHERE kind = ? AND project_path NOT IN (SELECT project_path FROM project_version WHERE project_path IS NOT NULL AND removed_at IS NULL)",
+    ).bind(EntryKind::Project.as_str()))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn index(db: &mut Database, kind: EntryKind, text: &str) {
+        let entry = SearchEntry {
+            kind,
+            text: String::from(text),
+            project_path: None,
+            version_path: None,
+            track: None,
+        };
+        db.execute_insert(entry.insert_query()).await.unwrap();
+    }
+
+    fn query(text: &str) -> SearchQuery {
+        SearchQuery {
+            text: String::from(text),
+            root: None,
+            kind: None,
+            limit: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn exact_matches_rank_first_past_the_candidate_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db")).await;
+        db.begin().await.unwrap();
+        for number in 0..CANDIDATE_LIMIT * 2 {
+            index(&mut db, EntryKind::Clip, &format!("bass loop {}", number)).await;
+        }
+        index(&mut db, EntryKind::Track, "Weird Bass").await;
+        index(&mut db, EntryKind::Track, "Weird Bass Resampled").await;
+        db.commit().await.unwrap();
+
+        let hits = search(&mut db, &query("weird bass")).await.unwrap();
+        let texts: Vec<&str> = hits.iter().map(|hit| hit.text.as_str()).collect();
+        assert_eq!(texts, ["Weird Bass", "Weird Bass Resampled"]);
+    }
+
+    #[tokio::test]
+    async fn misspelt_queries_find_close_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db")).await;
+        index(&mut db, EntryKind::Track, "Weird Bass").await;
+        index(&mut db, EntryKind::Track, "Drums").await;
+
+        let hits = search(&mut db, &query("wierd bass")).await.unwrap();
+        let texts: Vec<&str> = hits.iter().map(|hit| hit.text.as_str()).collect();
+        assert_eq!(texts, ["Weird Bass"]);
+        assert!(hits[0].score < 1.0);
+    }
+}
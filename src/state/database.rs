@@ -1,77 +1,218 @@
 #![allow(dead_code)]
-use sqlx::{migrate::MigrateDatabase, query::Query, Pool, Sqlite, SqlitePool};
+use crate::cli::output::{status, verbose};
+use sqlx::{
+    migrate::{MigrateDatabase, Migrator},
+    query::Query,
+    sqlite::{SqliteArguments, SqliteQueryResult, SqliteRow},
+    Pool, Row, Sqlite, SqlitePool, Transaction,
+};
+use std::fs;
+use std::path::Path;
 
-const DB_URL: &str = "sqlite://sqlite.db";
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Columns added to `project_version` before migrations existed, which older databases may lack.
+const LEGACY_VERSION_COLUMNS: [(&str, &str); 2] =
+    [("backup_of", "varchar(150)"), ("backed_up_at", "timestamp")];
+
+pub type SqliteQuery<'q> = Query<'q, Sqlite, SqliteArguments<'q>>;
 
 pub struct Database {
     pub db_url: String,
     pub connection: Option<Pool<Sqlite>>,
+    /// While a transaction is open, every query runs inside it.
+    transaction: Option<Transaction<'static, Sqlite>>,
 }
 
 impl Database {
-    pub async fn new() -> Database {
+    pub async fn new(path: &Path) -> Database {
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).expect("database directory to be creatable");
+        }
         let mut db = Database {
-            db_url: String::from(DB_URL),
+            db_url: format!("sqlite://{}", path.display()),
             connection: None,
+            transaction: None,
         };
         db.create_database().await;
         db.connection = db.connect().await;
+        db.migrate().await.expect("database migrations to apply");
         db
     }
 
     pub async fn connect(&mut self) -> Option<Pool<Sqlite>> {
         let connection = SqlitePool::connect(self.db_url.as_str()).await.unwrap();
-        println!("Connected to db");
+        verbose!("connected to {}", self.db_url);
         Some(connection)
     }
 
     pub async fn create_database(&self) {
-        if !Sqlite::database_exists(DB_URL).await.unwrap_or(false) {
-            println!("Creating database {}", DB_URL);
-            match Sqlite::create_database(DB_URL).await {
-                Ok(_) => println!("Success"),
-                Err(error) => panic!("error: {}", error),
+        if !Sqlite::database_exists(&self.db_url).await.unwrap_or(false) {
+            verbose!("creating database {}", self.db_url);
+            if let Err(error) = Sqlite::create_database(&self.db_url).await {
+                panic!("error: {}", error);
             }
-        } else {
-            println!("Database exists at {}", DB_URL);
         }
     }
 
+    /// Applies any migrations in `migrations/` that haven't run against this database yet.
+    pub async fn migrate(&mut self) -> anyhow::Result<()> {
+        self.adopt_legacy_schema().await?;
+        MIGRATOR.run(self.pool()).await?;
+        Ok(())
+    }
+
+    /// Databases created before migrations existed have a `project_version` table in whatever
+    /// layout the code had at the time. Add the columns the baseline migration expects so it can
+    /// be recorded as applied without losing rows.
+    async fn adopt_legacy_schema(&mut self) -> anyhow::Result<()> {
+        let migrated = sqlx::query(
+            "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+        )
+        .fetch_one(self.pool())
+        .await?
+        .get::<i64, usize>(0)
+            > 0;
+        if migrated {
+            return Ok(());
+        }
+        let columns: Vec<String> =
+            sqlx::query("SELECT name FROM pragma_table_info('project_version')")
+                .fetch_all(self.pool())
+                .await?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+        if columns.is_empty() {
+            return Ok(());
+        }
+        for (column, column_type) in LEGACY_VERSION_COLUMNS {
+            if !columns.iter().any(|existing| existing == column) {
+                status!("adding column {} to legacy project_version table", column);
+                sqlx::query(&format!(
+                    "ALTER TABLE project_version ADD COLUMN {} {}",
+                    column, column_type
+                ))
+                .execute(self.pool())
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn pool(&self) -> &Pool<Sqlite> {
+        self.connection.as_ref().expect("database to be connected")
+    }
+
+    /// Starts a transaction that the following queries run in until `commit`. Writing many rows in
+    /// one transaction is much faster than committing each of them.
+    pub async fn begin(&mut self) -> Result<(), sqlx::Error> {
+        if self.connection.is_none() {
+            self.connection = self.connect().await;
+        }
+        if self.transaction.is_none() {
+            self.transaction = Some(self.pool().begin().await?);
+        }
+        Ok(())
+    }
+
+    pub async fn commit(&mut self) -> Result<(), sqlx::Error> {
+        if let Some(transaction) = self.transaction.take() {
+            transaction.commit().await?;
+        }
+        Ok(())
+    }
+
     pub async fn execute_insert(
         &mut self,
-        query: String,
-    ) -> Result<sqlx::sqlite::SqliteQueryResult, sqlx::Error> {
+        query: SqliteQuery<'_>,
+    ) -> Result<SqliteQueryResult, sqlx::Error> {
         if self.connection.is_none() {
-            self.connect().await.expect("database to connect");
+            self.connection = self.connect().await;
+        }
+        match self.transaction.as_mut() {
+            Some(transaction) => query.execute(transaction).await,
+            None => query.execute(self.pool()).await,
         }
-        let result: Query<Sqlite, _> = sqlx::query(query.as_str());
-        result.execute(self.connection.as_ref().unwrap()).await
     }
 
     pub async fn execute_fetchone(
         &mut self,
-        query: String,
-    ) -> Result<sqlx::sqlite::SqliteRow, sqlx::Error> {
+        query: SqliteQuery<'_>,
+    ) -> Result<SqliteRow, sqlx::Error> {
         if self.connection.is_none() {
-            self.connect().await.expect("database to connect");
+            self.connection = self.connect().await;
+        }
+        match self.transaction.as_mut() {
+            Some(transaction) => query.fetch_one(transaction).await,
+            None => query.fetch_one(self.pool()).await,
         }
-        let result: Query<Sqlite, _> = sqlx::query(query.as_str());
-        result.fetch_one(self.connection.as_ref().unwrap()).await
     }
 
     pub async fn execute_fetchall(
         &mut self,
-        query: String,
-    ) -> Result<Vec<sqlx::sqlite::SqliteRow>, sqlx::Error> {
+        query: SqliteQuery<'_>,
+    ) -> Result<Vec<SqliteRow>, sqlx::Error> {
         if self.connection.is_none() {
-            self.connect().await.expect("database to connect");
+            self.connection = self.connect().await;
+        }
+        match self.transaction.as_mut() {
+            Some(transaction) => query.fetch_all(transaction).await,
+            None => query.fetch_all(self.pool()).await,
         }
-        let result: Query<Sqlite, _> = sqlx::query(query.as_str());
-        result.fetch_all(self.connection.as_ref().unwrap()).await
     }
 }
 
+/// A type stored as a row in one of the database's tables. Values are bound as query parameters
+/// rather than formatted into the SQL.
 pub trait DatabaseModel {
-    fn create_table_query(&self) -> String;
-    fn insert_into_query(&self) -> String;
+    fn insert_query(&self) -> SqliteQuery<'_>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn legacy_databases_are_adopted_and_migrated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("legacy.db");
+        let db_url = format!("sqlite://{}", path.display());
+        Sqlite::create_database(&db_url).await.unwrap();
+        let pool = SqlitePool::connect(&db_url).await.unwrap();
+        // The table and row layout written before migrations existed.
+        sqlx::query("CREATE TABLE IF NOT EXISTS project_version (path varchar(300), name varchar(150), created_at timestamp, accessed_at timestamp, modified_at timestamp, description varchar(300), primary key (name, created_at))")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO project_version (path, name, created_at, accessed_at, modified_at, description) VALUES ('/sessions/Song Project/Song.als', 'Song', '1686000000.123s', '1686000001s', '1686000002.5s', 'Some(\"first mix\")')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool.close().await;
+
+        let mut db = Database::new(&path).await;
+        let row = db
+            .execute_fetchone(sqlx::query(
+                "SELECT project_path, created_at, modified_at, description FROM project_version",
+            ))
+            .await
+            .unwrap();
+        assert_eq!(row.get::<String, usize>(0), "/sessions/Song Project");
+        assert_eq!(row.get::<i64, usize>(1), 1686000000123);
+        assert_eq!(row.get::<i64, usize>(2), 1686000002500);
+        assert_eq!(row.get::<String, usize>(3), "first mix");
+
+        // Opening the database again finds every migration applied.
+        db.migrate().await.unwrap();
+        let applied = db
+            .execute_fetchone(sqlx::query("SELECT count(*) FROM _sqlx_migrations"))
+            .await
+            .unwrap()
+            .get::<i64, usize>(0);
+        assert_eq!(applied as usize, MIGRATOR.iter().count());
+    }
 }
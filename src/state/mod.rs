@@ -1,2 +1,3 @@
 pub mod database;
+#[allow(clippy::module_inception)]
 pub mod state;
@@ -1,54 +1,82 @@
 #![allow(dead_code)]
-use crate::project::project::{has_als_files, AbletonProjectDirectory};
+use crate::cli::output::{progress_bar, status, verbose};
+use crate::config::config::{Config, SessionRoot};
+use crate::library::library::{find_library_files, update_library, LibraryFile};
+use crate::library::usage::record_preset_uses;
+use crate::live::live::{LiveDetector, LiveStatus, ProcessSource, SystemProcesses};
+use crate::parser::als::AbletonXmlParser;
+use crate::parser::structs::file_kind::FileKind;
+use crate::parser::structs::live_set::LiveSetContents;
+use crate::project::project::{
+    is_als_file, is_project_subdirectory, move_project, read_directory, refresh_projects,
+    select_project_id, store_project, AbletonProjectDirectory, BACKUP_DIRECTORY,
+    PROJECT_INFO_DIRECTORY,
+};
+use crate::search::search::{index_contents, index_project, prune_projects};
+use crate::state::database::{Database, DatabaseModel};
+use crate::version::version::{hash_file, move_version, to_epoch_millis, ProjectVersion};
+use rayon::prelude::*;
+use serde::Serialize;
+use sqlx::{FromRow, Row};
 use std::{
+    collections::{HashMap, HashSet},
     fmt::{Display, Error, Formatter},
     fs,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::SystemTime,
 };
-use sysinfo::{System, SystemExt};
 
 pub struct ProgramState {
     pub ableton_application_path: PathBuf,
     pub ableton_running: bool,
-    pub ableton_projects_directory: PathBuf,
+    /// The running Live and the sets that look open in it.
+    pub live: LiveStatus,
+    pub session_roots: Vec<SessionRoot>,
     pub ableton_projects: Vec<AbletonProjectDirectory>,
+    /// Racks, presets, clips and grooves found in the session roots and the User Library.
+    pub library_files: Vec<LibraryFile>,
+    /// Live's User Library, labelled as a root of its own.
+    pub user_library: SessionRoot,
 }
 
 impl ProgramState {
-    pub fn new(
-        mut sessions_path: Option<PathBuf>,
-        mut ableton_path: Option<PathBuf>,
-    ) -> ProgramState {
-        if sessions_path.is_none() {
-            let mut new_path = PathBuf::new();
-            new_path.push("/Users/jessewilliams/Ableton Sessions");
-            sessions_path = Some(new_path);
-        }
-        if ableton_path.is_none() {
-            let mut new_path = PathBuf::new();
-            new_path.push("/Applications/Ableton Live 11 Suite.app");
-            ableton_path = Some(new_path);
-        }
-        assert!(sessions_path.as_ref().unwrap().as_path().is_dir());
-        let ableton_projects = get_projects_and_versions(&sessions_path.clone().unwrap());
+    pub fn new(config: &Config) -> ProgramState {
+        ProgramState::with_processes(config, &SystemProcesses)
+    }
+
+    /// Builds the state with Live looked for among `processes` instead of the system's.
+    pub fn with_processes(config: &Config, processes: &dyn ProcessSource) -> ProgramState {
+        let session_roots = config.session_roots.value.clone();
+        let user_library = config.user_library_root();
+        let (ableton_projects, library_files) =
+            get_projects_and_versions(&session_roots, &user_library, config);
+        let live = LiveStatus::new(
+            LiveDetector::new(config).detect(processes),
+            &ableton_projects,
+        );
         ProgramState {
-            ableton_application_path: ableton_path.unwrap(),
-            ableton_running: ableton_is_running(),
-            ableton_projects_directory: sessions_path.unwrap(),
+            ableton_application_path: config.live_application_path.value.clone(),
+            ableton_running: live.live.is_some(),
+            live,
+            session_roots,
             ableton_projects,
+            library_files,
+            user_library,
         }
     }
 
     #[allow(dead_code)]
-    pub fn refresh_ableton_sessions(&mut self) {
-        self.ableton_projects = get_projects_and_versions(&self.ableton_projects_directory)
+    pub fn refresh_ableton_sessions(&mut self, config: &Config) {
+        (self.ableton_projects, self.library_files) =
+            get_projects_and_versions(&self.session_roots, &self.user_library, config)
     }
 
-    fn count_projects(&self) -> usize {
+    pub fn count_projects(&self) -> usize {
         self.ableton_projects.len()
     }
 
-    fn count_versions(&self) -> usize {
+    pub fn count_versions(&self) -> usize {
         let mut version_count = 0;
         for project in &self.ableton_projects {
             for _ in &project.versions {
@@ -65,6 +93,33 @@ impl ProgramState {
         }
     }
 
+    /// Folders of sets that have no Ableton Project Info folder.
+    pub fn count_loose_projects(&self) -> usize {
+        self.ableton_projects
+            .iter()
+            .filter(|project| project.is_loose())
+            .count()
+    }
+
+    pub fn count_library_files(&self) -> usize {
+        self.library_files.len()
+    }
+
+    pub fn count_backups(&self) -> usize {
+        self.ableton_projects
+            .iter()
+            .map(|project| project.backups.len())
+            .sum()
+    }
+
+    /// Roots whose directory isn't available, e.g. an unmounted external drive.
+    pub fn offline_roots(&self) -> Vec<&SessionRoot> {
+        self.session_roots
+            .iter()
+            .filter(|root| !root.is_online())
+            .collect()
+    }
+
     #[allow(dead_code)]
     pub fn print_project_names(&self) {
         for project in &self.ableton_projects {
@@ -75,51 +130,768 @@ impl ProgramState {
 
 impl Display for ProgramState {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        writeln!(
+            f,
+            "Program State:\n    Ableton application path: {:?}\n    Ableton session roots:",
+            self.ableton_application_path,
+        )?;
+        for root in &self.session_roots {
+            writeln!(
+                f,
+                "        {} ({})",
+                root,
+                if root.is_online() {
+                    "online"
+                } else {
+                    "offline"
+                }
+            )?;
+        }
         write!(
             f,
-            "Program State:\n    Ableton application path: {:?}\n    Ableton sessions directory: {:?}\n    Ableton running: {:?}\n    Ableton projects found: {:?}\n    Ableton project versions found: {:?}\n\n",
-            self.ableton_application_path, self.ableton_projects_directory, self.ableton_running, self.count_projects(), self.count_versions(),
+            "    Ableton running: {:?}{}\n    Ableton projects found: {:?}\n    Ableton project versions found: {:?}\n\n",
+            self.ableton_running,
+            self.live.live.as_ref().map(|live| format!(" ({})", live)).unwrap_or_default(),
+            self.count_projects(),
+            self.count_versions(),
         )
     }
 }
 
-fn ableton_is_running() -> bool {
-    System::new_all()
-        .processes_by_exact_name("Live")
-        .next()
-        .is_some()
+/// Opens a set in the configured Live application.
+pub fn open_in_live(live_application_path: &Path, set: &Path) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("open");
+        command.arg("-a").arg(live_application_path);
+        command
+    } else {
+        Command::new(live_application_path)
+    };
+    command
+        .arg(set)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    Ok(())
+}
+
+/// What discovery found below a session root.
+#[derive(Default)]
+struct Discovered {
+    project_directories: Vec<PathBuf>,
+    /// Racks, presets, clips and grooves outside projects, or at the top of a project's folder.
+    library_files: Vec<PathBuf>,
+}
+
+impl Discovered {
+    fn merge(mut self, other: Discovered) -> Discovered {
+        self.project_directories.extend(other.project_directories);
+        self.library_files.extend(other.library_files);
+        self
+    }
+}
+
+/// Finds the project directories and library files below `dir`. A directory with an
+/// `Ableton Project Info` folder is a project Live created, and isn't searched any further. A
+/// directory that holds sets without one is a folder of loose sets, which may have projects below
+/// it too. Subdirectories are searched in parallel. Symlinked directories are followed unless they
+/// lead back to a directory that's already being searched, which would never end.
+fn get_session_directories(dir: &Path, ancestors: &[PathBuf], config: &Config) -> Discovered {
+    let canonical = match fs::canonicalize(dir) {
+        Ok(canonical) => canonical,
+        Err(error) => {
+            status!("skipping {}: {}", dir.display(), error);
+            return Discovered::default();
+        }
+    };
+    if ancestors.contains(&canonical) {
+        status!(
+            "skipping {}: it links back to {}",
+            dir.display(),
+            canonical.display()
+        );
+        return Discovered::default();
+    }
+    let entries = read_directory(dir);
+    let library_files: Vec<PathBuf> = entries
+        .iter()
+        .filter(|path| {
+            FileKind::from_path(path).is_some_and(|kind| kind.is_library_file())
+                && path.is_file()
+                && !config.is_ignored(path)
+        })
+        .cloned()
+        .collect();
+    if has_project_info(&entries) {
+        return Discovered {
+            project_directories: vec![dir.to_path_buf()],
+            library_files,
+        };
+    }
+    let loose = holds_sets(&entries);
+    let ancestors = [ancestors, &[canonical]].concat();
+    let found = Discovered {
+        project_directories: if loose {
+            vec![dir.to_path_buf()]
+        } else {
+            vec![]
+        },
+        library_files,
+    };
+    entries
+        .into_par_iter()
+        .filter(|path| path.is_dir() && !is_project_subdirectory(path) && !config.is_ignored(path))
+        .map(|path| get_session_directories(&path, &ancestors, config))
+        .collect::<Vec<Discovered>>()
+        .into_iter()
+        .fold(found, Discovered::merge)
+}
+
+fn has_project_info(entries: &[PathBuf]) -> bool {
+    entries.iter().any(|path| {
+        path.file_name()
+            .is_some_and(|name| name == PROJECT_INFO_DIRECTORY)
+            && path.is_dir()
+    })
+}
+
+/// Whether a directory with these entries holds sets, or backups of sets that have since been
+/// deleted.
+fn holds_sets(entries: &[PathBuf]) -> bool {
+    let holds_set_files = |entries: &[PathBuf]| {
+        entries
+            .iter()
+            .any(|path| is_als_file(path) && path.is_file())
+    };
+    holds_set_files(entries)
+        || entries.iter().any(|path| {
+            path.file_name()
+                .is_some_and(|name| name == BACKUP_DIRECTORY)
+                && path.is_dir()
+                && holds_set_files(&read_directory(path))
+        })
 }
 
-fn get_session_directories(search_dir: fs::ReadDir) -> Vec<PathBuf> {
-    let mut ableton_session_directories = vec![];
-    let mut search_dirs: Vec<PathBuf> = search_dir.map(|x| return x.unwrap().path()).collect();
-    while search_dirs.len() > 0 {
-        let current_path = search_dirs.pop().unwrap();
-        if !current_path.as_path().is_dir() {
+/// The projects in the session roots, and the library files in them and in the User Library.
+fn get_projects_and_versions(
+    session_roots: &[SessionRoot],
+    user_library: &SessionRoot,
+    config: &Config,
+) -> (Vec<AbletonProjectDirectory>, Vec<LibraryFile>) {
+    let found: Vec<(Vec<AbletonProjectDirectory>, Vec<LibraryFile>)> = session_roots
+        .par_iter()
+        .map(|session_root| {
+            // Offline roots are reported when the index is updated.
+            if !session_root.is_online() {
+                verbose!(
+                    "skipping session root {}: it isn't a directory",
+                    session_root
+                );
+                return (vec![], vec![]);
+            }
+            let discovered = get_session_directories(&session_root.path, &[], config);
+            let root = Some(session_root.label.clone());
+            (
+                discovered
+                    .project_directories
+                    .into_par_iter()
+                    .map(|path| AbletonProjectDirectory::new(path, root.clone()))
+                    .collect(),
+                discovered
+                    .library_files
+                    .iter()
+                    .filter_map(|path| LibraryFile::from_path(path, root.clone()))
+                    .collect(),
+            )
+        })
+        .collect();
+    let (projects, library_files): (Vec<_>, Vec<_>) = found.into_iter().unzip();
+    let mut library_files: Vec<LibraryFile> = library_files.into_iter().flatten().collect();
+    if user_library.is_online() {
+        // A User Library inside a session root is only indexed as the User Library.
+        library_files.retain(|file| !file.path.starts_with(&user_library.path));
+        library_files.extend(
+            find_library_files(&user_library.path, &[], config)
+                .into_par_iter()
+                .filter_map(|path| LibraryFile::from_path(&path, Some(user_library.label.clone())))
+                .collect::<Vec<LibraryFile>>(),
+        );
+    }
+    (projects.into_iter().flatten().collect(), library_files)
+}
+
+/// What a scan changed in the index.
+#[derive(Debug, Default, Serialize)]
+pub struct IndexChanges {
+    /// Files that weren't indexed before.
+    pub added: usize,
+    /// Files whose size or modified time changed since the last scan.
+    pub updated: usize,
+    /// Indexed files found again under a new path, recognised by their contents.
+    pub moved: usize,
+    /// Indexed files in online roots that the scan didn't find.
+    pub removed: usize,
+    /// Files skipped because they haven't changed since the last scan.
+    pub unchanged: usize,
+    /// Indexed projects found again in a renamed or moved folder.
+    pub moved_projects: usize,
+}
+
+impl Display for IndexChanges {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), Error> {
+        write!(
+            f,
+            "{} added, {} updated, {} moved, {} removed, {} unchanged",
+            self.added, self.updated, self.moved, self.removed, self.unchanged
+        )?;
+        if self.moved_projects > 0 {
+            write!(f, ", {} projects moved", self.moved_projects)?;
+        }
+        Ok(())
+    }
+}
+
+/// Versions written to the database per transaction.
+const BATCH_SIZE: usize = 500;
+
+/// A set that changed since the last scan, as read by one of the scan's workers.
+struct ReadSet {
+    content_hash: anyhow::Result<String>,
+    /// Only read when the contents aren't already indexed under another path.
+    contents: Option<anyhow::Result<LiveSetContents>>,
+}
+
+fn read_set(version: &ProjectVersion, stored_hashes: &HashSet<&str>) -> ReadSet {
+    let content_hash = hash_file(&version.path);
+    let contents = match &content_hash {
+        Ok(content_hash)
+            if version.backup_of.is_none() && !stored_hashes.contains(content_hash.as_str()) =>
+        {
+            Some(AbletonXmlParser::new().read_contents_of(&version.path))
+        }
+        _ => None,
+    };
+    ReadSet {
+        content_hash,
+        contents,
+    }
+}
+
+/// Brings the index up to date with the last scan. Files whose size and modified time match what's
+/// stored are skipped. The rest are hashed and parsed in parallel on rayon's thread pool, then
+/// written in batched transactions; a set that was moved or renamed is recognised by its hash and
+/// keeps its description and tags, and a project folder that was renamed or moved keeps its id.
+/// Library files are brought up to date the same way. Versions that are no longer found in online
+/// roots are marked as removed. Versions in offline roots are left alone, since their drive may
+/// just not be mounted.
+pub async fn update_index(
+    db: &mut Database,
+    state: &mut ProgramState,
+) -> anyhow::Result<IndexChanges> {
+    let mut changes = IndexChanges::default();
+    let mut stored: HashMap<String, ProjectVersion> = db
+        .execute_fetchall(sqlx::query("SELECT * FROM project_version"))
+        .await?
+        .iter()
+        .map(|row| {
+            let version = ProjectVersion::from_row(row)?;
+            Ok((version.path.to_string_lossy().into_owned(), version))
+        })
+        .collect::<Result<_, sqlx::Error>>()?;
+    let mut scanned = HashSet::new();
+    let online_roots: HashSet<String> = state
+        .session_roots
+        .iter()
+        .filter(|root| root.is_online())
+        .map(|root| root.label.clone())
+        .collect();
+    // Changed versions by the index of their project and their position among its versions and
+    // then its backups.
+    let mut pending: Vec<(usize, usize)> = vec![];
+    for (index, project) in state.ableton_projects.iter().enumerate() {
+        for (position, version) in project.versions.iter().chain(&project.backups).enumerate() {
+            let path = version.path.to_string_lossy().into_owned();
+            let unchanged = stored
+                .get(&path)
+                .is_some_and(|previous| previous.is_unchanged(version.size, &version.modified_at));
+            scanned.insert(path);
+            if unchanged {
+                changes.unchanged += 1;
+            } else {
+                pending.push((index, position));
+            }
+        }
+    }
+
+    let progress = progress_bar(pending.len() as u64, "reading sets");
+    let read_sets: Vec<ReadSet> = {
+        let stored_hashes: HashSet<&str> = stored
+            .values()
+            .filter_map(|version| version.content_hash.as_deref())
+            .collect();
+        let projects = &state.ableton_projects;
+        pending
+            .par_iter()
+            .map(|&(index, position)| {
+                let version = projects[index]
+                    .versions
+                    .iter()
+                    .chain(&projects[index].backups)
+                    .nth(position)
+                    .expect("pending version to be in its project");
+                let read_set = read_set(version, &stored_hashes);
+                progress.inc(1);
+                read_set
+            })
+            .collect()
+    };
+    progress.finish_and_clear();
+    let mut changed_projects: Vec<(usize, Vec<(usize, ReadSet)>)> = vec![];
+    for ((index, position), read_set) in pending.into_iter().zip(read_sets) {
+        match changed_projects.last_mut() {
+            Some((last, read_sets)) if *last == index => read_sets.push((position, read_set)),
+            _ => changed_projects.push((index, vec![(position, read_set)])),
+        }
+    }
+
+    db.begin().await?;
+    let mut written = 0;
+    for (index, read_sets) in changed_projects {
+        let content_hashes: HashSet<&str> = read_sets
+            .iter()
+            .filter_map(|(_, read_set)| read_set.content_hash.as_deref().ok())
+            .collect();
+        store_project_identity(
+            db,
+            &state.ableton_projects[index],
+            &stored,
+            &content_hashes,
+            &online_roots,
+            &mut changes,
+        )
+        .await?;
+        for (position, read_set) in read_sets {
+            if written > 0 && written % BATCH_SIZE == 0 {
+                db.commit().await?;
+                db.begin().await?;
+            }
+            written += 1;
+            let project = &mut state.ableton_projects[index];
+            let version = project
+                .versions
+                .iter_mut()
+                .chain(&mut project.backups)
+                .nth(position)
+                .expect("pending version to be in its project");
+            let path = version.path.to_string_lossy().into_owned();
+            let content_hash = match read_set.content_hash {
+                Ok(content_hash) => content_hash,
+                Err(error) => {
+                    status!("couldn't read {}: {:#}", version.path.display(), error);
+                    continue;
+                }
+            };
+            // Saving a set without changing it only moves its modified time, and a moved set takes
+            // its search entries with it, so neither needs to be read again.
+            let mut contents_changed = true;
+            if let Some(previous) = stored.get(&path) {
+                contents_changed = previous.content_hash.as_ref() != Some(&content_hash);
+                changes.updated += 1;
+            } else if let Some(moved_from) =
+                find_moved_version(&stored, &scanned, &online_roots, &content_hash)
+            {
+                status!("{} was moved to {}", moved_from, path);
+                move_version(db, Path::new(&moved_from), &version.path).await?;
+                stored.remove(&moved_from);
+                contents_changed = false;
+                changes.moved += 1;
+            } else {
+                changes.added += 1;
+            }
+            version.content_hash = Some(content_hash);
+            db.execute_insert(version.insert_query()).await?;
+            if contents_changed && version.backup_of.is_none() {
+                // Sets whose contents were already indexed under another path, like a copy of a
+                // version, weren't parsed by the workers.
+                let contents = read_set
+                    .contents
+                    .unwrap_or_else(|| AbletonXmlParser::new().read_contents_of(&version.path));
+                match contents {
+                    Ok(contents) => {
+                        index_contents(db, version, &contents).await?;
+                        record_preset_uses(db, version, &contents).await?;
+                    }
+                    Err(error) => status!("couldn't read {}: {:#}", version.path.display(), error),
+                }
+            }
+        }
+        index_project(db, &state.ableton_projects[index]).await?;
+    }
+    let library_roots: Vec<SessionRoot> = state
+        .session_roots
+        .iter()
+        .chain([&state.user_library])
+        .cloned()
+        .collect();
+    update_library(db, &mut state.library_files, &library_roots, &mut changes).await?;
+    let removed_at = to_epoch_millis(&SystemTime::now());
+    for root in &state.session_roots {
+        if !root.is_online() {
+            status!(
+                "session root {} is offline, keeping its {} indexed projects",
+                root,
+                count_root_projects(db, root).await?
+            );
             continue;
         }
-        let current_dir = fs::read_dir(current_path).unwrap();
-        for path in current_dir {
-            if has_als_files(path.as_ref().unwrap().path()) {
-                ableton_session_directories.push(path.as_ref().unwrap().path());
+        // Only files under the root's directory are reconciled; snapshots taken by `watch` are
+        // labelled with the root of the set they were taken from but live elsewhere.
+        let root_path = root.path.join("").to_string_lossy().into_owned();
+        for (path, version) in &stored {
+            if version.removed_at.is_some()
+                || scanned.contains(path)
+                || !path.starts_with(&root_path)
+                || version
+                    .root
+                    .as_ref()
+                    .is_some_and(|label| *label != root.label)
+            {
                 continue;
             }
-            if !path.as_ref().unwrap().path().is_dir() {
-                continue;
+            db.execute_insert(
+                sqlx::query("UPDATE project_version SET removed_at = ? WHERE path = ?")
+                    .bind(removed_at)
+                    .bind(path),
+            )
+            .await?;
+            changes.removed += 1;
+        }
+    }
+    refresh_projects(db, None).await?;
+    prune_projects(db).await?;
+    db.commit().await?;
+    Ok(changes)
+}
+
+/// Stores `project`, or recognises it as a stored project whose folder was renamed or moved: one
+/// on an online root whose folder is gone and whose sets had the contents of the sets found in
+/// `project`. A moved project keeps its id and notes.
+async fn store_project_identity(
+    db: &mut Database,
+    project: &AbletonProjectDirectory,
+    stored: &HashMap<String, ProjectVersion>,
+    content_hashes: &HashSet<&str>,
+    online_roots: &HashSet<String>,
+    changes: &mut IndexChanges,
+) -> anyhow::Result<i64> {
+    if select_project_id(db, &project.path).await?.is_none() {
+        if let Some((id, moved_from)) = find_moved_project(stored, content_hashes, online_roots) {
+            status!(
+                "project {} was moved to {}",
+                moved_from.display(),
+                project.path.display()
+            );
+            move_project(db, id, project).await?;
+            changes.moved_projects += 1;
+        }
+    }
+    store_project(db, project).await
+}
+
+/// The stored project with the most sets with one of `content_hashes`, among those on the
+/// `online_roots` whose folder is gone, along with its path. A project on an offline root keeps
+/// its id, notes and to-dos when a copy of it is found elsewhere.
+fn find_moved_project(
+    stored: &HashMap<String, ProjectVersion>,
+    content_hashes: &HashSet<&str>,
+    online_roots: &HashSet<String>,
+) -> Option<(i64, PathBuf)> {
+    let mut matches: HashMap<(i64, &Path), usize> = HashMap::new();
+    for version in stored
+        .values()
+        .filter(|version| is_in_online_root(version, online_roots))
+    {
+        if let (Some(id), Some(project_path), Some(content_hash)) = (
+            version.project_id,
+            &version.project_path,
+            &version.content_hash,
+        ) {
+            if content_hashes.contains(content_hash.as_str()) {
+                *matches.entry((id, project_path.as_path())).or_default() += 1;
             }
-            search_dirs.push(path.as_ref().unwrap().path());
         }
     }
-    return ableton_session_directories;
+    matches
+        .into_iter()
+        .filter(|((_, project_path), _)| !project_path.exists())
+        .max_by_key(|((id, _), count)| (*count, -id))
+        .map(|((id, project_path), _)| (id, project_path.to_path_buf()))
+}
+
+/// A stored version with the given contents whose file is gone from an online root, which a new
+/// file with the same contents is taken to have been moved from. A version on an offline root is
+/// only out of reach, so a copy of it found elsewhere doesn't take its place.
+fn find_moved_version(
+    stored: &HashMap<String, ProjectVersion>,
+    scanned: &HashSet<String>,
+    online_roots: &HashSet<String>,
+    content_hash: &str,
+) -> Option<String> {
+    stored
+        .iter()
+        .filter(|(path, version)| {
+            version.content_hash.as_deref() == Some(content_hash)
+                && !scanned.contains(*path)
+                && is_in_online_root(version, online_roots)
+                && !Path::new(path).exists()
+        })
+        .map(|(path, _)| path.clone())
+        .min()
 }
 
-fn get_projects_and_versions(ableton_projects_path: &PathBuf) -> Vec<AbletonProjectDirectory> {
-    let root_dir = fs::read_dir(ableton_projects_path).expect("path to be valid");
-    let ableton_session_directories = get_session_directories(root_dir);
-    let mut project_directories: Vec<AbletonProjectDirectory> = vec![];
-    for path in ableton_session_directories {
-        let ableton_project_directory = AbletonProjectDirectory::new(path);
-        project_directories.push(ableton_project_directory);
+/// Whether `version` was scanned from one of the `online_roots`. Versions stored without a root
+/// predate labelled roots, and are taken to be online as they were before.
+fn is_in_online_root(version: &ProjectVersion, online_roots: &HashSet<String>) -> bool {
+    version
+        .root
+        .as_ref()
+        .is_none_or(|label| online_roots.contains(label))
+}
+
+/// The number of indexed projects scanned from `root`.
+pub async fn count_root_projects(db: &mut Database, root: &SessionRoot) -> anyhow::Result<i64> {
+    let row = db
+        .execute_fetchone(
+            sqlx::query("SELECT count(*) FROM project WHERE root = ? AND removed_at IS NULL")
+                .bind(&root.label),
+        )
+        .await?;
+    Ok(row.get(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::config::ConfigFlags;
+    use crate::project::samples::tests::write_set;
+    use crate::project::status::set_notes;
+    use crate::version::version::{describe_version, select_version};
+
+    /// The configuration of a scan of the roots named `roots` in `dir`.
+    fn config_with_roots(dir: &Path, roots: &[&str]) -> Config {
+        let config_file = dir.join("config.toml");
+        fs::write(&config_file, "").unwrap();
+        let flags = ConfigFlags {
+            config_file: Some(config_file),
+            session_roots: roots
+                .iter()
+                .map(|root| SessionRoot::new(Some(String::from(*root)), dir.join(root)))
+                .collect(),
+            user_library: Some(dir.join("User Library")),
+            ..ConfigFlags::default()
+        };
+        Config::load_from(&flags, &|_| None).unwrap()
+    }
+
+    /// Writes a project named `name` into `root` with a set of each of `sets`, whose contents
+    /// differ by name.
+    fn write_project(root: &Path, name: &str, sets: &[&str]) -> PathBuf {
+        let path = root.join(format!("{} Project", name));
+        fs::create_dir_all(path.join(PROJECT_INFO_DIRECTORY)).unwrap();
+        for set in sets {
+            let sample = PathBuf::from(format!("/samples/{}.wav", set));
+            write_set(&path.join(format!("{}.als", set)), &[(&sample, "")]);
+        }
+        path
+    }
+
+    async fn scan(db: &mut Database, config: &Config) -> IndexChanges {
+        update_index(db, &mut ProgramState::new(config))
+            .await
+            .unwrap()
+    }
+
+    async fn indexed_versions(db: &mut Database) -> Vec<(String, bool)> {
+        db.execute_fetchall(sqlx::query(
+            "SELECT path, removed_at IS NULL FROM project_version ORDER BY path",
+        ))
+        .await
+        .unwrap()
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect()
+    }
+
+    #[tokio::test]
+    async fn copies_of_sets_on_offline_roots_stay_separate() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(dir.join("main")).unwrap();
+        let original = write_project(&dir.join("ssd"), "Song", &["Song"]);
+        let config = config_with_roots(&dir, &["ssd", "main"]);
+        let mut db = Database::new(&dir.join("index.db")).await;
+        scan(&mut db, &config).await;
+
+        // The project is copied to the main drive, and the SSD is unplugged.
+        let copy = write_project(&dir.join("main"), "Song", &["Song"]);
+        fs::rename(dir.join("ssd"), dir.join("unplugged")).unwrap();
+        let changes = scan(&mut db, &config).await;
+        assert_eq!((changes.added, changes.moved), (1, 0));
+        assert_eq!(
+            indexed_versions(&mut db).await,
+            vec![
+                (copy.join("Song.als").to_string_lossy().into_owned(), true),
+                (
+                    original.join("Song.als").to_string_lossy().into_owned(),
+                    true
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn sets_moved_within_online_roots_keep_their_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let project = write_project(&dir.join("main"), "Song", &["Song"]);
+        let config = config_with_roots(&dir, &["main"]);
+        let mut db = Database::new(&dir.join("index.db")).await;
+        scan(&mut db, &config).await;
+        describe_version(&mut db, &project.join("Song.als"), "first mix")
+            .await
+            .unwrap();
+
+        fs::rename(project.join("Song.als"), project.join("Song mix.als")).unwrap();
+        let changes = scan(&mut db, &config).await;
+        assert_eq!((changes.added, changes.moved, changes.removed), (0, 1, 0));
+        let moved = select_version(&mut db, &project.join("Song mix.als"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(moved.description.as_deref(), Some("first mix"));
+    }
+
+    async fn project_notes(db: &mut Database, id: i64) -> Option<String> {
+        db.execute_fetchone(sqlx::query("SELECT notes FROM project WHERE id = ?").bind(id))
+            .await
+            .unwrap()
+            .get(0)
+    }
+
+    #[tokio::test]
+    async fn renamed_project_folders_keep_their_id_and_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let project = write_project(&dir.join("main"), "Song", &["Song", "Song 2"]);
+        let config = config_with_roots(&dir, &["main"]);
+        let mut db = Database::new(&dir.join("index.db")).await;
+        scan(&mut db, &config).await;
+        let id = select_project_id(&mut db, &project).await.unwrap().unwrap();
+        set_notes(&mut db, id, Some("needs a new bass"))
+            .await
+            .unwrap();
+
+        let renamed = dir.join("main/Song Final Project");
+        fs::rename(&project, &renamed).unwrap();
+        let changes = scan(&mut db, &config).await;
+        assert_eq!((changes.moved_projects, changes.moved), (1, 2));
+        assert_eq!(
+            select_project_id(&mut db, &renamed).await.unwrap(),
+            Some(id)
+        );
+        assert_eq!(select_project_id(&mut db, &project).await.unwrap(), None);
+        assert_eq!(
+            project_notes(&mut db, id).await.as_deref(),
+            Some("needs a new bass")
+        );
+    }
+
+    #[tokio::test]
+    async fn projects_on_offline_roots_keep_their_id_and_notes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(dir.join("main")).unwrap();
+        let original = write_project(&dir.join("ssd"), "Song", &["Song"]);
+        let config = config_with_roots(&dir, &["ssd", "main"]);
+        let mut db = Database::new(&dir.join("index.db")).await;
+        scan(&mut db, &config).await;
+        let id = select_project_id(&mut db, &original)
+            .await
+            .unwrap()
+            .unwrap();
+        set_notes(&mut db, id, Some("needs a new bass"))
+            .await
+            .unwrap();
+
+        let copy = write_project(&dir.join("main"), "Song", &["Song"]);
+        fs::rename(dir.join("ssd"), dir.join("unplugged")).unwrap();
+        let changes = scan(&mut db, &config).await;
+        assert_eq!(changes.moved_projects, 0);
+        assert_eq!(
+            select_project_id(&mut db, &original).await.unwrap(),
+            Some(id)
+        );
+        let copy_id = select_project_id(&mut db, &copy).await.unwrap().unwrap();
+        assert_ne!(copy_id, id);
+        assert_eq!(
+            project_notes(&mut db, id).await.as_deref(),
+            Some("needs a new bass")
+        );
+        assert_eq!(project_notes(&mut db, copy_id).await, None);
+    }
+
+    fn discovered_projects(config: &Config, root: &Path) -> Vec<PathBuf> {
+        let mut projects = get_session_directories(root, &[], config).project_directories;
+        projects.sort();
+        projects
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_back_into_a_searched_directory_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let root = dir.join("main");
+        let song = write_project(&root.join("Albums"), "Song", &["Song"]);
+        let other = write_project(&dir.join("elsewhere"), "Other", &["Other"]);
+        std::os::unix::fs::symlink(&root, root.join("Albums/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("Albums"), root.join("Albums/Sketches")).unwrap();
+        std::os::unix::fs::symlink(dir.join("elsewhere"), root.join("linked")).unwrap();
+        let config = config_with_roots(&dir, &["main"]);
+
+        assert_eq!(
+            discovered_projects(&config, &root),
+            vec![song, root.join("linked").join(other.file_name().unwrap())]
+        );
+    }
+
+    #[tokio::test]
+    async fn sets_with_dots_in_their_names_are_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let project = write_project(
+            &dir.join("main/v1.2"),
+            "My.Song",
+            &["My.Song", "My.Song.v2"],
+        );
+        // Metadata macOS leaves on other filesystems, and files that only look like sets.
+        write_set(&project.join("._My.Song.als"), &[]);
+        fs::write(project.join("My.Song.als.bak"), "").unwrap();
+        fs::create_dir(project.join("Stems.als")).unwrap();
+        let config = config_with_roots(&dir, &["main"]);
+
+        assert_eq!(
+            discovered_projects(&config, &dir.join("main")),
+            vec![project.clone()]
+        );
+        let mut db = Database::new(&dir.join("index.db")).await;
+        assert_eq!(scan(&mut db, &config).await.added, 2);
+        let version = select_version(&mut db, &project.join("My.Song.v2.als"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(version.name, "My.Song.v2");
+        assert_eq!(AbletonProjectDirectory::new(project, None).name, "My.Song");
     }
-    project_directories
 }
@@ -0,0 +1,511 @@
+use crate::config::config::Config;
+use crate::diff::diff::LiveSetDiff;
+use crate::parser::als::AbletonXmlParser;
+use crate::parser::structs::live_set::LiveSetContents;
+use crate::project::project::{select_projects, AbletonProjectDirectory, ProjectQuery};
+use crate::state::database::Database;
+use crate::state::state::open_in_live;
+use crate::version::tag::add_tags;
+use crate::version::version::{
+    format_time, list_versions, select_previous_version, VersionListing, VersionQuery,
+};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::{Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+use std::collections::HashMap;
+use std::io::{self, Stdout};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const PAGE_SIZE: usize = 10;
+
+type CrosstermTerminal = Terminal<CrosstermBackend<Stdout>>;
+
+#[derive(Debug, PartialEq)]
+enum View {
+    Projects,
+    Versions,
+}
+
+/// Text being typed into the status line.
+#[derive(Debug)]
+enum Input {
+    Filter(String),
+    Tag(String),
+}
+
+/// Browses the indexed projects and their versions. The side pane shows the selected set's
+/// contents, or its diff against the previous version.
+pub struct Browser<'a> {
+    config: &'a Config,
+    view: View,
+    projects: Vec<AbletonProjectDirectory>,
+    filter: String,
+    /// Indexes into `projects` of the projects matching `filter`.
+    visible_projects: Vec<usize>,
+    project_list: ListState,
+    versions: Vec<VersionListing>,
+    version_list: ListState,
+    input: Option<Input>,
+    message: Option<String>,
+    diff: Option<LiveSetDiff>,
+    contents: HashMap<PathBuf, Result<LiveSetContents, String>>,
+}
+
+impl<'a> Browser<'a> {
+    pub fn new(config: &'a Config, projects: Vec<AbletonProjectDirectory>) -> Browser<'a> {
+        let mut browser = Browser {
+            config,
+            view: View::Projects,
+            projects,
+            filter: String::new(),
+            visible_projects: vec![],
+            project_list: ListState::default(),
+            versions: vec![],
+            version_list: ListState::default(),
+            input: None,
+            message: None,
+            diff: None,
+            contents: HashMap::new(),
+        };
+        browser.apply_filter();
+        browser
+    }
+
+    /// Takes over the terminal until the user quits, restoring it even if browsing fails.
+    pub async fn run(&mut self, db: &mut Database) -> anyhow::Result<()> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        let result = self.event_loop(&mut terminal, db).await;
+        disable_raw_mode()?;
+        execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+        terminal.show_cursor()?;
+        result
+    }
+
+    async fn event_loop(
+        &mut self,
+        terminal: &mut CrosstermTerminal,
+        db: &mut Database,
+    ) -> anyhow::Result<()> {
+        loop {
+            self.read_selected_contents();
+            terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(POLL_INTERVAL)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if self.input.is_some() {
+                self.handle_input(key.code, db).await?;
+            } else if !self.handle_key(key.code, db).await? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handles a key while browsing. Returns false when the user quits.
+    async fn handle_key(&mut self, code: KeyCode, db: &mut Database) -> anyhow::Result<bool> {
+        self.message = None;
+        match code {
+            KeyCode::Char('q') => return Ok(false),
+            KeyCode::Esc if self.view == View::Projects => return Ok(false),
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-(PAGE_SIZE as isize)),
+            KeyCode::PageDown => self.move_selection(PAGE_SIZE as isize),
+            KeyCode::Home | KeyCode::Char('g') => self.move_selection(isize::MIN / 2),
+            KeyCode::End | KeyCode::Char('G') => self.move_selection(isize::MAX / 2),
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') if self.view == View::Projects => {
+                self.open_project(db).await?
+            }
+            KeyCode::Esc | KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h')
+                if self.view == View::Versions =>
+            {
+                self.view = View::Projects;
+                self.diff = None;
+            }
+            KeyCode::Char('/') if self.view == View::Projects => {
+                self.input = Some(Input::Filter(self.filter.clone()))
+            }
+            KeyCode::Char('t') if self.view == View::Versions => {
+                self.input = Some(Input::Tag(String::new()))
+            }
+            KeyCode::Char('o') => self.open_selected_in_live(),
+            KeyCode::Char('d') if self.view == View::Versions => self.diff_selected(db).await?,
+            _ => {}
+        }
+        Ok(true)
+    }
+
+    async fn handle_input(&mut self, code: KeyCode, db: &mut Database) -> anyhow::Result<()> {
+        let Some(input) = self.input.as_mut() else {
+            return Ok(());
+        };
+        let text = match input {
+            Input::Filter(text) | Input::Tag(text) => text,
+        };
+        match code {
+            KeyCode::Char(character) => text.push(character),
+            KeyCode::Backspace => {
+                text.pop();
+            }
+            KeyCode::Esc => {
+                if matches!(input, Input::Filter(_)) {
+                    self.filter.clear();
+                    self.apply_filter();
+                }
+                self.input = None;
+                return Ok(());
+            }
+            KeyCode::Enter => {
+                if let Some(Input::Tag(text)) = self.input.take() {
+                    self.tag_selected(db, &text).await?;
+                } else {
+                    self.input = None;
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        if let Some(Input::Filter(text)) = &self.input {
+            self.filter = text.clone();
+            self.apply_filter();
+        }
+        Ok(())
+    }
+
+    fn apply_filter(&mut self) {
+        let filter = self.filter.to_lowercase();
+        self.visible_projects = self
+            .projects
+            .iter()
+            .enumerate()
+            .filter(|(_, project)| project.name.to_lowercase().contains(&filter))
+            .map(|(index, _)| index)
+            .collect();
+        self.project_list
+            .select((!self.visible_projects.is_empty()).then_some(0));
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        let (state, length) = match self.view {
+            View::Projects => (&mut self.project_list, self.visible_projects.len()),
+            View::Versions => (&mut self.version_list, self.versions.len()),
+        };
+        if length == 0 {
+            return;
+        }
+        let current = state.selected().unwrap_or(0) as isize;
+        let selected = current.saturating_add(offset).clamp(0, length as isize - 1);
+        state.select(Some(selected as usize));
+        self.diff = None;
+    }
+
+    fn selected_project(&self) -> Option<&AbletonProjectDirectory> {
+        let index = self.visible_projects.get(self.project_list.selected()?)?;
+        self.projects.get(*index)
+    }
+
+    fn selected_version(&self) -> Option<&VersionListing> {
+        self.versions.get(self.version_list.selected()?)
+    }
+
+    /// The set whose contents the side pane shows: the selected version, or the latest version of
+    /// the selected project.
+    fn selected_set(&self) -> Option<&Path> {
+        match self.view {
+            View::Versions => self
+                .selected_version()
+                .map(|listing| listing.version.path.as_path()),
+            View::Projects => self
+                .selected_project()?
+                .versions
+                .iter()
+                .max_by_key(|version| version.modified_at)
+                .map(|version| version.path.as_path()),
+        }
+    }
+
+    fn read_selected_contents(&mut self) {
+        let Some(path) = self.selected_set().map(Path::to_path_buf) else {
+            return;
+        };
+        self.contents.entry(path).or_insert_with_key(|path| {
+            AbletonXmlParser::new()
+                .read_contents_of(path)
+                .map_err(|error| error.to_string())
+        });
+    }
+
+    async fn open_project(&mut self, db: &mut Database) -> anyhow::Result<()> {
+        let Some(project_path) = self.selected_project().map(|project| project.path.clone()) else {
+            return Ok(());
+        };
+        self.load_versions(db, project_path).await?;
+        self.version_list
+            .select((!self.versions.is_empty()).then_some(0));
+        self.view = View::Versions;
+        Ok(())
+    }
+
+    async fn load_versions(
+        &mut self,
+        db: &mut Database,
+        project_path: PathBuf,
+    ) -> anyhow::Result<()> {
+        let query = VersionQuery {
+            project_path: Some(project_path),
+            include_backups: true,
+            ..Default::default()
+        };
+        self.versions = list_versions(db, &query).await?;
+        Ok(())
+    }
+
+    fn open_selected_in_live(&mut self) {
+        let Some(path) = self.selected_set().map(Path::to_path_buf) else {
+            return;
+        };
+        self.message = Some(
+            match open_in_live(&self.config.live_application_path.value, &path) {
+                Ok(()) => format!("opening {} in Live", path.display()),
+                Err(error) => format!("couldn't open {}: {}", path.display(), error),
+            },
+        );
+    }
+
+    async fn diff_selected(&mut self, db: &mut Database) -> anyhow::Result<()> {
+        let Some(listing) = self.selected_version() else {
+            return Ok(());
+        };
+        let Some(previous) = select_previous_version(db, &listing.version).await? else {
+            self.message = Some(format!("no previous version of {}", listing.version.name));
+            return Ok(());
+        };
+        match LiveSetDiff::between(&previous.path, &listing.version.path) {
+            Ok(diff) => self.diff = Some(diff),
+            Err(error) => self.message = Some(format!("couldn't diff: {}", error)),
+        }
+        Ok(())
+    }
+
+    /// Tags are separated by spaces or commas.
+    async fn tag_selected(&mut self, db: &mut Database, text: &str) -> anyhow::Result<()> {
+        let Some(listing) = self.selected_version() else {
+            return Ok(());
+        };
+        let path = listing.version.path.clone();
+        let tags: Vec<String> = text
+            .split([' ', ','])
+            .filter(|tag| !tag.is_empty())
+            .map(String::from)
+            .collect();
+        if tags.is_empty() {
+            return Ok(());
+        }
+        add_tags(db, &path, &tags).await?;
+        if let Some(project_path) = self.selected_project().map(|project| project.path.clone()) {
+            self.load_versions(db, project_path).await?;
+        }
+        self.message = Some(format!(
+            "tagged {} with {}",
+            path.display(),
+            tags.join(", ")
+        ));
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(frame.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+            .split(rows[0]);
+        match self.view {
+            View::Projects => self.draw_projects(frame, columns[0]),
+            View::Versions => self.draw_versions(frame, columns[0]),
+        }
+        self.draw_details(frame, columns[1]);
+        frame.render_widget(Paragraph::new(self.status_line()), rows[1]);
+    }
+
+    fn draw_projects(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .visible_projects
+            .iter()
+            .map(|index| {
+                let project = &self.projects[*index];
+                let root = project.root.as_deref().unwrap_or("");
+                ListItem::new(format!(
+                    "{}  ({} versions) {}",
+                    project.name,
+                    project.versions.len(),
+                    root
+                ))
+            })
+            .collect();
+        let title = if self.filter.is_empty() {
+            format!("projects ({})", self.visible_projects.len())
+        } else {
+            format!(
+                "projects matching {:?} ({})",
+                self.filter,
+                self.visible_projects.len()
+            )
+        };
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.project_list);
+    }
+
+    fn draw_versions(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .versions
+            .iter()
+            .map(|listing| {
+                let version = &listing.version;
+                let mut line = match &version.backup_of {
+                    Some(_) => format!("  {} (backup)", version.name),
+                    None => format!("{}  {}", version.name, format_time(&version.modified_at)),
+                };
+                if !listing.tags.is_empty() {
+                    line.push_str(&format!("  [{}]", listing.tags.join(", ")));
+                }
+                ListItem::new(line)
+            })
+            .collect();
+        let title = self
+            .selected_project()
+            .map(|project| format!("versions of {}", project.name))
+            .unwrap_or_default();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.version_list);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let (title, lines) = match &self.diff {
+            Some(diff) => (String::from("diff"), text_lines(&diff.to_string())),
+            None => (String::from("set"), self.detail_lines()),
+        };
+        let details = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(details, area);
+    }
+
+    fn detail_lines(&self) -> Vec<Line<'static>> {
+        let mut lines = vec![];
+        if self.view == View::Projects {
+            if let Some(project) = self.selected_project() {
+                lines.push(Line::from(format!("project: {}", project.name)));
+                lines.push(Line::from(format!("path: {}", project.path.display())));
+                if let Some(root) = &project.root {
+                    lines.push(Line::from(format!("session root: {}", root)));
+                }
+                if let Some(status) = &project.status {
+                    lines.push(Line::from(format!("status: {}", status)));
+                }
+                if let Some(notes) = &project.notes {
+                    lines.push(Line::from(format!("notes: {}", notes)));
+                }
+                let open_todos = project.todos.iter().filter(|todo| !todo.is_done()).count();
+                if open_todos > 0 {
+                    lines.push(Line::from(format!("{} to-dos left", open_todos)));
+                }
+                lines.push(Line::from(format!(
+                    "{} versions, {} backups",
+                    project.versions.len(),
+                    project.backups.len()
+                )));
+                lines.push(Line::from(""));
+            }
+        }
+        if let Some(listing) = self
+            .selected_version()
+            .filter(|_| self.view == View::Versions)
+        {
+            lines.push(Line::from(listing.version.name.clone()));
+            if let Some(description) = &listing.version.description {
+                lines.push(Line::from(format!("description: {}", description)));
+            }
+            if !listing.tags.is_empty() {
+                lines.push(Line::from(format!("tags: {}", listing.tags.join(", "))));
+            }
+        }
+        let Some(path) = self.selected_set() else {
+            return lines;
+        };
+        lines.push(Line::from(format!("{}", path.display())));
+        match self.contents.get(path) {
+            Some(Ok(contents)) => {
+                lines.extend(text_lines(&contents.to_string()));
+                lines.push(Line::from("    tracks:"));
+                for track in &contents.tracks {
+                    let devices: Vec<&str> = contents
+                        .devices_on(&track.id)
+                        .iter()
+                        .map(|device| device.name.as_str())
+                        .collect();
+                    lines.push(Line::from(format!(
+                        "        {} ({}) {}",
+                        track.name,
+                        track.kind,
+                        devices.join(", ")
+                    )));
+                }
+            }
+            Some(Err(error)) => lines.push(Line::from(format!("couldn't read set: {}", error))),
+            None => {}
+        }
+        lines
+    }
+
+    fn status_line(&self) -> String {
+        match &self.input {
+            Some(Input::Filter(text)) => format!("filter: {}", text),
+            Some(Input::Tag(text)) => format!("tags to add: {}", text),
+            None => match (&self.message, &self.view) {
+                (Some(message), _) => message.clone(),
+                (None, View::Projects) => {
+                    String::from("enter: versions  /: filter  o: open in Live  q: quit")
+                }
+                (None, View::Versions) => String::from(
+                    "esc: projects  d: diff with previous  t: tag  o: open in Live  q: quit",
+                ),
+            },
+        }
+    }
+}
+
+fn text_lines(text: &str) -> Vec<Line<'static>> {
+    text.lines()
+        .map(|line| Line::from(String::from(line)))
+        .collect()
+}
+
+/// Loads the indexed projects and browses them.
+pub async fn browse(db: &mut Database, config: &Config) -> anyhow::Result<()> {
+    let projects = select_projects(db, &ProjectQuery::default()).await?;
+    Browser::new(config, projects).run(db).await
+}
@@ -1 +1,3 @@
+pub mod tag;
+#[allow(clippy::module_inception)]
 pub mod version;
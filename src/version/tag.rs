@@ -0,0 +1,70 @@
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use crate::version::version::index_version;
+use sqlx::Row;
+use std::path::{Path, PathBuf};
+
+/// A free-form label attached to a version, e.g. `mixdown` or `sent-to-label`.
+#[derive(Debug)]
+pub struct VersionTag {
+    pub version_path: PathBuf,
+    pub tag: String,
+}
+
+impl VersionTag {
+    pub fn new(version_path: &Path, tag: &str) -> VersionTag {
+        VersionTag {
+            version_path: version_path.to_path_buf(),
+            tag: normalise_tag(tag),
+        }
+    }
+}
+
+/// Tags are matched case-insensitively, so they're stored trimmed and lowercase.
+pub fn normalise_tag(tag: &str) -> String {
+    tag.trim().to_lowercase()
+}
+
+impl DatabaseModel for VersionTag {
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        sqlx::query("INSERT OR IGNORE INTO version_tag (version_path, tag) VALUES (?, ?)")
+            .bind(self.version_path.to_string_lossy().into_owned())
+            .bind(&self.tag)
+    }
+}
+
+pub async fn add_tags(db: &mut Database, path: &Path, tags: &[String]) -> anyhow::Result<()> {
+    index_version(db, path).await?;
+    for tag in tags {
+        let tag = VersionTag::new(path, tag);
+        if tag.tag.is_empty() {
+            continue;
+        }
+        db.execute_insert(tag.insert_query()).await?;
+    }
+    Ok(())
+}
+
+pub async fn remove_tags(db: &mut Database, path: &Path, tags: &[String]) -> anyhow::Result<()> {
+    for tag in tags {
+        let tag = VersionTag::new(path, tag);
+        db.execute_insert(
+            sqlx::query("DELETE FROM version_tag WHERE version_path = ? AND tag = ?")
+                .bind(tag.version_path.to_string_lossy().into_owned())
+                .bind(&tag.tag),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Every tag in use, with the number of versions carrying it.
+pub async fn list_tags(db: &mut Database) -> anyhow::Result<Vec<(String, i64)>> {
+    Ok(db
+        .execute_fetchall(sqlx::query(
+            "SELECT tag, count(*) FROM version_tag GROUP BY tag ORDER BY tag",
+        ))
+        .await?
+        .iter()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect())
+}
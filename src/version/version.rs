@@ -1,16 +1,43 @@
-use crate::state::database::DatabaseModel;
+use crate::project::project::{refresh_projects, store_project_of};
+use crate::state::database::{Database, DatabaseModel, SqliteQuery};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use sqlx::{sqlite::SqliteRow, FromRow, Row};
+use std::collections::HashMap;
 use std::fmt::{self, Display};
-use std::path::PathBuf;
-use std::time::SystemTime;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
 
-#[derive(Debug)]
+const BACKUP_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H%M%S";
+
+#[derive(Debug, Serialize)]
 pub struct ProjectVersion {
     pub path: PathBuf,
     pub name: String,
+    /// The id of the stored project the version is in, once it has been stored.
+    pub project_id: Option<i64>,
+    pub project_path: Option<PathBuf>,
+    pub root: Option<String>,
+    #[serde(serialize_with = "serialize_time")]
     pub created_at: SystemTime,
+    #[serde(serialize_with = "serialize_time")]
     pub accessed_at: SystemTime,
+    #[serde(serialize_with = "serialize_time")]
     pub modified_at: SystemTime,
     pub description: Option<String>,
+    pub backup_of: Option<String>,
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub backed_up_at: Option<SystemTime>,
+    /// The file's size in bytes when it was last scanned.
+    pub size: Option<u64>,
+    /// A SHA-256 of the file's contents when it was last scanned.
+    pub content_hash: Option<String>,
+    /// When a scan stopped finding the file.
+    #[serde(serialize_with = "serialize_optional_time")]
+    pub removed_at: Option<SystemTime>,
 }
 
 impl ProjectVersion {
@@ -24,53 +51,476 @@ impl ProjectVersion {
     ) -> ProjectVersion {
         match name {
             Some(name) => ProjectVersion {
+                project_id: None,
+                project_path: path.parent().map(Path::to_path_buf),
+                root: None,
                 path,
                 name,
                 created_at,
                 accessed_at,
                 modified_at,
                 description,
+                backup_of: None,
+                backed_up_at: None,
+                size: None,
+                content_hash: None,
+                removed_at: None,
             },
             None => {
-                let mut name = String::from(path.as_path().file_name().unwrap().to_str().unwrap());
-                let parts: Vec<&str> = name.split(".").collect();
-                name = String::from(parts[0]);
+                // Only the extension is dropped, so `My.Song.als` is named `My.Song`.
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
                 ProjectVersion {
+                    project_id: None,
+                    project_path: path.parent().map(Path::to_path_buf),
+                    root: None,
                     path,
                     name,
                     created_at,
                     accessed_at,
                     modified_at,
                     description,
+                    backup_of: None,
+                    backed_up_at: None,
+                    size: None,
+                    content_hash: None,
+                    removed_at: None,
                 }
             }
         }
     }
+
+    /// Builds a version for a single `.als` from its file metadata.
+    pub fn from_path(path: &Path) -> anyhow::Result<ProjectVersion> {
+        let metadata = fs::metadata(path)?;
+        let modified_at = metadata.modified()?;
+        // Not every filesystem records creation or access times.
+        let mut version = ProjectVersion::new(
+            path.to_path_buf(),
+            metadata.created().unwrap_or(modified_at),
+            metadata.accessed().unwrap_or(modified_at),
+            modified_at,
+            None,
+            None,
+        );
+        version.size = Some(metadata.len());
+        Ok(version)
+    }
+
+    /// Whether the file still has the size and modified time it had when `self` was stored, in
+    /// which case it's assumed to be unchanged without reading it.
+    pub fn is_unchanged(&self, size: Option<u64>, modified_at: &SystemTime) -> bool {
+        self.removed_at.is_none()
+            && self.content_hash.is_some()
+            && self.size.is_some()
+            && self.size == size
+            && to_epoch_millis(&self.modified_at) == to_epoch_millis(modified_at)
+    }
+
+    /// Builds a version from one of the copies Live writes into `<Project>/Backup/`, e.g.
+    /// `Song [2023-06-01 153012].als`. Returns None if the file name doesn't carry a backup timestamp.
+    pub fn from_backup(
+        path: PathBuf,
+        created_at: SystemTime,
+        accessed_at: SystemTime,
+        modified_at: SystemTime,
+    ) -> Option<ProjectVersion> {
+        let (base_name, backed_up_at) = parse_backup_name(path.file_stem()?.to_str()?)?;
+        let mut version =
+            ProjectVersion::new(path, created_at, accessed_at, modified_at, None, None);
+        version.name = String::from(version.path.file_stem()?.to_str()?);
+        // Live keeps backups in `<Project>/Backup/`; other copies don't say which project they're from.
+        version.project_path = version
+            .path
+            .parent()
+            .filter(|parent| parent.file_name().is_some_and(|name| name == "Backup"))
+            .and_then(Path::parent)
+            .map(Path::to_path_buf);
+        version.backup_of = Some(base_name);
+        version.backed_up_at = Some(backed_up_at);
+        Some(version)
+    }
+}
+
+/// Splits a backup file stem like `Song [2023-06-01 153012]` into the name of the version it was
+/// taken from and the time Live wrote it.
+fn parse_backup_name(stem: &str) -> Option<(String, SystemTime)> {
+    let stem = stem.strip_suffix(']')?;
+    let (base_name, timestamp) = stem.rsplit_once(" [")?;
+    let naive = NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT).ok()?;
+    let local = Local.from_local_datetime(&naive).earliest()?;
+    Some((String::from(base_name), SystemTime::from(local)))
+}
+
+/// A hex SHA-256 of the file at `path`.
+pub fn hash_file(path: &Path) -> anyhow::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// A hex SHA-256 of contents already read into memory.
+pub fn hash_contents(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Timestamps are stored as integer milliseconds since the epoch.
+pub fn to_epoch_millis(time: &SystemTime) -> i64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+pub fn from_epoch_millis(millis: i64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(millis.max(0) as u64)
+}
+
+/// Parses a `YYYY-MM-DD` date as local midnight.
+pub fn parse_date(date: &str) -> anyhow::Result<SystemTime> {
+    let naive =
+        NaiveDateTime::parse_from_str(&format!("{} 000000", date), BACKUP_TIMESTAMP_FORMAT)?;
+    let local = Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| anyhow::anyhow!("{} isn't a valid local date", date))?;
+    Ok(SystemTime::from(local))
+}
+
+/// Formats a time in the local timezone for display, e.g. `2023-06-01 15:30`.
+pub fn format_time(time: &SystemTime) -> String {
+    DateTime::<Local>::from(*time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+/// Times are written to JSON as RFC 3339 in the local timezone.
+pub fn serialize_time<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&DateTime::<Local>::from(*time).to_rfc3339())
+}
+
+pub fn serialize_optional_time<S: Serializer>(
+    time: &Option<SystemTime>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serialize_time(time, serializer),
+        None => serializer.serialize_none(),
+    }
 }
 
 impl DatabaseModel for ProjectVersion {
-    fn create_table_query(&self) -> String {
-        format!("CREATE TABLE IF NOT EXISTS project_version (path varchar(300), name varchar(150), created_at timestamp, accessed_at timestamp, modified_at timestamp, description varchar(300), primary key (name, created_at))")
+    /// Rescanning a version keeps its description, and its root while the root is unknown. A
+    /// version that's stored again is no longer removed. The version references the project stored
+    /// at its project path, which has to be stored first.
+    fn insert_query(&self) -> SqliteQuery<'_> {
+        sqlx::query("INSERT INTO project_version (path, name, project_path, root, created_at, accessed_at, modified_at, description, backup_of, backed_up_at, size, content_hash, project_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, (SELECT id FROM project WHERE path = ?3)) ON CONFLICT (path) DO UPDATE SET name = excluded.name, project_path = excluded.project_path, project_id = excluded.project_id, root = coalesce(excluded.root, project_version.root), created_at = excluded.created_at, accessed_at = excluded.accessed_at, modified_at = excluded.modified_at, description = coalesce(excluded.description, project_version.description), backup_of = excluded.backup_of, backed_up_at = excluded.backed_up_at, size = excluded.size, content_hash = excluded.content_hash, removed_at = NULL")
+            .bind(self.path.to_string_lossy().into_owned())
+            .bind(&self.name)
+            .bind(
+                self.project_path
+                    .as_ref()
+                    .map(|path| path.to_string_lossy().into_owned()),
+            )
+            .bind(&self.root)
+            .bind(to_epoch_millis(&self.created_at))
+            .bind(to_epoch_millis(&self.accessed_at))
+            .bind(to_epoch_millis(&self.modified_at))
+            .bind(&self.description)
+            .bind(&self.backup_of)
+            .bind(self.backed_up_at.as_ref().map(to_epoch_millis))
+            .bind(self.size.map(|size| size as i64))
+            .bind(&self.content_hash)
     }
+}
 
-    fn insert_into_query(&self) -> String {
-        format!("INSERT OR REPLACE INTO project_version (path, name, created_at, accessed_at, modified_at, description) VALUES ('{}', '{}', '{:?}', '{:?}', '{:?}', '{:?}')",
-        self.path.to_str().unwrap().replace("'", "\""),
-        self.name.replace("'", "\""),
-        self.created_at.duration_since(SystemTime::UNIX_EPOCH).unwrap(),
-        self.accessed_at.duration_since(SystemTime::UNIX_EPOCH).unwrap(),
-        self.modified_at.duration_since(SystemTime::UNIX_EPOCH).unwrap(),
-        self.description
-        )
+impl<'r> FromRow<'r, SqliteRow> for ProjectVersion {
+    fn from_row(row: &'r SqliteRow) -> Result<Self, sqlx::Error> {
+        Ok(ProjectVersion {
+            path: PathBuf::from(row.try_get::<String, &str>("path")?),
+            name: row.try_get("name")?,
+            project_id: row.try_get("project_id")?,
+            project_path: row
+                .try_get::<Option<String>, &str>("project_path")?
+                .map(PathBuf::from),
+            root: row.try_get("root")?,
+            created_at: from_epoch_millis(row.try_get("created_at")?),
+            accessed_at: from_epoch_millis(row.try_get("accessed_at")?),
+            modified_at: from_epoch_millis(row.try_get("modified_at")?),
+            description: row.try_get("description")?,
+            backup_of: row.try_get("backup_of")?,
+            backed_up_at: row
+                .try_get::<Option<i64>, &str>("backed_up_at")?
+                .map(from_epoch_millis),
+            size: row
+                .try_get::<Option<i64>, &str>("size")?
+                .map(|size| size as u64),
+            content_hash: row.try_get("content_hash")?,
+            removed_at: row
+                .try_get::<Option<i64>, &str>("removed_at")?
+                .map(from_epoch_millis),
+        })
     }
 }
 
 impl Display for ProjectVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
-        write!(
+        if let (Some(backup_of), Some(backed_up_at)) = (&self.backup_of, &self.backed_up_at) {
+            return writeln!(
+                f,
+                "        ---> backup of {}: {}\n            backup path: {:?}",
+                backup_of,
+                format_time(backed_up_at),
+                self.path
+            );
+        }
+        writeln!(
             f,
-            "---> version: {}\n        version path: {:?}\n        created: {:?}\n        updated: {:?}\n        accessed: {:?}\n        description: {:?}",
-            self.name, self.path, self.created_at, self.modified_at, self.accessed_at, self.description
+            "---> version: {}\n        version path: {:?}\n        created: {}\n        updated: {}\n        accessed: {}\n        description: {}",
+            self.name,
+            self.path,
+            format_time(&self.created_at),
+            format_time(&self.modified_at),
+            format_time(&self.accessed_at),
+            self.description.as_deref().unwrap_or("")
+        )
+    }
+}
+
+/// Stores the version at `path` if it isn't indexed yet or was removed, so it can be described and
+/// tagged.
+pub async fn index_version(db: &mut Database, path: &Path) -> anyhow::Result<()> {
+    let row = db
+        .execute_fetchone(
+            sqlx::query(
+                "SELECT count(*) FROM project_version WHERE path = ? AND removed_at IS NULL",
+            )
+            .bind(path.to_string_lossy().into_owned()),
         )
+        .await?;
+    if row.get::<i64, usize>(0) == 0 {
+        let version = ProjectVersion::from_path(path)?;
+        touch_version(db, &version).await?;
+    }
+    Ok(())
+}
+
+/// Records the time a version was last saved, keeping its description and tags. The version's
+/// project is stored too if it's new.
+pub async fn touch_version(db: &mut Database, version: &ProjectVersion) -> anyhow::Result<()> {
+    let project_id = match &version.project_path {
+        Some(project_path) => {
+            Some(store_project_of(db, project_path, version.root.as_deref()).await?)
+        }
+        None => None,
+    };
+    db.execute_insert(version.insert_query()).await?;
+    if project_id.is_some() {
+        refresh_projects(db, project_id).await?;
+    }
+    Ok(())
+}
+
+/// Points the version stored at `from` at the file it was moved or renamed to, keeping its
+/// description, tags, lineage and search entries.
+pub async fn move_version(db: &mut Database, from: &Path, to: &Path) -> anyhow::Result<()> {
+    let from = from.to_string_lossy().into_owned();
+    let to = to.to_string_lossy().into_owned();
+    for statement in [
+        "UPDATE project_version SET path = ?2 WHERE path = ?1",
+        "UPDATE version_tag SET version_path = ?2 WHERE version_path = ?1",
+        "UPDATE version_lineage SET version_path = ?2 WHERE version_path = ?1",
+        "UPDATE version_lineage SET parent_path = ?2 WHERE parent_path = ?1",
+        "UPDATE search_entry SET version_path = ?2 WHERE version_path = ?1",
+        "UPDATE preset_use SET version_path = ?2 WHERE version_path = ?1",
+    ] {
+        db.execute_insert(sqlx::query(statement).bind(&from).bind(&to))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Sets the commit-style message of the version at `path`.
+pub async fn describe_version(db: &mut Database, path: &Path, message: &str) -> anyhow::Result<()> {
+    index_version(db, path).await?;
+    db.execute_insert(
+        sqlx::query("UPDATE project_version SET description = ? WHERE path = ?")
+            .bind(message)
+            .bind(path.to_string_lossy().into_owned()),
+    )
+    .await?;
+    Ok(())
+}
+
+/// The stored version at `path`, if it has been indexed.
+pub async fn select_version(
+    db: &mut Database,
+    path: &Path,
+) -> anyhow::Result<Option<ProjectVersion>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT * FROM project_version WHERE path = ?")
+                .bind(path.to_string_lossy().into_owned()),
+        )
+        .await?;
+    Ok(rows.first().map(ProjectVersion::from_row).transpose()?)
+}
+
+/// The version that `version` is most likely a later save of: its latest backup, or for a backup
+/// the one taken before it. Versions without backups fall back to their inferred lineage parent.
+pub async fn select_previous_version(
+    db: &mut Database,
+    version: &ProjectVersion,
+) -> anyhow::Result<Option<ProjectVersion>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT * FROM project_version WHERE removed_at IS NULL AND backup_of = ?1 AND project_path IS ?2 AND path != ?3 AND (?4 IS NULL OR backed_up_at < ?4) ORDER BY backed_up_at DESC LIMIT 1")
+                .bind(version.backup_of.as_ref().unwrap_or(&version.name))
+                .bind(
+                    version
+                        .project_path
+                        .as_ref()
+                        .map(|path| path.to_string_lossy().into_owned()),
+                )
+                .bind(version.path.to_string_lossy().into_owned())
+                .bind(version.backed_up_at.as_ref().map(to_epoch_millis)),
+        )
+        .await?;
+    if let Some(row) = rows.first() {
+        return Ok(Some(ProjectVersion::from_row(row)?));
+    }
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT project_version.* FROM version_lineage JOIN project_version ON project_version.path = version_lineage.parent_path WHERE version_lineage.version_path = ?")
+                .bind(version.path.to_string_lossy().into_owned()),
+        )
+        .await?;
+    Ok(rows.first().map(ProjectVersion::from_row).transpose()?)
+}
+
+/// Filters for selecting stored versions. Versions are returned most recently modified first.
+#[derive(Debug, Default)]
+pub struct VersionQuery {
+    pub project_path: Option<PathBuf>,
+    pub root: Option<String>,
+    pub tag: Option<String>,
+    pub modified_since: Option<SystemTime>,
+    pub modified_until: Option<SystemTime>,
+    pub include_backups: bool,
+    /// Also select versions whose files a scan no longer found.
+    pub include_removed: bool,
+}
+
+pub async fn select_versions(
+    db: &mut Database,
+    query: &VersionQuery,
+) -> anyhow::Result<Vec<ProjectVersion>> {
+    let rows = db
+        .execute_fetchall(
+            sqlx::query("SELECT * FROM project_version WHERE (?1 IS NULL OR project_path = ?1) AND (?2 IS NULL OR path IN (SELECT version_path FROM version_tag WHERE tag = ?2)) AND (?3 IS NULL OR modified_at >= ?3) AND (?4 IS NULL OR modified_at < ?4) AND (?5 OR backup_of IS NULL) AND (?6 IS NULL OR root = ?6) AND (?7 OR removed_at IS NULL) ORDER BY modified_at DESC")
+                .bind(
+                    query
+                        .project_path
+                        .as_ref()
+                        .map(|path| path.to_string_lossy().into_owned()),
+                )
+                .bind(&query.tag)
+                .bind(query.modified_since.as_ref().map(to_epoch_millis))
+                .bind(query.modified_until.as_ref().map(to_epoch_millis))
+                .bind(query.include_backups)
+                .bind(&query.root)
+                .bind(query.include_removed),
+        )
+        .await?;
+    Ok(rows
+        .iter()
+        .map(ProjectVersion::from_row)
+        .collect::<Result<Vec<ProjectVersion>, sqlx::Error>>()?)
+}
+
+/// The tags of every tagged version, keyed by version path.
+pub async fn select_version_tags(
+    db: &mut Database,
+) -> anyhow::Result<HashMap<String, Vec<String>>> {
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for row in db
+        .execute_fetchall(sqlx::query(
+            "SELECT version_path, tag FROM version_tag ORDER BY version_path, tag",
+        ))
+        .await?
+    {
+        tags.entry(row.get(0)).or_default().push(row.get(1));
+    }
+    Ok(tags)
+}
+
+/// A stored version as shown in listings, with its tags.
+#[derive(Serialize)]
+pub struct VersionListing {
+    #[serde(flatten)]
+    pub version: ProjectVersion,
+    pub tags: Vec<String>,
+}
+
+impl Display for VersionListing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "{} ({})",
+            self.version.name,
+            format_time(&self.version.modified_at)
+        )?;
+        if !self.tags.is_empty() {
+            write!(f, " [{}]", self.tags.join(", "))?;
+        }
+        if let Some(removed_at) = &self.version.removed_at {
+            write!(f, " (removed {})", format_time(removed_at))?;
+        }
+        if let Some(description) = &self.version.description {
+            write!(f, ": {}", description)?;
+        }
+        write!(f, "\n    {}", self.version.path.display())
+    }
+}
+
+/// Lists stored versions matching `query` along with their tags.
+pub async fn list_versions(
+    db: &mut Database,
+    query: &VersionQuery,
+) -> anyhow::Result<Vec<VersionListing>> {
+    let versions = select_versions(db, query).await?;
+    let mut tags = select_version_tags(db).await?;
+    Ok(versions
+        .into_iter()
+        .map(|version| {
+            let tags = tags
+                .remove(version.path.to_string_lossy().as_ref())
+                .unwrap_or_default();
+            VersionListing { version, tags }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::version::tag::VersionTag;
+
+    #[tokio::test]
+    async fn tags_containing_commas_are_kept_whole() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut db = Database::new(&dir.path().join("test.db")).await;
+        let path = Path::new("/sessions/Song Project/Song.als");
+        for tag in ["mix, v2", "mixdown"] {
+            db.execute_insert(VersionTag::new(path, tag).insert_query())
+                .await
+                .unwrap();
+        }
+        let tags = select_version_tags(&mut db).await.unwrap();
+        assert_eq!(
+            tags[path.to_string_lossy().as_ref()],
+            vec![String::from("mix, v2"), String::from("mixdown")]
+        );
     }
 }
@@ -0,0 +1,265 @@
+use crate::cli::output::status;
+use crate::config::config::Config;
+use crate::library::usage::record_preset_uses;
+use crate::parser::als::AbletonXmlParser;
+use crate::project::project::{is_als_file, AbletonProjectDirectory, BACKUP_DIRECTORY};
+use crate::search::search::index_contents;
+use crate::state::database::{Database, DatabaseModel};
+use crate::version::version::{hash_contents, touch_version, ProjectVersion};
+use chrono::Local;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tokio::time;
+
+/// Live writes a temporary file and renames it over the set, so a save shows up as a burst of
+/// events. A set is only snapshotted once it has been quiet for this long.
+const SAVE_DEBOUNCE: Duration = Duration::from_secs(2);
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Watches the configured session roots and snapshots every `.als` that Live saves into them.
+pub struct SessionWatcher<'a> {
+    config: &'a Config,
+    pending: HashMap<PathBuf, Instant>,
+    last_snapshot_hashes: HashMap<PathBuf, String>,
+}
+
+impl<'a> SessionWatcher<'a> {
+    pub fn new(config: &'a Config) -> SessionWatcher<'a> {
+        SessionWatcher {
+            config,
+            pending: HashMap::new(),
+            last_snapshot_hashes: HashMap::new(),
+        }
+    }
+
+    /// Runs until the watcher's channel closes, recording a snapshot and a `project_version` row
+    /// for each save.
+    pub async fn run(&mut self, db: &mut Database) -> anyhow::Result<()> {
+        // notify calls the handler from its own thread, so events are passed over a channel that
+        // can be awaited without blocking the runtime.
+        let (sender, mut receiver) = mpsc::unbounded_channel::<notify::Result<Event>>();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = sender.send(event);
+        })?;
+        for root in &self.config.session_roots.value {
+            match watcher.watch(&root.path, RecursiveMode::Recursive) {
+                Ok(()) => status!("watching {} for saves", root),
+                Err(error) => status!("couldn't watch {}: {}", root, error),
+            }
+        }
+        loop {
+            match time::timeout(POLL_INTERVAL, receiver.recv()).await {
+                Ok(Some(Ok(event))) => self.record_event(event),
+                Ok(Some(Err(error))) => status!("watch error: {}", error),
+                Ok(None) => return Ok(()),
+                Err(_) => {}
+            }
+            for path in self.settled_saves() {
+                if let Err(error) = self.snapshot(db, &path).await {
+                    status!("couldn't snapshot {:?}: {}", path, error);
+                }
+            }
+        }
+    }
+
+    fn record_event(&mut self, event: Event) {
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            return;
+        }
+        for path in event.paths {
+            if is_watched_set(&path) && !self.config.is_ignored(&path) {
+                self.pending.insert(path, Instant::now());
+            }
+        }
+    }
+
+    /// Sets whose last event is older than the debounce window.
+    fn settled_saves(&mut self) -> Vec<PathBuf> {
+        let settled: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, last_event)| last_event.elapsed() >= SAVE_DEBOUNCE)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &settled {
+            self.pending.remove(path);
+        }
+        settled
+    }
+
+    async fn snapshot(&mut self, db: &mut Database, path: &Path) -> anyhow::Result<()> {
+        if !path.is_file() {
+            return Ok(());
+        }
+        // notify reports paths under the root as it was watched, and scans index canonical ones.
+        let path = &fs::canonicalize(path)?;
+        let contents = fs::read(path)?;
+        let hash = hash_contents(&contents);
+        if self.last_snapshot_hashes.get(path) == Some(&hash) {
+            return Ok(());
+        }
+        let mut version = ProjectVersion::from_path(path)?;
+        version.content_hash = Some(hash.clone());
+        version.root = self.config.root_of(path).map(|root| root.label.clone());
+        let directory = self
+            .config
+            .snapshot_directory
+            .value
+            .join(snapshot_directory_name(path.parent().unwrap()));
+        fs::create_dir_all(&directory)?;
+        // Snapshots use the same naming as Live's own backups so they're read back the same way.
+        let snapshot_path = directory.join(format!(
+            "{} [{}].als",
+            version.name,
+            Local::now().format("%Y-%m-%d %H%M%S")
+        ));
+        fs::write(&snapshot_path, &contents)?;
+        let snapshot_metadata = fs::metadata(&snapshot_path)?;
+        let modified_at = snapshot_metadata.modified()?;
+        // Not every filesystem records creation or access times.
+        let mut snapshot = ProjectVersion::from_backup(
+            fs::canonicalize(&snapshot_path)?,
+            snapshot_metadata.created().unwrap_or(modified_at),
+            snapshot_metadata.accessed().unwrap_or(modified_at),
+            modified_at,
+        )
+        .expect("snapshot name to carry a timestamp");
+        snapshot.project_path = version.project_path.clone();
+        snapshot.root = version.root.clone();
+        snapshot.size = version.size;
+        snapshot.content_hash = Some(hash.clone());
+        touch_version(db, &version).await?;
+        db.execute_insert(snapshot.insert_query()).await?;
+        match AbletonXmlParser::new().read_contents_of(path) {
+            Ok(set_contents) => {
+                index_contents(db, &version, &set_contents).await?;
+                record_preset_uses(db, &version, &set_contents).await?;
+            }
+            Err(error) => status!("couldn't read {}: {:#}", path.display(), error),
+        }
+        self.last_snapshot_hashes.insert(path.to_path_buf(), hash);
+        status!(
+            "snapshotted {} to {}",
+            path.display(),
+            snapshot_path.display()
+        );
+        Ok(())
+    }
+}
+
+/// Snapshots of a project are kept in a directory named after it, with a hash of its path so that
+/// projects with the same name in different roots don't share one.
+fn snapshot_directory_name(project_path: &Path) -> String {
+    let path_hash = hash_contents(project_path.to_string_lossy().as_bytes());
+    format!(
+        "{} {}",
+        AbletonProjectDirectory::name_from_path(project_path),
+        &path_hash[..8]
+    )
+}
+
+/// Live's own backups are indexed from the project's Backup folder, so only top-level sets are
+/// snapshotted.
+fn is_watched_set(path: &Path) -> bool {
+    is_als_file(path)
+        && path
+            .parent()
+            .and_then(Path::file_name)
+            .is_none_or(|parent| parent != BACKUP_DIRECTORY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::config::ConfigFlags;
+    use notify::event::{ModifyKind, RemoveKind};
+    use sqlx::Row;
+
+    #[test]
+    fn same_name_projects_snapshot_to_different_directories() {
+        let home = snapshot_directory_name(Path::new("/music/home/Song Project"));
+        let studio = snapshot_directory_name(Path::new("/music/studio/Song Project"));
+        assert!(home.starts_with("Song "));
+        assert_ne!(home, studio);
+    }
+
+    #[test]
+    fn saves_settle_once_their_events_stop() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_file = dir.path().join("config.toml");
+        fs::write(&config_file, "ignore_patterns = [\"*old*\"]").unwrap();
+        let flags = ConfigFlags {
+            config_file: Some(config_file),
+            ..ConfigFlags::default()
+        };
+        let config = Config::load_from(&flags, &|_| None).unwrap();
+        let mut watcher = SessionWatcher::new(&config);
+        let project = Path::new("/sessions/Song Project");
+        let event = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(project.join("Song.als"))
+            .add_path(project.join("Backup/Song [2023-06-01 153012].als"))
+            .add_path(project.join("Song old.als"))
+            .add_path(project.join("Samples/Recorded/take.wav"));
+        watcher.record_event(event.clone());
+        watcher.record_event(
+            Event::new(EventKind::Remove(RemoveKind::Any)).add_path(project.join("Song 2.als")),
+        );
+        assert_eq!(
+            watcher.pending.keys().collect::<Vec<_>>(),
+            vec![&project.join("Song.als")]
+        );
+        assert!(watcher.settled_saves().is_empty());
+
+        // A save is only picked up once it's been quiet for the debounce window.
+        let quiet_since = Instant::now() - SAVE_DEBOUNCE;
+        watcher
+            .pending
+            .insert(project.join("Song.als"), quiet_since);
+        assert_eq!(watcher.settled_saves(), vec![project.join("Song.als")]);
+        assert!(watcher.pending.is_empty());
+        watcher.record_event(event);
+        assert!(watcher.settled_saves().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn snapshots_index_the_set_under_its_canonical_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = fs::canonicalize(dir.path()).unwrap();
+        let project = dir.join("sessions/Song Project");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("Song.als"), "a set").unwrap();
+        std::os::unix::fs::symlink(dir.join("sessions"), dir.join("link")).unwrap();
+        let config_file = dir.join("config.toml");
+        fs::write(&config_file, "snapshot_directory = \"snapshots\"").unwrap();
+        let flags = ConfigFlags {
+            config_file: Some(config_file),
+            ..ConfigFlags::default()
+        };
+        let config = Config::load_from(&flags, &|_| None).unwrap();
+        let mut db = Database::new(&dir.join("index.db")).await;
+        let mut watcher = SessionWatcher::new(&config);
+        watcher
+            .snapshot(&mut db, &dir.join("link/Song Project/Song.als"))
+            .await
+            .unwrap();
+
+        let paths: Vec<String> = db
+            .execute_fetchall(sqlx::query(
+                "SELECT path FROM project_version WHERE backup_of IS NULL",
+            ))
+            .await
+            .unwrap()
+            .iter()
+            .map(|row| row.get(0))
+            .collect();
+        assert_eq!(
+            paths,
+            vec![project.join("Song.als").to_string_lossy().into_owned()]
+        );
+    }
+}